@@ -105,7 +105,7 @@ pub struct State {{
     pub next_state: StateId,   // Next state
 }}
 
-#[allow(dead_code)]
+#[allow(dead_code, clippy::large_const_arrays)]
 pub const STATES: [State; {}] = [
 "#,
                 states.len()
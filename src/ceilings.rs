@@ -0,0 +1,347 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::map::{Linedef, Map, Sector};
+use crate::planes::{find_highest_ceiling_surrounding, move_plane, Direction, MoveResult, Plane};
+use crate::thinkers::{get_thinkers_mut, is_sector_moving, Thinker};
+
+// Ceilings that move up or down once, and crushers that keep going down and up until
+// they are stopped. The ceilings of the sectors with the linedef's tag are moved.
+// https://doomwiki.org/wiki/Crusher
+
+const CEILING_SPEED: i16 = 1; // Units per tic
+const FAST_CEILING_SPEED: i16 = CEILING_SPEED * 2; // Units per tic of the fast crushers
+const CRUSH_SLOWDOWN: u32 = 8; // A slowed down crusher moves once every this many tics
+const CRUSH_GAP: i16 = 8; // A crusher stops this far above the floor
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CeilingType {
+    LowerToFloor,        // Lower to the floor
+    RaiseToHighest,      // Raise to the highest surrounding ceiling
+    LowerAndCrush,       // Lower to 8 above the floor
+    CrushAndRaise,       // Crusher that slows down when it hits something
+    FastCrushAndRaise,   // Crusher that doesn't slow down
+    SilentCrushAndRaise, // Crusher that doesn't make a sound
+}
+
+impl CeilingType {
+    fn is_crusher(&self) -> bool {
+        matches!(
+            self,
+            CeilingType::CrushAndRaise
+                | CeilingType::FastCrushAndRaise
+                | CeilingType::SilentCrushAndRaise
+        )
+    }
+}
+
+// See T_MoveCeiling in p_ceilng.c
+#[derive(Debug)]
+pub struct MovingCeiling {
+    sector: Rc<RefCell<Sector>>, // The sector to move the ceiling of
+    ceiling_type: CeilingType,   // What the ceiling does
+    tag: i16,                    // Tag of the linedef that started it
+    bottom_height: i16,          // Ceiling height at the bottom
+    top_height: i16,             // Ceiling height at the top
+    speed: i16,                  // Units per tic
+    crush: bool,                 // Keep moving down when the player is in the way
+    slow: bool,                  // Crushing something, moving 8 times as slow
+    direction: Direction,        // Up or down
+    in_stasis: bool,             // A stopped crusher
+    tics: u32,                   // Tics since it started, for moving slowly
+    last_height: i16,            // Ceiling height before the last move
+    done: bool,                  // The ceiling reached the destination
+}
+
+impl MovingCeiling {
+    // See EV_DoCeiling in p_ceilng.c
+    pub fn new(
+        map: &Map,
+        sector: Rc<RefCell<Sector>>,
+        ceiling_type: CeilingType,
+        tag: i16,
+    ) -> MovingCeiling {
+        let (floor_height, ceiling_height, highest_ceiling) = {
+            let sector = sector.borrow();
+            (
+                sector.floor_height,
+                sector.ceiling_height,
+                find_highest_ceiling_surrounding(map, &sector),
+            )
+        };
+
+        let (bottom_height, top_height, direction, speed) = match ceiling_type {
+            CeilingType::LowerToFloor => {
+                (floor_height, ceiling_height, Direction::Down, CEILING_SPEED)
+            }
+            CeilingType::RaiseToHighest => {
+                (floor_height, highest_ceiling, Direction::Up, CEILING_SPEED)
+            }
+            CeilingType::FastCrushAndRaise => (
+                floor_height + CRUSH_GAP,
+                ceiling_height,
+                Direction::Down,
+                FAST_CEILING_SPEED,
+            ),
+            _ => (
+                floor_height + CRUSH_GAP,
+                ceiling_height,
+                Direction::Down,
+                CEILING_SPEED,
+            ),
+        };
+
+        MovingCeiling {
+            sector,
+            ceiling_type,
+            tag,
+            bottom_height,
+            top_height,
+            speed,
+            crush: ceiling_type.is_crusher(),
+            slow: false,
+            direction,
+            in_stasis: false,
+            tics: 0,
+            last_height: ceiling_height,
+            done: false,
+        }
+    }
+
+    fn move_ceiling(&mut self, destination: i16) -> MoveResult {
+        move_plane(
+            &self.sector,
+            Plane::Ceiling,
+            self.speed,
+            destination,
+            self.direction,
+        )
+    }
+}
+
+impl Thinker for MovingCeiling {
+    fn mutate(&mut self) {
+        self.last_height = self.sector.borrow().ceiling_height;
+        self.tics += 1;
+
+        if self.in_stasis {
+            return;
+        }
+
+        match self.direction {
+            Direction::Up => {
+                if self.move_ceiling(self.top_height) == MoveResult::PastDestination {
+                    if self.ceiling_type.is_crusher() {
+                        self.direction = Direction::Down;
+                    } else {
+                        self.done = true;
+                    }
+                }
+            }
+
+            Direction::Down => {
+                if self.slow && !self.tics.is_multiple_of(CRUSH_SLOWDOWN) {
+                    return;
+                }
+
+                if self.move_ceiling(self.bottom_height) == MoveResult::PastDestination {
+                    if self.ceiling_type.is_crusher() {
+                        self.slow = false;
+                        self.direction = Direction::Up;
+                    } else {
+                        self.done = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn moved_sector(&self) -> Option<&Rc<RefCell<Sector>>> {
+        Some(&self.sector)
+    }
+
+    // A crusher keeps going down on the player, slowing down unless it's a fast one.
+    // Other ceilings stay where they were until the player moves away.
+    fn crushed(&mut self) {
+        if self.in_stasis || self.direction != Direction::Down {
+            return;
+        }
+
+        if !self.crush {
+            self.sector.borrow_mut().ceiling_height = self.last_height;
+            self.done = false;
+        }
+
+        match self.ceiling_type {
+            CeilingType::CrushAndRaise
+            | CeilingType::SilentCrushAndRaise
+            | CeilingType::LowerAndCrush => self.slow = true,
+            _ => {}
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+// Move the ceiling of each sector with the linedef's tag that isn't moving yet.
+// Stopped crushers with the tag start again. Returns true if a ceiling was started.
+// See EV_DoCeiling in p_ceilng.c.
+pub fn do_ceiling(
+    map: &Map,
+    linedef: &Linedef,
+    ceiling_type: CeilingType,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+) -> bool {
+    if ceiling_type.is_crusher() {
+        // See P_ActivateInStasisCeiling in p_ceilng.c
+        for ceiling in get_thinkers_mut::<MovingCeiling>(thinkers) {
+            if ceiling.tag == linedef.sector_tag {
+                ceiling.in_stasis = false;
+            }
+        }
+    }
+
+    let mut started = false;
+
+    for sector in map.get_tagged_sectors(linedef.sector_tag) {
+        if is_sector_moving(thinkers, &sector.borrow()) {
+            continue;
+        }
+
+        let ceiling = MovingCeiling::new(map, sector, ceiling_type, linedef.sector_tag);
+        thinkers.push(Box::new(ceiling));
+        started = true;
+    }
+
+    started
+}
+
+// Stop the crushers with the linedef's tag, until a crusher special with the same
+// tag starts them again. Returns true if a crusher was stopped.
+// See EV_CeilingCrushStop in p_ceilng.c.
+pub fn stop_crushers(linedef: &Linedef, thinkers: &mut [Box<dyn Thinker>]) -> bool {
+    let mut stopped = false;
+
+    for ceiling in get_thinkers_mut::<MovingCeiling>(thinkers) {
+        if ceiling.tag == linedef.sector_tag && !ceiling.in_stasis {
+            ceiling.in_stasis = true;
+            stopped = true;
+        }
+    }
+
+    stopped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_maps::{run_thinkers, tagged_two_rooms};
+
+    // The west room has a ceiling at 128, the east room a floor at 16 and a ceiling
+    // at 112. Linedef 1 has the tag of the east room.
+    fn ceiling_height(map: &Map) -> i16 {
+        map.sectors[1].borrow().ceiling_height
+    }
+
+    #[test]
+    fn test_lower_and_raise_ceiling() {
+        let map = tagged_two_rooms();
+        let linedef = &map.linedefs[1];
+        let mut thinkers = Vec::new();
+
+        assert!(do_ceiling(
+            &map,
+            linedef,
+            CeilingType::LowerToFloor,
+            &mut thinkers
+        ));
+        run_thinkers(&mut thinkers, 10);
+        assert_eq!(ceiling_height(&map), 102);
+        run_thinkers(&mut thinkers, 87);
+        assert_eq!(ceiling_height(&map), 16);
+        assert!(thinkers.is_empty());
+
+        // Up to the west room's ceiling
+        assert!(do_ceiling(
+            &map,
+            linedef,
+            CeilingType::RaiseToHighest,
+            &mut thinkers
+        ));
+        run_thinkers(&mut thinkers, 113);
+        assert_eq!(ceiling_height(&map), 128);
+        assert!(thinkers.is_empty());
+    }
+
+    #[test]
+    fn test_crusher() {
+        let map = tagged_two_rooms();
+        let linedef = &map.linedefs[1];
+        let mut thinkers = Vec::new();
+
+        // A crusher goes down to 8 above the floor and back up, and keeps going
+        assert!(do_ceiling(
+            &map,
+            linedef,
+            CeilingType::FastCrushAndRaise,
+            &mut thinkers
+        ));
+        run_thinkers(&mut thinkers, 44);
+        assert_eq!(ceiling_height(&map), 24);
+        run_thinkers(&mut thinkers, 45);
+        assert_eq!(ceiling_height(&map), 112);
+        run_thinkers(&mut thinkers, 6);
+        assert_eq!(ceiling_height(&map), 102);
+
+        // Stopping it freezes it, until a crusher special starts it again
+        assert!(stop_crushers(linedef, &mut thinkers));
+        assert!(!stop_crushers(linedef, &mut thinkers));
+        run_thinkers(&mut thinkers, 10);
+        assert_eq!(ceiling_height(&map), 102);
+        assert!(!do_ceiling(
+            &map,
+            linedef,
+            CeilingType::CrushAndRaise,
+            &mut thinkers
+        ));
+        run_thinkers(&mut thinkers, 1);
+        assert_eq!(ceiling_height(&map), 100);
+        assert_eq!(thinkers.len(), 1);
+    }
+
+    #[test]
+    fn test_ceiling_crushed() {
+        let map = tagged_two_rooms();
+        let linedef = &map.linedefs[1];
+        let mut thinkers: Vec<Box<dyn Thinker>> = Vec::new();
+
+        // A crusher slows down to a unit every 8 tics while it crushes
+        assert!(do_ceiling(
+            &map,
+            linedef,
+            CeilingType::CrushAndRaise,
+            &mut thinkers
+        ));
+        run_thinkers(&mut thinkers, 4);
+        thinkers[0].crushed();
+        assert_eq!(ceiling_height(&map), 108);
+        run_thinkers(&mut thinkers, 4);
+        assert_eq!(ceiling_height(&map), 107);
+        run_thinkers(&mut thinkers, 8);
+        assert_eq!(ceiling_height(&map), 106);
+
+        // Other ceilings are held back
+        thinkers.clear();
+        assert!(do_ceiling(
+            &map,
+            linedef,
+            CeilingType::LowerToFloor,
+            &mut thinkers
+        ));
+        run_thinkers(&mut thinkers, 1);
+        thinkers[0].crushed();
+        assert_eq!(ceiling_height(&map), 106);
+    }
+}
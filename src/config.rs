@@ -0,0 +1,283 @@
+// Key bindings from a config file in the style of doom's default.cfg. Each line
+// is a name and a value separated by whitespace, e.g.
+//
+//   key_up      "w"
+//   key_strafe  "lalt,ralt"
+//   key_left    172
+//
+// A value is either a doom key code, like in the original default.cfg, or a quoted
+// comma-separated list of key names. An empty string unbinds the action. Lines with
+// other names are ignored, so that an original default.cfg can be used.
+use clap::ValueEnum;
+use sdl2::keyboard::Keycode;
+use std::error::Error;
+use std::fmt;
+
+use crate::input::{Action, KeyBindings};
+
+pub const DEFAULT_CONFIG_FILE: &str = "default.cfg";
+
+// Errors in a config file
+#[derive(Debug)]
+pub enum ConfigError {
+    InvalidKey {
+        line: usize, // Line number, starting at 1
+        key: String, // The key name or doom key code that isn't known
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::InvalidKey { line, key } => {
+                write!(f, "Unknown key {} on line {} of the config file", key, line)
+            }
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+// Key names that aren't a single character, and names for the characters that
+// can't be in a quoted list
+const KEY_NAMES: [(&str, Keycode); 23] = [
+    ("up", Keycode::Up),
+    ("down", Keycode::Down),
+    ("left", Keycode::Left),
+    ("right", Keycode::Right),
+    ("lalt", Keycode::LAlt),
+    ("ralt", Keycode::RAlt),
+    ("lshift", Keycode::LShift),
+    ("rshift", Keycode::RShift),
+    ("lctrl", Keycode::LCtrl),
+    ("rctrl", Keycode::RCtrl),
+    ("tab", Keycode::Tab),
+    ("space", Keycode::Space),
+    ("enter", Keycode::Return),
+    ("escape", Keycode::Escape),
+    ("backspace", Keycode::Backspace),
+    ("pageup", Keycode::PageUp),
+    ("pagedown", Keycode::PageDown),
+    ("home", Keycode::Home),
+    ("end", Keycode::End),
+    ("insert", Keycode::Insert),
+    ("delete", Keycode::Delete),
+    ("comma", Keycode::Comma), // Separates key names
+    ("quote", Keycode::Quotedbl),
+];
+
+const FUNCTION_KEYS: [Keycode; 12] = [
+    Keycode::F1,
+    Keycode::F2,
+    Keycode::F3,
+    Keycode::F4,
+    Keycode::F5,
+    Keycode::F6,
+    Keycode::F7,
+    Keycode::F8,
+    Keycode::F9,
+    Keycode::F10,
+    Keycode::F11,
+    Keycode::F12,
+];
+
+// Parse a key name, e.g. "w", "lalt" or "f12". Single characters are the key that
+// types the character, whatever the keyboard layout.
+fn parse_key_name(name: &str) -> Option<Keycode> {
+    let name = name.trim().to_ascii_lowercase();
+
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Keycode::from_i32(c as i32);
+    }
+
+    if let Some((_, keycode)) = KEY_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(*keycode);
+    }
+
+    let number: usize = name.strip_prefix('f')?.parse().ok()?;
+    FUNCTION_KEYS.get(number.checked_sub(1)?).copied()
+}
+
+// The name of a key in the config file
+fn key_name(keycode: Keycode) -> Option<String> {
+    if let Some((name, _)) = KEY_NAMES.iter().find(|(_, k)| *k == keycode) {
+        return Some(name.to_string());
+    }
+
+    if let Some(i) = FUNCTION_KEYS.iter().position(|k| *k == keycode) {
+        return Some(format!("f{}", i + 1));
+    }
+
+    let c = char::from_u32(keycode as u32)?;
+    (c.is_ascii_graphic()).then(|| c.to_string())
+}
+
+// Convert a doom key code, see doomdef.h. Doom doesn't tell the left and right
+// shift, control and alt keys apart.
+fn parse_doom_key_code(code: i32) -> Option<Vec<Keycode>> {
+    let keycodes = match code {
+        0xac => vec![Keycode::Left],
+        0xad => vec![Keycode::Up],
+        0xae => vec![Keycode::Right],
+        0xaf => vec![Keycode::Down],
+        0x9d => vec![Keycode::LCtrl, Keycode::RCtrl],
+        0xb6 => vec![Keycode::LShift, Keycode::RShift],
+        0xb8 => vec![Keycode::LAlt, Keycode::RAlt],
+        0xbb..=0xc4 => vec![FUNCTION_KEYS[(code - 0xbb) as usize]],
+        0xd7 => vec![Keycode::F11],
+        0xd8 => vec![Keycode::F12],
+        0x7f => vec![Keycode::Backspace],
+        _ if (0..0x7f).contains(&code) => vec![Keycode::from_i32(code)?],
+        _ => return None,
+    };
+
+    Some(keycodes)
+}
+
+// Parse the text of a config file and apply it to the default key bindings
+pub fn parse_config(text: &str) -> Result<KeyBindings, ConfigError> {
+    let mut key_bindings = KeyBindings::default();
+
+    for (i, line) in text.lines().enumerate() {
+        let Some((name, value)) = line.trim().split_once(char::is_whitespace) else {
+            continue;
+        };
+        let Some(action) = Action::from_config_name(name) else {
+            continue;
+        };
+
+        let value = value.trim();
+        let invalid_key = |key: &str| ConfigError::InvalidKey {
+            line: i + 1,
+            key: key.to_string(),
+        };
+
+        let keycodes = if let Some(names) = value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+        {
+            names
+                .split(',')
+                .filter(|name| !name.trim().is_empty())
+                .map(|name| parse_key_name(name).ok_or_else(|| invalid_key(name)))
+                .collect::<Result<Vec<Keycode>, ConfigError>>()?
+        } else {
+            value
+                .parse()
+                .ok()
+                .and_then(parse_doom_key_code)
+                .ok_or_else(|| invalid_key(value))?
+        };
+
+        key_bindings.bind(action, keycodes);
+    }
+
+    Ok(key_bindings)
+}
+
+// Write key bindings in the config file format, as a starting point for a config
+// file. Keys without a name are left out.
+pub fn write_config(key_bindings: &KeyBindings) -> String {
+    let mut text = String::new();
+
+    for action in Action::value_variants() {
+        let names: Vec<String> = key_bindings
+            .keys(*action)
+            .iter()
+            .filter_map(|keycode| key_name(*keycode))
+            .collect();
+        text.push_str(&format!(
+            "{:<24}\"{}\"\n",
+            action.config_name(),
+            names.join(",")
+        ));
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_empty_config() {
+        assert_eq!(parse_config("").unwrap(), KeyBindings::default());
+    }
+
+    #[test]
+    fn test_key_names() {
+        let key_bindings = parse_config(
+            "key_up \"w\"\n\
+             key_down \"S\"\n\
+             key_strafe \"lalt, RCtrl\"\n\
+             key_screenshot \"f11\"\n\
+             key_map \"\"\n\
+             sfx_volume 8\n",
+        )
+        .unwrap();
+
+        assert_eq!(key_bindings.keys(Action::Forward), &[Keycode::W]);
+        assert_eq!(key_bindings.keys(Action::Back), &[Keycode::S]);
+        assert_eq!(
+            key_bindings.keys(Action::Strafe),
+            &[Keycode::LAlt, Keycode::RCtrl]
+        );
+        assert_eq!(key_bindings.keys(Action::Screenshot), &[Keycode::F11]);
+        assert!(key_bindings.keys(Action::Automap).is_empty());
+
+        // Other actions keep the defaults
+        assert_eq!(key_bindings.keys(Action::TurnLeft), &[Keycode::Left]);
+        assert!(key_bindings.is_action_down(Action::Quit, &HashSet::from([Keycode::Q])));
+    }
+
+    #[test]
+    fn test_doom_key_codes() {
+        // The bindings in doom's default default.cfg
+        let key_bindings = parse_config(
+            "key_right\t\t174\n\
+             key_left\t\t172\n\
+             key_up\t\t173\n\
+             key_down\t\t175\n\
+             key_strafeleft\t\t44\n\
+             key_straferight\t\t46\n\
+             key_fire\t\t157\n\
+             key_use\t\t32\n\
+             key_strafe\t\t184\n\
+             key_speed\t\t182\n",
+        )
+        .unwrap();
+
+        assert_eq!(key_bindings, KeyBindings::default());
+    }
+
+    #[test]
+    fn test_write_config() {
+        let mut key_bindings = KeyBindings::default();
+        key_bindings.bind(Action::Forward, vec![Keycode::W, Keycode::Up]);
+        key_bindings.bind(Action::Screenshot, vec![Keycode::F5]);
+        key_bindings.bind(Action::Automap, vec![]);
+
+        let text = write_config(&key_bindings);
+        assert!(text.starts_with("key_up                  \"w,up\"\n"));
+        assert!(text.contains("key_strafe              \"lalt,ralt\"\n"));
+        assert!(text.contains("key_map                 \"\"\n"));
+        assert!(text.contains("key_strafeleft          \"comma\"\n"));
+
+        // Reading it back gives the same bindings
+        assert_eq!(parse_config(&text).unwrap(), key_bindings);
+    }
+
+    #[test]
+    fn test_invalid_keys() {
+        assert!(matches!(
+            parse_config("key_up \"w\"\nkey_down \"numpad\""),
+            Err(ConfigError::InvalidKey { line: 2, .. })
+        ));
+        assert!(parse_config("key_up 1000").is_err());
+        assert!(parse_config("key_up f13").is_err());
+        assert!(parse_config("key_up \"f13\"").is_err());
+    }
+}
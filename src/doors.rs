@@ -0,0 +1,394 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::map::{Linedef, Map, Sector};
+use crate::planes::{find_lowest_ceiling_surrounding, move_plane, Direction, MoveResult, Plane};
+use crate::thinkers::{get_sector_mover, is_sector_moving, Thinker};
+
+// Doors are sectors of which the ceiling goes up and down. A door is opened by
+// using its linedef, or by a linedef with the door's sector tag.
+// https://doomwiki.org/wiki/Door
+
+const DOOR_SPEED: i16 = 2; // Units per tic
+const BLAZE_DOOR_SPEED: i16 = DOOR_SPEED * 4; // Units per tic of the fast doors
+const DOOR_WAIT: i16 = 150; // Tics an opened door waits before closing
+const DOOR_TOP_GAP: i16 = 4; // An open door stops this far below the surrounding ceiling
+const CLOSE_30_WAIT: i16 = 30 * 35; // Tics a closed close30ThenOpen door waits
+const RAISE_IN_5_MINS_WAIT: i16 = 5 * 60 * 35; // Tics before a raiseIn5Mins door opens
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoorType {
+    Normal,          // Open, wait and close
+    Close30ThenOpen, // Close, wait 30 seconds and open
+    Close,           // Close and stay closed
+    Open,            // Open and stay open
+    RaiseIn5Mins,    // Wait 5 minutes, then open, wait and close
+    BlazeRaise,      // Fast normal door
+    BlazeOpen,       // Fast open door
+    BlazeClose,      // Fast close door
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DoorDirection {
+    Up,
+    Down,
+    Waiting,     // At the top, or closed for a close30ThenOpen door
+    InitialWait, // Before a raiseIn5Mins door opens
+}
+
+// See T_VerticalDoor in p_doors.c
+#[derive(Debug)]
+pub struct VerticalDoor {
+    sector: Rc<RefCell<Sector>>, // The sector to move the ceiling of
+    door_type: DoorType,         // What the door does
+    top_height: i16,             // Ceiling height of the open door
+    speed: i16,                  // Units per tic
+    direction: DoorDirection,    // Moving or waiting
+    top_wait: i16,               // Tics to wait at the top
+    top_countdown: i16,          // Counts down while waiting
+    last_height: i16,            // Ceiling height before the last move
+    done: bool,                  // The door stopped moving
+}
+
+impl VerticalDoor {
+    // Make a door that starts moving right away. See EV_DoDoor in p_doors.c.
+    pub fn new(map: &Map, sector: Rc<RefCell<Sector>>, door_type: DoorType) -> VerticalDoor {
+        let (top_height, ceiling_height) = {
+            let sector = sector.borrow();
+            (
+                find_lowest_ceiling_surrounding(map, &sector) - DOOR_TOP_GAP,
+                sector.ceiling_height,
+            )
+        };
+
+        let (top_height, direction) = match door_type {
+            DoorType::Close30ThenOpen => (ceiling_height, DoorDirection::Down),
+            DoorType::Close | DoorType::BlazeClose => (top_height, DoorDirection::Down),
+            _ => (top_height, DoorDirection::Up),
+        };
+
+        let speed = match door_type {
+            DoorType::BlazeRaise | DoorType::BlazeOpen | DoorType::BlazeClose => BLAZE_DOOR_SPEED,
+            _ => DOOR_SPEED,
+        };
+
+        VerticalDoor {
+            sector,
+            door_type,
+            top_height,
+            speed,
+            direction,
+            top_wait: DOOR_WAIT,
+            top_countdown: 0,
+            last_height: ceiling_height,
+            done: false,
+        }
+    }
+
+    // Sector special 10, the door closes after 30 seconds and stays closed.
+    // See P_SpawnDoorCloseIn30 in p_doors.c.
+    pub fn close_in_30(sector: Rc<RefCell<Sector>>) -> VerticalDoor {
+        let ceiling_height = sector.borrow().ceiling_height;
+
+        VerticalDoor {
+            sector,
+            door_type: DoorType::Normal,
+            top_height: ceiling_height,
+            speed: DOOR_SPEED,
+            direction: DoorDirection::Waiting,
+            top_wait: DOOR_WAIT,
+            top_countdown: CLOSE_30_WAIT,
+            last_height: ceiling_height,
+            done: false,
+        }
+    }
+
+    // Sector special 14, the door opens after 5 minutes, waits and closes.
+    // See P_SpawnDoorRaiseIn5Mins in p_doors.c.
+    pub fn raise_in_5_mins(map: &Map, sector: Rc<RefCell<Sector>>) -> VerticalDoor {
+        VerticalDoor {
+            direction: DoorDirection::InitialWait,
+            top_countdown: RAISE_IN_5_MINS_WAIT,
+            ..VerticalDoor::new(map, sector, DoorType::RaiseIn5Mins)
+        }
+    }
+
+    fn move_ceiling(&mut self, destination: i16, direction: Direction) -> MoveResult {
+        move_plane(
+            &self.sector,
+            Plane::Ceiling,
+            self.speed,
+            destination,
+            direction,
+        )
+    }
+}
+
+impl Thinker for VerticalDoor {
+    fn mutate(&mut self) {
+        self.last_height = self.sector.borrow().ceiling_height;
+
+        match self.direction {
+            DoorDirection::Waiting => {
+                self.top_countdown -= 1;
+                if self.top_countdown == 0 {
+                    match self.door_type {
+                        DoorType::Normal | DoorType::BlazeRaise => {
+                            self.direction = DoorDirection::Down
+                        }
+                        DoorType::Close30ThenOpen => self.direction = DoorDirection::Up,
+                        _ => {}
+                    }
+                }
+            }
+
+            DoorDirection::InitialWait => {
+                self.top_countdown -= 1;
+                if self.top_countdown == 0 && self.door_type == DoorType::RaiseIn5Mins {
+                    self.direction = DoorDirection::Up;
+                    self.door_type = DoorType::Normal;
+                }
+            }
+
+            DoorDirection::Down => {
+                let floor_height = self.sector.borrow().floor_height;
+                if self.move_ceiling(floor_height, Direction::Down) == MoveResult::PastDestination {
+                    match self.door_type {
+                        DoorType::Normal
+                        | DoorType::Close
+                        | DoorType::BlazeRaise
+                        | DoorType::BlazeClose => self.done = true,
+                        DoorType::Close30ThenOpen => {
+                            self.direction = DoorDirection::Waiting;
+                            self.top_countdown = CLOSE_30_WAIT;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+
+            DoorDirection::Up => {
+                if self.move_ceiling(self.top_height, Direction::Up) == MoveResult::PastDestination
+                {
+                    match self.door_type {
+                        DoorType::Normal | DoorType::BlazeRaise => {
+                            self.direction = DoorDirection::Waiting;
+                            self.top_countdown = self.top_wait;
+                        }
+                        DoorType::Close30ThenOpen | DoorType::Open | DoorType::BlazeOpen => {
+                            self.done = true
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    fn moved_sector(&self) -> Option<&Rc<RefCell<Sector>>> {
+        Some(&self.sector)
+    }
+
+    // A closing door that comes down on the player goes back up, unless it's a door
+    // that only closes.
+    fn crushed(&mut self) {
+        if self.direction != DoorDirection::Down {
+            return;
+        }
+
+        self.sector.borrow_mut().ceiling_height = self.last_height;
+        match self.door_type {
+            DoorType::Close | DoorType::BlazeClose => {}
+            _ => self.direction = DoorDirection::Up,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+// Open the door on the back side of a used linedef. Using a door that closes again
+// while it's moving makes it go the other way. Returns true if a door was opened or
+// reversed. See EV_VerticalDoor in p_doors.c.
+pub fn use_door(
+    map: &Map,
+    linedef: &Linedef,
+    door_type: DoorType,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+) -> bool {
+    let Some(back_sidedef) = &linedef.back_sidedef else {
+        return false;
+    };
+    let sector = Rc::clone(&back_sidedef.borrow().sector);
+
+    if is_sector_moving(thinkers, &sector.borrow()) {
+        if !matches!(door_type, DoorType::Normal | DoorType::BlazeRaise) {
+            return false;
+        }
+
+        let Some(door) = get_sector_mover::<VerticalDoor>(thinkers, &sector.borrow()) else {
+            return false;
+        };
+        door.direction = match door.direction {
+            DoorDirection::Down => DoorDirection::Up,
+            _ => DoorDirection::Down,
+        };
+        return true;
+    }
+
+    thinkers.push(Box::new(VerticalDoor::new(map, sector, door_type)));
+    true
+}
+
+// Start a door in each sector with the linedef's tag that isn't moving yet.
+// Returns true if a door was started. See EV_DoDoor in p_doors.c.
+pub fn do_door(
+    map: &Map,
+    linedef: &Linedef,
+    door_type: DoorType,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+) -> bool {
+    let mut started = false;
+
+    for sector in map.get_tagged_sectors(linedef.sector_tag) {
+        if is_sector_moving(thinkers, &sector.borrow()) {
+            continue;
+        }
+
+        thinkers.push(Box::new(VerticalDoor::new(map, sector, door_type)));
+        started = true;
+    }
+
+    started
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_maps::{door_rooms, run_thinkers, TEST_MAP_NAME};
+
+    fn door_height(map: &Map) -> i16 {
+        map.sectors[1].borrow().ceiling_height
+    }
+
+    #[test]
+    fn test_manual_door() {
+        let mut builder = door_rooms();
+        builder.linedef_mut(3).special_type = 1;
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let linedef = &map.linedefs[3];
+        let mut thinkers = Vec::new();
+
+        // The door opens to 4 below the lowest surrounding ceiling
+        assert!(use_door(&map, linedef, DoorType::Normal, &mut thinkers));
+        run_thinkers(&mut thinkers, 10);
+        assert_eq!(door_height(&map), 20);
+        run_thinkers(&mut thinkers, 53);
+        assert_eq!(door_height(&map), 124);
+
+        // It waits and closes again
+        run_thinkers(&mut thinkers, DOOR_WAIT as usize);
+        assert_eq!(door_height(&map), 124);
+        run_thinkers(&mut thinkers, 63);
+        assert_eq!(door_height(&map), 0);
+        assert!(thinkers.is_empty());
+
+        // Using it while it's closing opens it again
+        assert!(use_door(&map, linedef, DoorType::Normal, &mut thinkers));
+        run_thinkers(&mut thinkers, 63 + DOOR_WAIT as usize + 10);
+        assert_eq!(door_height(&map), 104);
+        assert!(use_door(&map, linedef, DoorType::Normal, &mut thinkers));
+        run_thinkers(&mut thinkers, 1);
+        assert_eq!(door_height(&map), 106);
+        assert_eq!(thinkers.len(), 1);
+        assert_eq!(linedef.special_type.get(), 1);
+    }
+
+    #[test]
+    fn test_one_time_door() {
+        let mut builder = door_rooms();
+        builder.linedef_mut(3).special_type = 118;
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let linedef = &map.linedefs[3];
+        let mut thinkers = Vec::new();
+
+        // A fast door that stays open, and can't be closed by using it
+        let door_type = DoorType::BlazeOpen;
+        assert!(use_door(&map, linedef, door_type, &mut thinkers));
+        run_thinkers(&mut thinkers, 15);
+        assert_eq!(door_height(&map), 120);
+        assert!(!use_door(&map, linedef, door_type, &mut thinkers));
+        run_thinkers(&mut thinkers, 1);
+        assert_eq!(door_height(&map), 124);
+        assert!(thinkers.is_empty());
+    }
+
+    #[test]
+    fn test_tagged_door() {
+        let mut builder = door_rooms();
+        builder.sector_mut(1).tag_number = 5;
+        builder.linedef_mut(0).sector_tag = 5;
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let linedef = &map.linedefs[0];
+        let mut thinkers = Vec::new();
+
+        assert!(do_door(&map, linedef, DoorType::Open, &mut thinkers));
+        run_thinkers(&mut thinkers, 10);
+        assert_eq!(door_height(&map), 20);
+
+        // A moving sector isn't started again
+        assert!(!do_door(&map, linedef, DoorType::Open, &mut thinkers));
+        run_thinkers(&mut thinkers, 60);
+        assert_eq!(door_height(&map), 124);
+        assert!(thinkers.is_empty());
+
+        // Close it, wait 30 seconds and open it again
+        assert!(do_door(
+            &map,
+            linedef,
+            DoorType::Close30ThenOpen,
+            &mut thinkers
+        ));
+        run_thinkers(&mut thinkers, 63 + CLOSE_30_WAIT as usize);
+        assert_eq!(door_height(&map), 0);
+        run_thinkers(&mut thinkers, 1);
+        assert_eq!(door_height(&map), 2);
+        run_thinkers(&mut thinkers, 62);
+        assert_eq!(door_height(&map), 124);
+        assert!(thinkers.is_empty());
+
+        // Fast doors move 4 times as fast
+        assert!(do_door(&map, linedef, DoorType::BlazeClose, &mut thinkers));
+        run_thinkers(&mut thinkers, 16);
+        assert_eq!(door_height(&map), 0);
+        assert!(thinkers.is_empty());
+
+        // Linedefs without a tag don't do anything
+        assert!(!do_door(
+            &map,
+            &map.linedefs[1],
+            DoorType::Open,
+            &mut thinkers
+        ));
+    }
+
+    #[test]
+    fn test_door_sector_specials() {
+        let mut builder = door_rooms();
+        builder.sector_mut(1).special_type = 14;
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let mut thinkers = Vec::new();
+        crate::thinkers::init_thinkers(
+            &mut thinkers,
+            &map,
+            &crate::map_objects::MapObjects::new(&map).unwrap(),
+        );
+
+        run_thinkers(&mut thinkers, RAISE_IN_5_MINS_WAIT as usize);
+        assert_eq!(door_height(&map), 0);
+        run_thinkers(&mut thinkers, 10);
+        assert_eq!(door_height(&map), 20);
+        assert_eq!(map.sectors[1].borrow().special_type, 0);
+    }
+}
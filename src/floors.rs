@@ -0,0 +1,267 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::map::{Linedef, Map, Sector};
+use crate::planes::{
+    find_highest_floor_surrounding, find_lowest_ceiling_surrounding, find_lowest_floor_surrounding,
+    find_next_highest_floor, move_plane, Direction, MoveResult, Plane,
+};
+use crate::thinkers::{is_sector_moving, Thinker};
+
+// Floors that move up or down to a height once and stay there. The floors of the
+// sectors with the linedef's tag are moved. https://doomwiki.org/wiki/Floor
+
+const FLOOR_SPEED: i16 = 1; // Units per tic
+const TURBO_FLOOR_SPEED: i16 = FLOOR_SPEED * 4; // Units per tic of the fast floors
+const TURBO_LOWER_GAP: i16 = 8; // A turbo floor stops this far above the surrounding floor
+const CRUSH_GAP: i16 = 8; // A crushing floor stops this far below the ceiling
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloorType {
+    Lower,          // Lower to the highest surrounding floor
+    LowerToLowest,  // Lower to the lowest surrounding floor
+    TurboLower,     // Fast lower to 8 above the highest surrounding floor
+    Raise,          // Raise to the lowest surrounding ceiling
+    RaiseCrush,     // Raise to 8 below the lowest surrounding ceiling and crush
+    RaiseToNearest, // Raise to the next higher surrounding floor
+    RaiseTurbo,     // Fast raise to the next higher surrounding floor
+    Raise24,        // Raise by 24 units
+    Raise512,       // Raise by 512 units
+}
+
+// See T_MoveFloor in p_floor.c
+#[derive(Debug)]
+pub struct MovingFloor {
+    sector: Rc<RefCell<Sector>>, // The sector to move the floor of
+    direction: Direction,        // Up or down
+    destination: i16,            // Floor height to stop at
+    speed: i16,                  // Units per tic
+    crush: bool,                 // Keep moving up when the player is in the way
+    last_height: i16,            // Floor height before the last move
+    done: bool,                  // The floor reached the destination
+}
+
+impl MovingFloor {
+    // See EV_DoFloor in p_floor.c
+    pub fn new(map: &Map, sector: Rc<RefCell<Sector>>, floor_type: FloorType) -> MovingFloor {
+        let (direction, destination, speed) = {
+            let sector = sector.borrow();
+            let floor_height = sector.floor_height;
+
+            match floor_type {
+                FloorType::Lower => (
+                    Direction::Down,
+                    find_highest_floor_surrounding(map, &sector),
+                    FLOOR_SPEED,
+                ),
+                FloorType::LowerToLowest => (
+                    Direction::Down,
+                    find_lowest_floor_surrounding(map, &sector),
+                    FLOOR_SPEED,
+                ),
+                FloorType::TurboLower => {
+                    let mut destination = find_highest_floor_surrounding(map, &sector);
+                    if destination != floor_height {
+                        destination += TURBO_LOWER_GAP;
+                    }
+                    (Direction::Down, destination, TURBO_FLOOR_SPEED)
+                }
+                FloorType::Raise | FloorType::RaiseCrush => {
+                    let mut destination =
+                        find_lowest_ceiling_surrounding(map, &sector).min(sector.ceiling_height);
+                    if floor_type == FloorType::RaiseCrush {
+                        destination -= CRUSH_GAP;
+                    }
+                    (Direction::Up, destination, FLOOR_SPEED)
+                }
+                FloorType::RaiseToNearest => (
+                    Direction::Up,
+                    find_next_highest_floor(map, &sector, floor_height),
+                    FLOOR_SPEED,
+                ),
+                FloorType::RaiseTurbo => (
+                    Direction::Up,
+                    find_next_highest_floor(map, &sector, floor_height),
+                    TURBO_FLOOR_SPEED,
+                ),
+                FloorType::Raise24 => (Direction::Up, floor_height + 24, FLOOR_SPEED),
+                FloorType::Raise512 => (Direction::Up, floor_height + 512, FLOOR_SPEED),
+            }
+        };
+        let last_height = sector.borrow().floor_height;
+
+        MovingFloor {
+            sector,
+            direction,
+            destination,
+            speed,
+            crush: floor_type == FloorType::RaiseCrush,
+            last_height,
+            done: false,
+        }
+    }
+}
+
+impl Thinker for MovingFloor {
+    fn mutate(&mut self) {
+        self.last_height = self.sector.borrow().floor_height;
+
+        let result = move_plane(
+            &self.sector,
+            Plane::Floor,
+            self.speed,
+            self.destination,
+            self.direction,
+        );
+        if result == MoveResult::PastDestination {
+            self.done = true;
+        }
+    }
+
+    fn moved_sector(&self) -> Option<&Rc<RefCell<Sector>>> {
+        Some(&self.sector)
+    }
+
+    // A rising floor that pushes the player into the ceiling stays where it was until
+    // the player moves away, unless it crushes
+    fn crushed(&mut self) {
+        if self.direction == Direction::Up && !self.crush {
+            self.sector.borrow_mut().floor_height = self.last_height;
+            self.done = false;
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+// Move the floor of each sector with the linedef's tag that isn't moving yet.
+// Returns true if a floor was started. See EV_DoFloor in p_floor.c.
+pub fn do_floor(
+    map: &Map,
+    linedef: &Linedef,
+    floor_type: FloorType,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+) -> bool {
+    let mut started = false;
+
+    for sector in map.get_tagged_sectors(linedef.sector_tag) {
+        if is_sector_moving(thinkers, &sector.borrow()) {
+            continue;
+        }
+
+        thinkers.push(Box::new(MovingFloor::new(map, sector, floor_type)));
+        started = true;
+    }
+
+    started
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_maps::{run_thinkers, tagged_two_rooms};
+
+    // The west room has a floor at 0 and a ceiling at 128, the east room a floor at
+    // 16 and a ceiling at 112
+    fn floor_heights(map: &Map) -> (i16, i16) {
+        (
+            map.sectors[0].borrow().floor_height,
+            map.sectors[1].borrow().floor_height,
+        )
+    }
+
+    #[test]
+    fn test_lower_floor() {
+        let map = tagged_two_rooms();
+        let east = &map.linedefs[1];
+        let mut thinkers = Vec::new();
+
+        // Lower the east floor to the west floor, one unit per tic
+        assert!(do_floor(&map, east, FloorType::Lower, &mut thinkers));
+        assert!(!do_floor(&map, east, FloorType::Lower, &mut thinkers));
+        run_thinkers(&mut thinkers, 10);
+        assert_eq!(floor_heights(&map), (0, 6));
+        run_thinkers(&mut thinkers, 7);
+        assert_eq!(floor_heights(&map), (0, 0));
+        assert!(thinkers.is_empty());
+
+        // Raise it again to the next higher floor, which is none
+        assert!(do_floor(
+            &map,
+            east,
+            FloorType::RaiseToNearest,
+            &mut thinkers
+        ));
+        run_thinkers(&mut thinkers, 1);
+        assert_eq!(floor_heights(&map), (0, 0));
+        assert!(thinkers.is_empty());
+    }
+
+    #[test]
+    fn test_turbo_lower_floor() {
+        let map = tagged_two_rooms();
+        let mut thinkers = Vec::new();
+
+        // Fast floors stop 8 above the surrounding floor
+        assert!(do_floor(
+            &map,
+            &map.linedefs[1],
+            FloorType::TurboLower,
+            &mut thinkers
+        ));
+        run_thinkers(&mut thinkers, 2);
+        assert_eq!(floor_heights(&map), (0, 8));
+        run_thinkers(&mut thinkers, 1);
+        assert!(thinkers.is_empty());
+    }
+
+    #[test]
+    fn test_raise_floor() {
+        let map = tagged_two_rooms();
+        let west = &map.linedefs[0];
+        let mut thinkers = Vec::new();
+
+        assert!(do_floor(
+            &map,
+            west,
+            FloorType::RaiseToNearest,
+            &mut thinkers
+        ));
+        run_thinkers(&mut thinkers, 17);
+        assert_eq!(floor_heights(&map), (16, 16));
+        assert!(thinkers.is_empty());
+
+        assert!(do_floor(&map, west, FloorType::Raise24, &mut thinkers));
+        run_thinkers(&mut thinkers, 25);
+        assert_eq!(floor_heights(&map), (40, 16));
+
+        // Up to the lowest surrounding ceiling, or 8 below it when crushing
+        assert!(do_floor(&map, west, FloorType::RaiseCrush, &mut thinkers));
+        run_thinkers(&mut thinkers, 100);
+        assert_eq!(floor_heights(&map), (104, 16));
+        assert!(do_floor(&map, west, FloorType::Raise, &mut thinkers));
+        run_thinkers(&mut thinkers, 100);
+        assert_eq!(floor_heights(&map), (112, 16));
+    }
+
+    #[test]
+    fn test_floor_crushed() {
+        let map = tagged_two_rooms();
+        let mut thinkers: Vec<Box<dyn Thinker>> = Vec::new();
+        assert!(do_floor(
+            &map,
+            &map.linedefs[0],
+            FloorType::Raise,
+            &mut thinkers
+        ));
+
+        // A floor that can't crush is held back, but keeps trying
+        run_thinkers(&mut thinkers, 2);
+        thinkers[0].crushed();
+        assert_eq!(floor_heights(&map), (1, 16));
+        run_thinkers(&mut thinkers, 1);
+        assert_eq!(floor_heights(&map), (2, 16));
+    }
+}
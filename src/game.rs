@@ -1,3 +1,4 @@
+use clap::ValueEnum;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 
@@ -7,22 +8,35 @@ use sdl2::pixels::Color;
 use sdl2::pixels::PixelFormatEnum;
 use sdl2::rect::Point;
 use sdl2::rect::Rect;
-use sdl2::render::Canvas;
 use sdl2::render::TextureCreator;
-use sdl2::video::Window;
 use sdl2::EventPump;
-use sdl2::Sdl;
 use std::collections::HashSet;
 use std::f32::consts::PI;
+use std::io;
+use std::path::PathBuf;
 use std::rc::Rc;
 use std::time::Instant;
 
-use crate::graphics::{Flats, Palette, Pictures, Sprites, Texture, Textures};
-use crate::map::{get_thing_by_type, Flags, Map, ThingTypes, Vertex};
+use crate::graphics::{
+    Colormaps, Flats, Palette, Pictures, Sprites, Texture, Textures, INVULNERABILITY_COLORMAP,
+};
+use crate::input::{
+    Action, KeyBindings, Mouse, MOUSE_LOOK_SPEED, MOUSE_MOVE_SPEED, MOUSE_TURN_SPEED,
+};
+use crate::map::{get_thing_by_type, Flags, Map, MapError, ThingTypes, Vertex};
 use crate::map_objects::{explode_everything, kill_everything, respawn_everything, MapObjects};
-use crate::renderer::{get_sector_from_vertex, Pixels, Renderer};
+use crate::movement::{
+    bob, crossed_special_linedefs, fits_in_sector, floor_and_ceiling, slide_move,
+    smooth_view_height, view_z, z_movement,
+};
+use crate::palette_effects::PaletteEffects;
+use crate::renderer::{Lighting, LightingMode, Pixels, Renderer, PLAYER_EYE_HEIGHT};
+use crate::screenshot::{save_screenshot, ScreenshotFormat};
+use crate::specials::{cross_special_line, use_lines, Activation, LevelExit};
+use crate::switches::Switches;
+use crate::teleports::{spawn_teleport_fog, FOG_DISTANCE};
 use crate::thinkers::{init_thinkers, Thinker};
-use crate::wad::WadFile;
+use crate::wad::{Namespace, WadError, WadFile};
 
 const TITLE: &str = "A doom renderer in Rust";
 pub const SCREEN_WIDTH: u32 = 1024;
@@ -30,18 +44,59 @@ pub const SCREEN_HEIGHT: u32 = 768;
 const MAP_BORDER: u32 = 20;
 
 const CLOCK_HZ: u32 = 35;
+const MAX_PITCH: f32 = PI / 6.0; // Looking up and down is limited to 30 degrees
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct OverridePlayer {
     pub position: Vertex,
     pub angle: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_z: Option<f32>, // Eye height, starts the game with the fly camera
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pitch: Option<f32>, // Looking up or down
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Player {
     pub position: Vertex,
-    pub floor_height: f32, // Set to the height of the sector the player is in
+    pub floor_height: f32,   // Set to the height of the floor under the player
+    pub ceiling_height: f32, // Set to the height of the ceiling above the player
+    pub z: f32,              // Height of the feet
+    pub momentum_z: f32,     // Vertical speed in map units per tic
+    pub view_height: f32,    // Height of the eyes above the feet
+    pub delta_view_height: f32, // Change of the view height per tic
+    pub bob: f32,            // View bob, depends on the speed
+    pub view_z: f32,         // Height of the eyes
     pub angle: f32,
+    pub pitch: f32, // Looking up (positive) or down in radians
+}
+
+impl Player {
+    // The heights are updated once the player is in the map
+    pub fn new(position: Vertex, angle: f32) -> Player {
+        Player {
+            position,
+            floor_height: 0.0,
+            ceiling_height: 0.0,
+            z: 0.0,
+            momentum_z: 0.0,
+            view_height: PLAYER_EYE_HEIGHT,
+            delta_view_height: 0.0,
+            bob: 0.0,
+            view_z: 0.0,
+            angle,
+            pitch: 0.0,
+        }
+    }
+}
+
+// How the player moves around. The noclip and fly camera modes are for inspecting
+// maps from places that can't be reached in normal play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CameraMode {
+    Normal, // Collision detection, the eyes are above the floor
+    Noclip, // Walk through walls, the eyes are above the floor
+    Fly,    // Walk through walls, the eye height is changed with page up/down
 }
 
 pub const AVG_TICKS_MAXSAMPLES: u32 = 16;
@@ -89,29 +144,44 @@ impl Clock {
     fn get_fps(&mut self) -> f32 {
         1.0 / self.get_avg_ticks()
     }
+
+    // Move the clock forward by exactly one tick, regardless of the real time passed
+    fn add_tick(&mut self) {
+        self.ticks += 1;
+        self.timestamp = self.ticks as f32 / CLOCK_HZ as f32;
+    }
 }
 
-#[allow(dead_code)]
+// The game state. It doesn't depend on SDL, so that frames can also be rendered
+// without a window.
 pub struct Game {
-    sdl_context: Sdl,
-    pub canvas: Canvas<Window>,
     clock: Clock,
     last_tick_processed: u32,
     map: Map,
     pub palette: Palette,
+    pub lighting: Lighting, // Light diminishing and the fixed colormap
+    pub palette_effects: PaletteEffects, // Damage, bonus and radiation suit palettes
     player: Player,
+    camera_mode: CameraMode, // Collision detection, noclip or fly camera
     pressed_keys: HashSet<Keycode>,
-    viewing_map: bool,  // Toggle the 2D map
-    turbo: f32,         // Percentage speed increase
-    pictures: Pictures, // Pictures (aka patches)
-    flats: Flats,       // Flats
+    pub key_bindings: KeyBindings, // Keys of the actions
+    pub mouse: Mouse,              // Mouse settings, buttons and motion
+    viewing_map: bool,             // Toggle the 2D map
+    turbo: f32,                    // Percentage speed increase
+    pictures: Pictures,            // Pictures (aka patches)
+    flats: Flats,                  // Flats
     textures: Textures,
+    switches: Switches, // Switch textures, to flip used switches
     sky_texture: Rc<Texture>,
     map_objects: MapObjects,
     sprites: Sprites,
     thinkers: Vec<Box<dyn Thinker>>,
-    print_fps: bool,             // Show frames per second
-    print_player_position: bool, // Print player position
+    print_fps: bool,                         // Show frames per second
+    print_player_position: bool,             // Print player position
+    pub screenshot_format: ScreenshotFormat, // Format of screenshots
+    pub screenshot_directory: PathBuf,       // Directory screenshots are written to
+    take_screenshot: bool,                   // Save the next rendered frame
+    level_exit: Option<LevelExit>,           // An exit special was activated
 }
 
 impl Game {
@@ -122,90 +192,95 @@ impl Game {
         print_fps: bool,
         print_player_position: bool,
         override_player: Option<OverridePlayer>,
-    ) -> Game {
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
-
-        let window = video_subsystem
-            .window(TITLE, SCREEN_WIDTH, SCREEN_HEIGHT)
-            .position_centered()
-            .build()
-            .unwrap();
-
-        let canvas = window
-            .into_canvas()
-            .software()
-            .present_vsync()
-            .build()
-            .unwrap();
+    ) -> Result<Game, MapError> {
+        let map = Map::new(&wad_file, map_name)?;
 
-        let map = Map::new(&wad_file, map_name);
+        // Keep the eye height of an overridden player
+        let camera_mode = match override_player.as_ref().and_then(|p| p.view_z) {
+            Some(_) => CameraMode::Fly,
+            None => CameraMode::Normal,
+        };
 
         let player = if let Some(override_player) = override_player {
             Player {
-                position: override_player.position,
-                angle: override_player.angle,
-                floor_height: 0.0, // Will be updated later
+                view_z: override_player.view_z.unwrap_or(0.0),
+                pitch: override_player.pitch.unwrap_or(0.0),
+                ..Player::new(override_player.position, override_player.angle)
             }
         } else {
-            let player1_start = get_thing_by_type(&map.things, ThingTypes::Player1Start);
-            Player {
-                position: Vertex::new(player1_start.x, player1_start.y),
-                angle: player1_start.angle,
-                floor_height: 0.0, // Will be updated later
-            }
+            let player1_start = get_thing_by_type(&map.things, ThingTypes::Player1Start)
+                .ok_or_else(|| MapError::MissingThing {
+                    map_name: map_name.to_string(),
+                    thing_type: ThingTypes::Player1Start as i16,
+                })?;
+            Player::new(
+                Vertex::new(player1_start.x, player1_start.y),
+                player1_start.angle,
+            )
         };
 
-        let palette = Palette::new(&wad_file);
-        let mut pictures = Pictures::new(&wad_file);
-        let flats = Flats::new(&wad_file);
-        let mut textures = Textures::new(&wad_file);
+        let palette = Palette::new(&wad_file)?;
+        let lighting = Lighting::new(Colormaps::new(&wad_file)?, LightingMode::Colormap);
+        let mut pictures = Pictures::new(&wad_file, Namespace::Sprites);
+        let flats = Flats::new(&wad_file)?;
+        let mut textures = Textures::new(&wad_file)?;
+        let switches = Switches::load(&wad_file)?;
 
-        let sky_texture = Self::get_sky_texture(map_name, &mut textures);
+        let sky_texture = Self::get_sky_texture(map_name, &mut textures)?;
 
-        let map_objects = MapObjects::new(&map);
-        let sprites = Sprites::new(&wad_file, &mut pictures);
+        let map_objects = MapObjects::new(&map)?;
+        let sprites = Sprites::new(&wad_file, &mut pictures)?;
 
         let mut game = Game {
-            sdl_context,
-            canvas,
             clock: Clock::new(),
             last_tick_processed: 0,
             map,
             player,
+            camera_mode,
             pressed_keys: HashSet::new(),
+            key_bindings: KeyBindings::default(),
+            mouse: Mouse::new(),
             viewing_map: false,
             turbo: (turbo as f32) / 100.0,
             palette,
+            lighting,
+            palette_effects: PaletteEffects::new(),
             pictures,
             flats,
             textures,
+            switches,
             sky_texture: Rc::clone(&sky_texture),
             map_objects,
             sprites,
             thinkers: Vec::new(),
             print_fps,
             print_player_position,
+            screenshot_format: ScreenshotFormat::Png,
+            screenshot_directory: PathBuf::from("."),
+            take_screenshot: false,
+            level_exit: None,
         };
 
         // Set initial player height
         game.update_current_player_height();
+        game.player.z = game.player.floor_height;
+        game.update_current_player_height();
         init_thinkers(&mut game.thinkers, &game.map, &game.map_objects);
 
-        game
+        Ok(game)
     }
 
     // Determine which sky texture to be used based on the map name
-    fn get_sky_texture(map_name: &str, textures: &mut Textures) -> Rc<Texture> {
+    fn get_sky_texture(map_name: &str, textures: &mut Textures) -> Result<Rc<Texture>, WadError> {
         let doom1_re = Regex::new(r"e(?<episode>\d+)m(?<map>\d+)").unwrap();
         if let Some(caps) = doom1_re.captures(map_name) {
             let episode = caps["episode"].parse::<i32>().unwrap();
 
             return match episode {
-                1 => Rc::clone(&textures.get("SKY1")),
-                2 => Rc::clone(&textures.get("SKY2")),
-                3 => Rc::clone(&textures.get("SKY3")),
-                _ => Rc::clone(&textures.get("SKY1")), // Should not happen
+                1 => textures.get("SKY1"),
+                2 => textures.get("SKY2"),
+                3 => textures.get("SKY3"),
+                _ => textures.get("SKY1"), // Should not happen
             };
         }
 
@@ -214,16 +289,16 @@ impl Game {
             let map = caps["map"].parse::<i32>().unwrap();
 
             if map < 12 {
-                return Rc::clone(&textures.get("SKY1"));
+                return textures.get("SKY1");
             } else if map < 21 {
-                return Rc::clone(&textures.get("SKY2"));
+                return textures.get("SKY2");
             } else {
-                return Rc::clone(&textures.get("SKY3"));
+                return textures.get("SKY3");
             }
         }
 
         // Fall back to something
-        Rc::clone(&textures.get("SKY1"))
+        textures.get("SKY1")
     }
 
     pub fn transform_vertex_to_point_for_map(&self, v: &Vertex) -> Point {
@@ -241,28 +316,25 @@ impl Game {
         Point::new(x, y)
     }
 
-    #[allow(dead_code)]
-    fn draw_map_linedefs(&mut self) {
-        self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-
+    fn draw_map_linedefs(&self, pixels: &mut Pixels) {
         for linedef in &self.map.linedefs {
-            if linedef.flags & Flags::DONTDRAW > 0 {
+            let color = if linedef.flags & Flags::DONTDRAW > 0 {
                 continue;
             } else if linedef.flags & Flags::TWOSIDED > 0 {
-                self.canvas.set_draw_color(Color::RGB(255, 255, 0));
+                Color::RGB(255, 255, 0)
             } else {
-                self.canvas.set_draw_color(Color::RGB(255, 0, 0));
-            }
+                Color::RGB(255, 0, 0)
+            };
 
             let start_point = self.transform_vertex_to_point_for_map(&linedef.start_vertex);
             let end_point = self.transform_vertex_to_point_for_map(&linedef.end_vertex);
-            self.canvas.draw_line(start_point, end_point).unwrap();
+            pixels.draw_line(&start_point, &end_point, &color);
         }
     }
 
     #[allow(dead_code)]
-    fn draw_map_nodes(&mut self) {
-        self.canvas.set_draw_color(Color::RGB(255, 0, 0));
+    fn draw_map_nodes(&self, pixels: &mut Pixels) {
+        let color = Color::RGB(255, 0, 0);
 
         for node in &self.map.nodes {
             let x = node.x;
@@ -279,13 +351,12 @@ impl Game {
             let start_point = self.transform_vertex_to_point_for_map(&start_vertex);
             let end_point = self.transform_vertex_to_point_for_map(&end_vertex);
 
-            self.canvas.draw_line(start_point, end_point).unwrap();
+            pixels.draw_line(&start_point, &end_point, &color);
         }
     }
 
-    #[allow(dead_code)]
-    fn draw_map_player(&mut self) {
-        self.canvas.set_draw_color(Color::RGB(255, 255, 0));
+    fn draw_map_player(&self, pixels: &mut Pixels) {
+        let color = Color::RGB(255, 255, 0);
 
         let length = SCREEN_WIDTH as f32 / 16.0;
         let arrow_length = SCREEN_WIDTH as f32 / 32.0;
@@ -296,7 +367,7 @@ impl Game {
         let start_point = self.transform_vertex_to_point_for_map(start_vertex);
         let end_point = self.transform_vertex_to_point_for_map(&end_vertex);
 
-        self.canvas.draw_line(start_point, end_point).unwrap();
+        pixels.draw_line(&start_point, &end_point, &color);
 
         // Draw arrow lines
         let arrow = Vertex::new(arrow_length, 0.0);
@@ -304,8 +375,8 @@ impl Game {
         let left_arrow_vertex = &end_vertex + &arrow.rotate(self.player.angle - PI + PI / 4.0);
         let right_arrow_point = self.transform_vertex_to_point_for_map(&right_arrow_vertex);
         let left_arrow_point = self.transform_vertex_to_point_for_map(&left_arrow_vertex);
-        self.canvas.draw_line(right_arrow_point, end_point).unwrap();
-        self.canvas.draw_line(left_arrow_point, end_point).unwrap();
+        pixels.draw_line(&right_arrow_point, &end_point, &color);
+        pixels.draw_line(&left_arrow_point, &end_point, &color);
     }
 
     // This is done differently from Doom, which runs with a 35 Hz clock. If this was
@@ -316,11 +387,8 @@ impl Game {
         let rotate_factor: f32 = duration * 0.0025; // radians/msec
         let move_factor: f32 = duration * 0.291; // 291 mu/sec
 
-        let alt_down = self.pressed_keys.contains(&Keycode::LAlt)
-            || self.pressed_keys.contains(&Keycode::RAlt);
-
-        let shift_down = self.pressed_keys.contains(&Keycode::LShift)
-            || self.pressed_keys.contains(&Keycode::RShift);
+        let alt_down = self.is_action_down(Action::Strafe);
+        let shift_down = self.is_action_down(Action::Run);
 
         let move_length = if shift_down {
             move_factor * self.turbo * 2.0
@@ -335,56 +403,169 @@ impl Game {
         };
 
         // Rotation
-        if !alt_down && self.pressed_keys.contains(&Keycode::Left) {
+        if !alt_down && self.is_action_down(Action::TurnLeft) {
             self.player.angle += rotate_angle;
-            self.update_current_player_height();
+            self.print_position();
         }
 
-        if !alt_down && self.pressed_keys.contains(&Keycode::Right) {
+        if !alt_down && self.is_action_down(Action::TurnRight) {
             self.player.angle -= rotate_angle;
-            self.update_current_player_height();
+            self.print_position();
         }
 
-        // Strafe
-        if alt_down && self.pressed_keys.contains(&Keycode::Left) {
-            let delta = Vertex::new(move_length, 0.0).rotate(self.player.angle + PI / 2.0);
-            self.player.position = &self.player.position + &delta;
-            self.update_current_player_height();
+        // Turn with the mouse, or strafe while the strafe action is held
+        let (mouse_x, mouse_y) = self.mouse.take_motion();
+        let mut mouse_strafe = 0.0;
+        if alt_down {
+            mouse_strafe = -mouse_x * MOUSE_MOVE_SPEED;
+        } else if mouse_x != 0.0 {
+            self.player.angle -= mouse_x * MOUSE_TURN_SPEED;
+            self.print_position();
         }
 
-        if alt_down && self.pressed_keys.contains(&Keycode::Right) {
-            let delta = Vertex::new(move_length, 0.0).rotate(self.player.angle + PI / 2.0);
-            self.player.position = &self.player.position - &delta;
-            self.update_current_player_height();
+        // Looking up and down
+        if self.is_action_down(Action::LookUp) {
+            self.look(rotate_angle / 2.0);
         }
 
-        // Forward/backward
-        if self.pressed_keys.contains(&Keycode::Up) {
-            let delta = Vertex::new(move_length, 0.0).rotate(self.player.angle);
-            self.player.position = &self.player.position + &delta;
-            self.update_current_player_height();
+        if self.is_action_down(Action::LookDown) {
+            self.look(-rotate_angle / 2.0);
         }
 
-        if self.pressed_keys.contains(&Keycode::Down) {
-            let delta = Vertex::new(move_length, 0.0).rotate(self.player.angle);
-            self.player.position = &self.player.position - &delta;
-            self.update_current_player_height();
+        if self.is_action_down(Action::CenterView) {
+            self.player.pitch = 0.0;
+        }
+
+        // Moving the mouse away looks up or moves forward
+        let mut mouse_forward = 0.0;
+        if self.mouse.look {
+            if mouse_y != 0.0 {
+                self.look(-mouse_y * MOUSE_LOOK_SPEED);
+            }
+        } else if !self.mouse.novert {
+            mouse_forward = -mouse_y * MOUSE_MOVE_SPEED;
+        }
+
+        // Strafe and forward/backward moves are added up and done in one go
+        let mut delta = Vertex::new(mouse_forward, mouse_strafe).rotate(self.player.angle);
+        let strafe = Vertex::new(move_length, 0.0).rotate(self.player.angle + PI / 2.0);
+        let forward = Vertex::new(move_length, 0.0).rotate(self.player.angle);
+
+        if (alt_down && self.is_action_down(Action::TurnLeft))
+            || self.is_action_down(Action::StrafeLeft)
+        {
+            delta = &delta + &strafe;
+        }
+
+        if (alt_down && self.is_action_down(Action::TurnRight))
+            || self.is_action_down(Action::StrafeRight)
+        {
+            delta = &delta - &strafe;
+        }
+
+        if self.is_action_down(Action::Forward) {
+            delta = &delta + &forward;
+        }
+
+        if self.is_action_down(Action::Back) {
+            delta = &delta - &forward;
+        }
+
+        self.player.bob = bob(&delta);
+        if delta.x != 0.0 || delta.y != 0.0 {
+            self.move_player(&delta);
+        }
+
+        // Up/down with the fly camera
+        if self.camera_mode == CameraMode::Fly {
+            if self.is_action_down(Action::FlyUp) {
+                self.player.view_z += move_length;
+                self.print_position();
+            }
+
+            if self.is_action_down(Action::FlyDown) {
+                self.player.view_z -= move_length;
+                self.print_position();
+            }
         }
     }
 
-    // Update the height of the player by looking at ther sector height the player is in.
-    fn update_current_player_height(&mut self) {
+    // Move the player. In normal mode, the player slides along walls, can't take
+    // steps that are too high and activates the walk-over specials of the linedefs
+    // it crosses. Otherwise, walls are ignored.
+    fn move_player(&mut self, delta: &Vertex) {
+        match self.camera_mode {
+            CameraMode::Normal => {
+                let from = self.player.position.clone();
+                self.player.position = slide_move(&self.map, &from, delta, self.player.z);
+
+                let to = &self.player.position;
+                for (linedef, is_back_side) in crossed_special_linedefs(&self.map, &from, to) {
+                    let activation =
+                        cross_special_line(&self.map, &linedef, is_back_side, &mut self.thinkers);
+                    self.activated(activation);
+                }
+            }
+            CameraMode::Noclip | CameraMode::Fly => {
+                self.player.position = &self.player.position + delta;
+            }
+        }
+        self.update_current_player_height();
+        self.print_position();
+    }
+
+    // Look up (positive) or down
+    fn look(&mut self, pitch: f32) {
+        self.player.pitch = (self.player.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+        self.print_position();
+    }
+
+    // Fall, step up and smooth the view height. The fly camera doesn't fall.
+    fn move_player_z(&mut self) {
+        if self.camera_mode != CameraMode::Fly {
+            z_movement(&mut self.player);
+            smooth_view_height(&mut self.player);
+        }
+        self.update_current_player_height();
+    }
+
+    // Switch to a camera mode, or back to normal if it's already on
+    pub fn toggle_camera_mode(&mut self, camera_mode: CameraMode) {
+        if self.camera_mode == camera_mode {
+            self.set_camera_mode(CameraMode::Normal);
+        } else {
+            self.set_camera_mode(camera_mode);
+        }
+    }
+
+    pub fn set_camera_mode(&mut self, camera_mode: CameraMode) {
+        self.camera_mode = camera_mode;
+        self.update_current_player_height();
+    }
+
+    fn print_position(&self) {
         if self.print_player_position {
             let player = OverridePlayer {
                 position: self.player.position.clone(),
                 angle: self.player.angle,
+                view_z: (self.camera_mode == CameraMode::Fly).then_some(self.player.view_z),
+                pitch: (self.player.pitch != 0.0).then_some(self.player.pitch),
             };
             let serialized = serde_json::to_string(&player).unwrap();
             println!("--player-position '{}'", serialized);
         }
+    }
+
+    // Update the floor and ceiling heights around the player and the height of the
+    // eyes. The fly camera keeps its own eye height.
+    fn update_current_player_height(&mut self) {
+        if let Some((floor, ceiling)) = floor_and_ceiling(&self.map, &self.player.position) {
+            self.player.floor_height = floor;
+            self.player.ceiling_height = ceiling;
+        }
 
-        if let Some(sector) = get_sector_from_vertex(&self.map, &self.player.position) {
-            self.player.floor_height = sector.borrow().floor_height as f32;
+        if self.camera_mode != CameraMode::Fly {
+            self.player.view_z = view_z(&self.player, self.clock.ticks);
         }
     }
 
@@ -392,58 +573,47 @@ impl Game {
     fn process_events(&mut self, event_pump: &mut EventPump) -> bool {
         for event in event_pump.poll_iter() {
             match event {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Q),
-                    ..
-                } => {
+                Event::Quit { .. } => {
                     return true; // Stop the main loop and terminate
                 }
 
                 Event::KeyDown {
-                    keycode: Some(Keycode::Tab),
-                    ..
-                } => {
-                    self.viewing_map = !self.viewing_map;
-                }
-
-                Event::KeyDown {
-                    keycode: Some(Keycode::K),
+                    keycode: Some(keycode),
+                    repeat,
                     ..
                 } => {
-                    kill_everything(&mut self.thinkers);
+                    self.pressed_keys.insert(keycode);
+                    if !repeat {
+                        for action in self.key_bindings.actions(keycode) {
+                            if self.start_action(action) {
+                                return true;
+                            }
+                        }
+                    }
                 }
 
-                Event::KeyDown {
-                    keycode: Some(Keycode::X),
+                Event::KeyUp {
+                    keycode: Some(keycode),
                     ..
                 } => {
-                    explode_everything(&mut self.thinkers);
+                    self.pressed_keys.remove(&keycode);
                 }
 
-                Event::KeyDown {
-                    keycode: Some(Keycode::R),
-                    ..
-                } => {
-                    respawn_everything(&mut self.thinkers);
+                Event::MouseMotion { xrel, yrel, .. } => {
+                    self.mouse.motion(xrel, yrel);
                 }
 
-                Event::KeyDown {
-                    keycode: Some(keycode),
-                    ..
-                } => {
-                    self.pressed_keys.insert(keycode);
+                Event::MouseButtonDown { mouse_btn, .. } => {
+                    self.mouse.button_down(mouse_btn);
+                    if let Some(&action) = self.mouse.bindings.get(&mouse_btn) {
+                        if self.start_action(action) {
+                            return true;
+                        }
+                    }
                 }
 
-                Event::KeyUp {
-                    keycode: Some(keycode),
-                    ..
-                } => {
-                    self.pressed_keys.remove(&keycode);
+                Event::MouseButtonUp { mouse_btn, .. } => {
+                    self.mouse.button_up(mouse_btn);
                 }
 
                 _ => {}
@@ -453,16 +623,108 @@ impl Game {
         false
     }
 
+    // Do an action that happens once when its key is pressed. Actions that are
+    // held down are done in process_down_keys. Returns true if the game should end.
+    fn start_action(&mut self, action: Action) -> bool {
+        match action {
+            Action::Quit => return true,
+            Action::Automap => self.viewing_map = !self.viewing_map,
+            Action::Screenshot => self.take_screenshot = true,
+            Action::Invulnerability => self.toggle_invulnerability(),
+            Action::Noclip => self.toggle_camera_mode(CameraMode::Noclip),
+            Action::Fly => self.toggle_camera_mode(CameraMode::Fly),
+            Action::GrabMouse => self.mouse.toggle_grab(),
+            Action::Use => {
+                let activation = use_lines(
+                    &self.map,
+                    &self.player.position,
+                    self.player.angle,
+                    &self.switches,
+                    &mut self.thinkers,
+                );
+                self.activated(activation);
+            }
+            Action::KillAll => kill_everything(&mut self.thinkers),
+            Action::ExplodeAll => explode_everything(&mut self.thinkers),
+            Action::Respawn => respawn_everything(&mut self.thinkers),
+            _ => {}
+        }
+
+        false
+    }
+
+    // Act on an activated special: teleport the player, or remember a level exit.
+    // There is only one level, so an exit ends the game.
+    fn activated(&mut self, activation: Activation) {
+        match activation {
+            Activation::Teleport(thing) => self.teleport(thing),
+            Activation::Exit(level_exit) => self.level_exit = Some(level_exit),
+            _ => {}
+        }
+    }
+
+    // Move the player onto the floor at a teleport destination thing, facing its
+    // direction, with fog where the player left and in front of where it arrives.
+    // See EV_Teleport in p_telept.c.
+    fn teleport(&mut self, thing: usize) {
+        let destination = Rc::clone(&self.map.things[thing]);
+        let position = Vertex::new(destination.x, destination.y);
+
+        let old_position = self.player.position.clone();
+        spawn_teleport_fog(&mut self.map_objects, &mut self.thinkers, old_position);
+        let fog_position = &position + &Vertex::new(FOG_DISTANCE, 0.0).rotate(destination.angle);
+        spawn_teleport_fog(&mut self.map_objects, &mut self.thinkers, fog_position);
+
+        self.player.position = position;
+        self.player.angle = destination.angle;
+        self.player.momentum_z = 0.0;
+        self.update_current_player_height();
+        self.player.z = self.player.floor_height;
+        self.update_current_player_height();
+    }
+
+    // Is a key or mouse button bound to the action held down?
+    fn is_action_down(&self, action: Action) -> bool {
+        self.key_bindings.is_action_down(action, &self.pressed_keys)
+            || self.mouse.is_action_down(action)
+    }
+
+    // Run the thinkers and remove the finished ones. A floor or ceiling that moves
+    // into the player is handled by its thinker, e.g. a closing door goes back up.
+    // Noclip and the fly camera aren't in the way.
     fn tick_thinkers(&mut self) {
+        let on_floor = self.player.z == self.player.floor_height;
+
         for thinker in &mut self.thinkers {
             thinker.mutate();
+
+            if let Some(sector) = thinker.moved_sector() {
+                if self.camera_mode == CameraMode::Normal
+                    && !fits_in_sector(&self.map, &self.player.position, sector)
+                {
+                    thinker.crushed();
+                }
+            }
+        }
+
+        self.thinkers.retain(|thinker| !thinker.is_done());
+        self.map_objects.remove_finished();
+
+        // The player stays on a floor that moves up or down.
+        // See P_ThingHeightClip in p_map.c.
+        self.update_current_player_height();
+        if on_floor && self.camera_mode != CameraMode::Fly {
+            self.player.z = self.player.floor_height;
+            self.update_current_player_height();
         }
     }
 
     // Process one game tick
     fn tick(&mut self) {
         self.process_down_keys();
+        self.move_player_z();
         self.tick_thinkers();
+        self.palette_effects.tick();
     }
 
     // Move forward in time & run game logic
@@ -482,30 +744,34 @@ impl Game {
         }
     }
 
+    // Run the game logic for a number of ticks without waiting for real time to
+    // pass. This makes the game state deterministic, e.g. for headless rendering.
+    pub fn run_tics(&mut self, tics: u32) {
+        for _ in 0..tics {
+            self.clock.add_tick();
+            self.tick();
+        }
+
+        self.last_tick_processed = self.clock.ticks;
+    }
+
     #[allow(dead_code)]
-    fn test_draw_picture(&mut self, name: &str, offset: &Vertex) {
-        self.pictures
-            .test_draw(&mut self.canvas, &self.palette, name, offset);
+    fn test_draw_picture(&mut self, pixels: &mut Pixels, name: &str, offset: &Vertex) {
+        self.pictures.test_draw(pixels, &self.palette, name, offset);
     }
 
-    fn render(&mut self) {
-        if self.viewing_map {
-            self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-            self.canvas.clear();
+    // Render either the 3D view or the 2D map into the frame buffer
+    pub fn render(&mut self, pixels: &mut Pixels) {
+        pixels.clear();
+        self.palette
+            .set_palette(self.palette_effects.palette_index());
 
-            self.draw_map_linedefs();
-            self.draw_map_player();
+        if self.viewing_map {
+            self.draw_map_linedefs(pixels);
+            self.draw_map_player(pixels);
         } else {
-            // Create the texture + pixels for the renderer
-            let texture_creator: TextureCreator<_> = self.canvas.texture_creator();
-            let mut texture = texture_creator
-                .create_texture_streaming(PixelFormatEnum::RGB24, SCREEN_WIDTH, SCREEN_HEIGHT)
-                .unwrap();
-
-            let mut pixels = Pixels::new();
-
             Renderer::new(
-                &mut pixels,
+                pixels,
                 &self.map,
                 &self.map_objects,
                 &mut self.textures,
@@ -513,39 +779,523 @@ impl Game {
                 Rc::clone(&self.sky_texture),
                 &mut self.flats,
                 &self.palette,
+                &self.lighting,
                 &self.player,
-                self.clock.timestamp,
+                self.clock.ticks,
             )
             .render();
+        }
+    }
 
-            texture
-                .with_lock(None, |buffer: &mut [u8], _pitch: usize| {
-                    buffer.copy_from_slice(pixels.pixels.as_ref());
-                })
-                .unwrap();
+    // Toggle the invulnerability colormap
+    pub fn toggle_invulnerability(&mut self) {
+        self.lighting.fixed_colormap = match self.lighting.fixed_colormap {
+            Some(_) => None,
+            None => Some(INVULNERABILITY_COLORMAP),
+        };
+    }
 
-            let screen_rect = Rect::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT);
-            self.canvas
-                .copy(&texture, screen_rect, screen_rect)
-                .unwrap();
-        }
+    // Toggle between the 3D view and the 2D map
+    pub fn set_viewing_map(&mut self, viewing_map: bool) {
+        self.viewing_map = viewing_map;
+    }
 
-        self.canvas.present();
+    // Save a rendered frame to the next free doomNNNN or automapNNNN file
+    pub fn save_screenshot(&self, pixels: &Pixels) -> Result<PathBuf, io::Error> {
+        let prefix = if self.viewing_map { "automap" } else { "doom" };
+        save_screenshot(
+            pixels,
+            &self.screenshot_directory,
+            prefix,
+            self.screenshot_format,
+        )
     }
 
-    pub fn main_loop(&mut self) {
-        let mut event_pump = self.sdl_context.event_pump().unwrap();
+    // Render a single frame into a new frame buffer
+    pub fn render_frame(&mut self) -> Pixels {
+        let mut pixels = Pixels::new();
+        self.render(&mut pixels);
+
+        pixels
+    }
+
+    // Open a window and run the game until it's quit
+    pub fn main_loop(&mut self) -> Result<(), String> {
+        let sdl_context = sdl2::init()?;
+        let video_subsystem = sdl_context.video()?;
+
+        let window = video_subsystem
+            .window(TITLE, SCREEN_WIDTH, SCREEN_HEIGHT)
+            .position_centered()
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let mut canvas = window
+            .into_canvas()
+            .software()
+            .present_vsync()
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        // Create the texture the frame buffer is copied to
+        let texture_creator: TextureCreator<_> = canvas.texture_creator();
+        let mut texture = texture_creator
+            .create_texture_streaming(PixelFormatEnum::RGB24, SCREEN_WIDTH, SCREEN_HEIGHT)
+            .map_err(|e| e.to_string())?;
+
+        let mut event_pump = sdl_context.event_pump()?;
+        let mut pixels = Pixels::new();
+
+        // Relative mouse mode hides the cursor and keeps it in the window
+        let mouse_util = sdl_context.mouse();
+        let mut mouse_grabbed = !self.mouse.grabbed;
 
         loop {
             let t0 = Instant::now();
 
-            self.render();
+            if mouse_grabbed != self.mouse.grabbed {
+                mouse_grabbed = self.mouse.grabbed;
+                mouse_util.set_relative_mouse_mode(mouse_grabbed);
+            }
+
+            self.render(&mut pixels);
+
+            texture.with_lock(None, |buffer: &mut [u8], _pitch: usize| {
+                buffer.copy_from_slice(pixels.pixels.as_ref());
+            })?;
+
+            let screen_rect = Rect::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT);
+            canvas.copy(&texture, screen_rect, screen_rect)?;
+            canvas.present();
+
+            if self.take_screenshot {
+                self.take_screenshot = false;
+                match self.save_screenshot(&pixels) {
+                    Ok(path) => println!("Saved screenshot {}", path.display()),
+                    Err(error) => eprintln!("Unable to save screenshot: {}", error),
+                }
+            }
 
             if self.process_events(&mut event_pump) {
                 break;
             }
 
             self.evolve(&t0);
+
+            if let Some(level_exit) = self.level_exit {
+                match level_exit {
+                    LevelExit::Normal => println!("Exited the level"),
+                    LevelExit::Secret => println!("Exited the level to the secret level"),
+                }
+                break;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_maps::{square_room, TestMapBuilder, TEST_MAP_NAME};
+
+    fn new_game() -> Game {
+        Game::new(
+            Rc::new(square_room().build()),
+            TEST_MAP_NAME,
+            100,
+            false,
+            false,
+            None,
+        )
+        .unwrap()
+    }
+
+    // A game on the test map with the player at x, y, facing east
+    fn new_game_at(test_map: TestMapBuilder, x: f32, y: f32) -> Game {
+        let override_player = OverridePlayer {
+            position: Vertex::new(x, y),
+            angle: 0.0,
+            view_z: None,
+            pitch: None,
+        };
+        Game::new(
+            Rc::new(test_map.build()),
+            TEST_MAP_NAME,
+            100,
+            false,
+            false,
+            Some(override_player),
+        )
+        .unwrap()
+    }
+
+    fn get_pixel(pixels: &Pixels, x: u32, y: u32) -> [u8; 3] {
+        let offset = 3 * (y * SCREEN_WIDTH + x) as usize;
+        pixels.pixels[offset..offset + 3].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_render_frame_headless() {
+        let mut game = new_game();
+        game.run_tics(10);
+        assert_eq!(game.clock.ticks, 10);
+
+        let pixels = game.render_frame();
+        assert_eq!(
+            pixels.pixels.len(),
+            (SCREEN_WIDTH * SCREEN_HEIGHT * 3) as usize
+        );
+
+        // The player is looking at the east wall, which fills the middle of the screen
+        assert_ne!(
+            get_pixel(&pixels, SCREEN_WIDTH / 2, SCREEN_HEIGHT / 2),
+            [0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn test_render_is_deterministic() {
+        let mut game1 = new_game();
+        let mut game2 = new_game();
+        game1.run_tics(35);
+        game2.run_tics(35);
+
+        assert!(game1.render_frame().pixels == game2.render_frame().pixels);
+    }
+
+    #[test]
+    fn test_invulnerability_colormap() {
+        let mut game = new_game();
+        let pixels = game.render_frame();
+
+        game.toggle_invulnerability();
+        let invulnerable_pixels = game.render_frame();
+        assert!(pixels.pixels != invulnerable_pixels.pixels);
+
+        game.toggle_invulnerability();
+        assert!(pixels.pixels == game.render_frame().pixels);
+    }
+
+    #[test]
+    fn test_palette_effects() {
+        let mut game = new_game();
+        let pixels = game.render_frame();
+
+        game.palette_effects.add_damage(20);
+        assert!(pixels.pixels != game.render_frame().pixels);
+        assert_eq!(game.palette.current(), 4);
+
+        // The red fades back to the normal palette
+        game.run_tics(20);
+        assert!(pixels.pixels == game.render_frame().pixels);
+        assert_eq!(game.palette.current(), 0);
+    }
+
+    #[test]
+    fn test_camera_modes() {
+        // Walking east stops at the east wall
+        let mut game = new_game();
+        assert_eq!(game.player.view_z, PLAYER_EYE_HEIGHT);
+        game.pressed_keys.insert(Keycode::Up);
+        game.run_tics(20);
+        assert!(game.player.position.x <= 112.0);
+
+        // Noclip walks through it
+        game.toggle_camera_mode(CameraMode::Noclip);
+        game.run_tics(5);
+        assert!(game.player.position.x > 128.0);
+        assert!((game.player.view_z - PLAYER_EYE_HEIGHT).abs() <= 8.0); // View bob
+
+        // The fly camera goes up through the ceiling
+        game.toggle_camera_mode(CameraMode::Fly);
+        game.pressed_keys.clear();
+        game.pressed_keys.insert(Keycode::PageUp);
+        game.run_tics(20);
+        assert!(game.player.view_z > 128.0);
+
+        // Going back to normal puts the eyes back above the floor
+        game.toggle_camera_mode(CameraMode::Fly);
+        assert_eq!(game.camera_mode, CameraMode::Normal);
+        assert_eq!(game.player.view_z, PLAYER_EYE_HEIGHT);
+    }
+
+    #[test]
+    fn test_override_view_z() {
+        let override_player = OverridePlayer {
+            position: Vertex::new(64.0, 64.0),
+            angle: 0.0,
+            view_z: Some(200.0),
+            pitch: None,
+        };
+        let mut game = Game::new(
+            Rc::new(square_room().build()),
+            TEST_MAP_NAME,
+            100,
+            false,
+            false,
+            Some(override_player),
+        )
+        .unwrap();
+        assert_eq!(game.camera_mode, CameraMode::Fly);
+        assert_eq!(game.player.view_z, 200.0);
+
+        // Looking from above the ceiling renders a different frame
+        let pixels = game.render_frame();
+        game.set_camera_mode(CameraMode::Normal);
+        assert!(pixels.pixels != game.render_frame().pixels);
+    }
+
+    #[test]
+    fn test_looking_up_at_the_sky() {
+        let mut test_map = square_room();
+        test_map.sector_mut(0).ceiling_texture = "F_SKY1";
+        let mut game = Game::new(
+            Rc::new(test_map.build()),
+            TEST_MAP_NAME,
+            100,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+        let pixels = game.render_frame();
+
+        // Looking up is limited
+        game.pressed_keys.insert(Keycode::Home);
+        game.run_tics(35);
+        assert_eq!(game.player.pitch, MAX_PITCH);
+        let looking_up_pixels = game.render_frame();
+        assert!(pixels.pixels != looking_up_pixels.pixels);
+
+        // The sky fills the top of the screen without gaps
+        let sky_color = game.palette.colors[192];
+        let sky_color = [sky_color.r, sky_color.g, sky_color.b];
+        for x in 0..SCREEN_WIDTH {
+            assert_eq!(get_pixel(&looking_up_pixels, x, 0), sky_color);
+        }
+
+        // Centering the view brings back the first frame
+        game.pressed_keys.clear();
+        game.pressed_keys.insert(Keycode::Delete);
+        game.run_tics(1);
+        assert!(pixels.pixels == game.render_frame().pixels);
+    }
+
+    #[test]
+    fn test_mouse() {
+        use sdl2::mouse::MouseButton;
+
+        // Moving the mouse right turns right, moving it away moves forward
+        let mut game = new_game();
+        game.mouse.motion(100, -40);
+        game.run_tics(1);
+        assert!(game.player.angle < 0.0);
+        assert!(game.player.position.x > 64.0);
+
+        // With novert, the mouse doesn't move the player
+        let mut game = new_game();
+        game.mouse.novert = true;
+        game.mouse.motion(0, -40);
+        game.run_tics(1);
+        assert_eq!(game.player.position.x, 64.0);
+
+        // With mouse look, moving the mouse away looks up
+        game.mouse.look = true;
+        game.mouse.motion(0, -40);
+        game.run_tics(1);
+        assert!(game.player.pitch > 0.0);
+
+        // Holding the right button strafes instead of turning
+        let mut game = new_game();
+        game.mouse.button_down(MouseButton::Right);
+        game.mouse.motion(40, 0);
+        game.run_tics(1);
+        assert_eq!(game.player.angle, 0.0);
+        assert!(game.player.position.y < 64.0);
+    }
+
+    #[test]
+    fn test_walk_over_specials() {
+        use crate::test_maps::two_rooms;
+
+        // The line between the rooms is a W1 exit
+        let mut test_map = two_rooms();
+        test_map.linedef_mut(3).special_type = 52;
+        let mut game = new_game_at(test_map, 100.0, 64.0);
+
+        // Noclip doesn't activate specials
+        game.set_camera_mode(CameraMode::Noclip);
+        game.move_player(&Vertex::new(40.0, 0.0));
+        game.move_player(&Vertex::new(-40.0, 0.0));
+        assert_eq!(game.level_exit, None);
+
+        game.set_camera_mode(CameraMode::Normal);
+        game.move_player(&Vertex::new(20.0, 0.0));
+        assert_eq!(game.level_exit, None);
+        game.move_player(&Vertex::new(20.0, 0.0));
+        assert_eq!(game.level_exit, Some(LevelExit::Normal));
+        assert_eq!(game.map.linedefs[3].special_type.get(), 0);
+    }
+
+    #[test]
+    fn test_teleport() {
+        use crate::test_maps::two_rooms;
+
+        // The line between the rooms is a teleporter to the east side of the east room
+        let mut test_map = two_rooms();
+        test_map.sector_mut(1).tag_number = 1;
+        test_map.linedef_mut(3).special_type = 39;
+        test_map.linedef_mut(3).sector_tag = 1;
+        test_map.add_thing(224, 64, 270, 14);
+        let mut game = Game::new(
+            Rc::new(test_map.build()),
+            TEST_MAP_NAME,
+            100,
+            false,
+            false,
+            None,
+        )
+        .unwrap();
+        game.player.momentum_z = -4.0;
+        let objects = game.map_objects.objects.len();
+
+        game.move_player(&Vertex::new(70.0, 0.0));
+        assert_eq!(game.player.position.x, 224.0);
+        assert_eq!(game.player.position.y, 64.0);
+        assert_eq!(game.player.angle, 270f32.to_radians());
+        assert_eq!(game.player.z, 16.0);
+        assert_eq!(game.player.momentum_z, 0.0);
+        assert_eq!(game.map.linedefs[3].special_type.get(), 0);
+
+        // Fog where the player left and in front of the destination, until it's
+        // done
+        assert_eq!(game.map_objects.objects.len(), objects + 2);
+        for _ in 0..12 * 6 {
+            game.tick_thinkers();
         }
+        assert_eq!(game.map_objects.objects.len(), objects);
+    }
+
+    #[test]
+    fn test_use() {
+        use crate::test_maps::door_rooms;
+
+        let mut test_map = door_rooms();
+        test_map.linedef_mut(3).special_type = 1;
+        let mut game = new_game_at(test_map, 100.0, 64.0);
+
+        // Using the door opens it
+        assert_eq!(game.key_bindings.actions(Keycode::Space), vec![Action::Use]);
+        assert!(!game.start_action(Action::Use));
+        game.run_tics(10);
+        assert_eq!(game.map.sectors[1].borrow().ceiling_height, 20);
+    }
+
+    #[test]
+    fn test_door_reopens_on_the_player() {
+        use crate::doors::{use_door, DoorType};
+        use crate::test_maps::door_rooms;
+
+        // The player stands in the doorway
+        let mut test_map = door_rooms();
+        test_map.linedef_mut(3).special_type = 1;
+        let mut game = new_game_at(test_map, 136.0, 64.0);
+
+        let linedef = Rc::clone(&game.map.linedefs[3]);
+        let door_type = DoorType::Normal;
+        assert!(use_door(&game.map, &linedef, door_type, &mut game.thinkers));
+        game.run_tics(63);
+        let door = Rc::clone(&game.map.sectors[1]);
+        assert_eq!(door.borrow().ceiling_height, 124);
+
+        // The closing door stops above the player's head and goes back up
+        let mut lowest = 124;
+        for _ in 0..300 {
+            game.run_tics(1);
+            lowest = lowest.min(door.borrow().ceiling_height);
+        }
+        assert_eq!(lowest, 56);
+        assert!(door.borrow().ceiling_height > 56);
+
+        // Without the player in the way, it closes
+        game.player.position = Vertex::new(64.0, 64.0);
+        game.run_tics(500);
+        assert_eq!(door.borrow().ceiling_height, 0);
+        assert!(game.thinkers.is_empty());
+    }
+
+    #[test]
+    fn test_player_rides_a_lift() {
+        use crate::platforms::{do_platform, PlatformType};
+        use crate::test_maps::two_rooms;
+
+        // The player stands on the east floor, which is a lift
+        let mut test_map = two_rooms();
+        test_map.sector_mut(1).tag_number = 1;
+        test_map.linedef_mut(0).sector_tag = 1;
+        let mut game = new_game_at(test_map, 192.0, 64.0);
+        game.run_tics(1);
+        assert_eq!(game.player.z, 16.0);
+
+        // The player goes down and up with the lift
+        let linedef = Rc::clone(&game.map.linedefs[0]);
+        let lift = PlatformType::DownWaitUpStay;
+        assert!(do_platform(&game.map, &linedef, lift, &mut game.thinkers));
+        game.run_tics(2);
+        assert_eq!(game.player.z, 8.0);
+        game.run_tics(3 + 105);
+        assert_eq!(game.player.z, 0.0);
+        game.run_tics(2);
+        assert_eq!(game.player.z, 8.0);
+        game.run_tics(3);
+        assert_eq!(game.player.z, 16.0);
+        assert!(game.thinkers.is_empty());
+    }
+
+    #[test]
+    fn test_crusher_slows_down_on_the_player() {
+        use crate::ceilings::{do_ceiling, CeilingType};
+        use crate::test_maps::two_rooms;
+
+        // The player stands under a crusher in the east room, with the floor at 16
+        let mut test_map = two_rooms();
+        test_map.sector_mut(1).tag_number = 1;
+        test_map.linedef_mut(0).sector_tag = 1;
+        let mut game = new_game_at(test_map, 192.0, 64.0);
+
+        let linedef = Rc::clone(&game.map.linedefs[0]);
+        let crusher = CeilingType::CrushAndRaise;
+        assert!(do_ceiling(&game.map, &linedef, crusher, &mut game.thinkers));
+        let sector = Rc::clone(&game.map.sectors[1]);
+
+        // It comes down a unit per tic until it reaches the player's head at 72, and
+        // then moves a unit every 8 tics
+        game.run_tics(41);
+        assert_eq!(sector.borrow().ceiling_height, 71);
+        game.run_tics(16);
+        assert_eq!(sector.borrow().ceiling_height, 69);
+
+        // At the bottom it goes back up at full speed
+        game.run_tics(45 * 8);
+        assert_eq!(sector.borrow().ceiling_height, 24);
+        game.run_tics(8);
+        let height = sector.borrow().ceiling_height;
+        game.run_tics(10);
+        assert_eq!(sector.borrow().ceiling_height, height + 10);
+    }
+
+    #[test]
+    fn test_render_map() {
+        let mut game = new_game();
+        game.viewing_map = true;
+        let pixels = game.render_frame();
+
+        // The bottom-left corner of the room is in the bottom-left corner of the map
+        assert_eq!(
+            get_pixel(&pixels, MAP_BORDER, SCREEN_HEIGHT - MAP_BORDER - 1),
+            [255, 0, 0]
+        );
     }
 }
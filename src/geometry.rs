@@ -81,6 +81,25 @@ impl Line {
         Ok(Vertex::new(px, py))
     }
 
+    // Where does our line cross another line, as a fraction of our length from the
+    // start? Both lines end at their end points. Returns None if they don't cross or
+    // are parallel.
+    pub fn crossing_fraction(&self, other: &Line) -> Option<f32> {
+        let direction = &self.end - &self.start;
+        let other_direction = &other.end - &other.start;
+        let denominator = direction.cross_product(&other_direction);
+        if denominator.abs() < 0.001 {
+            return None;
+        }
+
+        let offset = &other.start - &self.start;
+        let fraction = offset.cross_product(&other_direction) / denominator;
+        let other_fraction = offset.cross_product(&direction) / denominator;
+
+        ((0.0..=1.0).contains(&fraction) && (0.0..=1.0).contains(&other_fraction))
+            .then_some(fraction)
+    }
+
     pub fn length(&self) -> f32 {
         ((self.start.x - self.end.x).powi(2) + (self.start.y - self.end.y).powi(2)).sqrt()
     }
@@ -0,0 +1,239 @@
+// Golden-image regression tests for the renderer. Frames of the test maps are rendered
+// headlessly from fixed camera positions and compared against the reference images in
+// tests/golden. After an intended rendering change, run the tests with UPDATE_GOLDEN=1
+// to write new reference images. When a frame doesn't match, the rendered frame and a
+// diff image with the changed pixels in red are written to target/golden-diffs.
+use sdl2::pixels::Color;
+use std::env;
+use std::f32::consts::PI;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::game::{Game, OverridePlayer, SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::map::Vertex;
+use crate::renderer::Pixels;
+use crate::screenshot::{write_screenshot, ScreenshotFormat};
+use crate::test_maps::{square_room, two_rooms, TestMapBuilder, TEST_MAP_NAME};
+
+const PIXEL_TOLERANCE: u8 = 2; // Maximum difference of a color channel of a pixel
+const MAX_DIFFERENT_PIXELS: usize = 0; // Amount of pixels allowed to exceed the tolerance
+
+fn golden_directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+fn diff_directory() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target")
+        .join("golden-diffs")
+}
+
+// Render a frame of a test map with the player at x, y looking at angle degrees
+fn render(
+    test_map: TestMapBuilder,
+    (x, y): (f32, f32),
+    angle: f32,
+    tics: u32,
+    viewing_map: bool,
+) -> Pixels {
+    let override_player = OverridePlayer {
+        position: Vertex::new(x, y),
+        angle: angle * PI / 180.0,
+        view_z: None,
+        pitch: None,
+    };
+
+    render_player(test_map, override_player, tics, viewing_map)
+}
+
+// Render a frame of a test map with an overridden player, e.g. to look up or down
+fn render_player(
+    test_map: TestMapBuilder,
+    override_player: OverridePlayer,
+    tics: u32,
+    viewing_map: bool,
+) -> Pixels {
+    let mut game = Game::new(
+        Rc::new(test_map.build()),
+        TEST_MAP_NAME,
+        100,
+        false,
+        false,
+        Some(override_player),
+    )
+    .unwrap();
+
+    game.run_tics(tics);
+    game.set_viewing_map(viewing_map);
+    game.render_frame()
+}
+
+// Read a reference image, returns None if it doesn't exist
+fn read_png(path: &Path) -> Option<Vec<u8>> {
+    let decoder = png::Decoder::new(File::open(path).ok()?);
+    let mut reader = decoder.read_info().unwrap();
+    let mut data = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut data).unwrap();
+
+    assert_eq!(
+        (info.width, info.height, info.color_type),
+        (SCREEN_WIDTH, SCREEN_HEIGHT, png::ColorType::Rgb),
+        "Unexpected format of reference image {}",
+        path.display()
+    );
+
+    Some(data)
+}
+
+// Compare two frames. Returns the amount of pixels that differ by more than the
+// tolerance and a diff image. The diff image is a dimmed copy of the expected frame
+// with the differing pixels in red.
+fn diff_frames(expected: &[u8], actual: &[u8]) -> (usize, Pixels) {
+    let mut diff = Pixels::new();
+    let mut count = 0;
+
+    for (i, (expected_pixel, actual_pixel)) in expected.chunks(3).zip(actual.chunks(3)).enumerate()
+    {
+        let is_different = expected_pixel
+            .iter()
+            .zip(actual_pixel)
+            .any(|(e, a)| e.abs_diff(*a) > PIXEL_TOLERANCE);
+
+        let color = if is_different {
+            count += 1;
+            Color::RGB(255, 0, 0)
+        } else {
+            let gray = (expected_pixel.iter().map(|&c| c as u32).sum::<u32>() / 9) as u8;
+            Color::RGB(gray, gray, gray)
+        };
+
+        let x = i % SCREEN_WIDTH as usize;
+        let y = i / SCREEN_WIDTH as usize;
+        diff.set(x, y, &color);
+    }
+
+    (count, diff)
+}
+
+// Compare a frame to the reference image with the same name
+fn assert_golden(name: &str, pixels: &Pixels) {
+    let path = golden_directory().join(format!("{}.png", name));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(golden_directory()).unwrap();
+        write_screenshot(pixels, &path, ScreenshotFormat::Png).unwrap();
+        return;
+    }
+
+    let expected = read_png(&path).unwrap_or_else(|| {
+        panic!(
+            "Missing reference image {}, run the tests with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+
+    let (count, diff) = diff_frames(&expected, &pixels.pixels);
+    if count > MAX_DIFFERENT_PIXELS {
+        let directory = diff_directory();
+        fs::create_dir_all(&directory).unwrap();
+
+        let actual_path = directory.join(format!("{}-actual.png", name));
+        let diff_path = directory.join(format!("{}-diff.png", name));
+        write_screenshot(pixels, &actual_path, ScreenshotFormat::Png).unwrap();
+        write_screenshot(&diff, &diff_path, ScreenshotFormat::Png).unwrap();
+
+        panic!(
+            "{} pixels differ from {}, see {} and {}",
+            count,
+            path.display(),
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn test_diff_frames() {
+    let mut expected = Pixels::new();
+    let mut actual = Pixels::new();
+    expected.set(10, 20, &Color::RGB(100, 100, 100));
+    actual.set(10, 20, &Color::RGB(100 + PIXEL_TOLERANCE, 100, 100));
+    actual.set(30, 40, &Color::RGB(0, 0, 100));
+
+    let (count, diff) = diff_frames(&expected.pixels, &actual.pixels);
+    assert_eq!(count, 1);
+
+    let offset = 3 * (40 * SCREEN_WIDTH as usize + 30);
+    assert_eq!(&diff.pixels[offset..offset + 3], &[255, 0, 0]);
+    let offset = 3 * (20 * SCREEN_WIDTH as usize + 10);
+    assert_eq!(&diff.pixels[offset..offset + 3], &[33, 33, 33]);
+}
+
+#[test]
+fn test_golden_square_room_wall() {
+    let pixels = render(square_room(), (32.0, 64.0), 0.0, 0, false);
+    assert_golden("square_room_wall", &pixels);
+}
+
+#[test]
+fn test_golden_square_room_scrolling_wall() {
+    // The east wall scrolls one unit per tic, half a checkerboard square in 4 tics
+    let mut test_map = square_room();
+    test_map.linedef_mut(2).special_type = 48;
+    let pixels = render(test_map, (32.0, 64.0), 0.0, 4, false);
+    assert_golden("square_room_scrolling_wall", &pixels);
+}
+
+#[test]
+fn test_golden_square_room_corner() {
+    let pixels = render(square_room(), (32.0, 32.0), 45.0, 0, false);
+    assert_golden("square_room_corner", &pixels);
+}
+
+#[test]
+fn test_golden_two_rooms_step() {
+    let pixels = render(two_rooms(), (16.0, 64.0), 0.0, 0, false);
+    assert_golden("two_rooms_step", &pixels);
+}
+
+#[test]
+fn test_golden_two_rooms_looking_back() {
+    let pixels = render(two_rooms(), (240.0, 32.0), 160.0, 35, false);
+    assert_golden("two_rooms_looking_back", &pixels);
+}
+
+#[test]
+fn test_golden_sky_looking_up() {
+    // The top row of the sky is stretched above the top of the sky texture
+    let mut test_map = square_room();
+    test_map.sector_mut(0).ceiling_texture = "F_SKY1";
+    let override_player = OverridePlayer {
+        position: Vertex::new(32.0, 64.0),
+        angle: 0.0,
+        view_z: None,
+        pitch: Some(PI / 6.0),
+    };
+    let pixels = render_player(test_map, override_player, 0, false);
+    assert_golden("sky_looking_up", &pixels);
+}
+
+#[test]
+fn test_golden_two_rooms_looking_down() {
+    let override_player = OverridePlayer {
+        position: Vertex::new(16.0, 64.0),
+        angle: 0.0,
+        view_z: None,
+        pitch: Some(-PI / 6.0),
+    };
+    let pixels = render_player(two_rooms(), override_player, 0, false);
+    assert_golden("two_rooms_looking_down", &pixels);
+}
+
+#[test]
+fn test_golden_two_rooms_map() {
+    let pixels = render(two_rooms(), (64.0, 64.0), 90.0, 0, true);
+    assert_golden("two_rooms_map", &pixels);
+}
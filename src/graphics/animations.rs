@@ -0,0 +1,310 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::wad::{WadError, WadFile};
+
+// Animated textures and flats cycle through a range of textures or flats. The range is
+// defined by a start and end name, the textures or flats in between are taken from the
+// order in which they are defined in the WAD file.
+// https://doomwiki.org/wiki/Animated_flat
+
+const ANIMATION_SPEED: u32 = 8; // Tics per frame in the built-in definitions
+const ANIMATED_RECORD_SIZE: usize = 23; // Size of an entry in an ANIMATED lump
+const ANIMATED_TERMINATOR: u8 = 0xff; // Type of the entry that ends an ANIMATED lump
+
+// The built-in animations, see animdefs in p_spec.c. The names are the end and
+// start of the cycle.
+const ANIMDEFS: [(bool, &str, &str); 22] = [
+    (false, "NUKAGE3", "NUKAGE1"),
+    (false, "FWATER4", "FWATER1"),
+    (false, "SWATER4", "SWATER1"),
+    (false, "LAVA4", "LAVA1"),
+    (false, "BLOOD3", "BLOOD1"),
+    // Doom II flat animations
+    (false, "RROCK08", "RROCK05"),
+    (false, "SLIME04", "SLIME01"),
+    (false, "SLIME08", "SLIME05"),
+    (false, "SLIME12", "SLIME09"),
+    (true, "BLODGR4", "BLODGR1"),
+    (true, "SLADRIP3", "SLADRIP1"),
+    (true, "BLODRIP4", "BLODRIP1"),
+    (true, "FIREWALA", "FIREWALL"),
+    (true, "GSTFONT3", "GSTFONT1"),
+    (true, "FIRELAVA", "FIRELAV3"),
+    (true, "FIREMAG3", "FIREMAG1"),
+    (true, "FIREBLU2", "FIREBLU1"),
+    (true, "ROCKRED3", "ROCKRED1"),
+    (true, "BFALL4", "BFALL1"),
+    (true, "SFALL4", "SFALL1"),
+    (true, "WFALL4", "WFALL1"),
+    (true, "DBRAIN4", "DBRAIN1"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationDefinition {
+    pub is_texture: bool,   // A wall texture or a flat
+    pub start_name: String, // First texture or flat in the cycle
+    pub end_name: String,   // Last texture or flat in the cycle
+    pub speed: u32,         // Tics per frame
+}
+
+impl AnimationDefinition {
+    // Load the definitions from a Boom ANIMATED lump, which replaces the built-in
+    // definitions. Without the lump, the built-in definitions are used.
+    pub fn load(wad_file: &WadFile) -> Result<Vec<AnimationDefinition>, WadError> {
+        let Ok(dir_entry) = wad_file.get_dir_entry("ANIMATED") else {
+            return Ok(ANIMDEFS
+                .iter()
+                .map(|(is_texture, end_name, start_name)| AnimationDefinition {
+                    is_texture: *is_texture,
+                    start_name: start_name.to_string(),
+                    end_name: end_name.to_string(),
+                    speed: ANIMATION_SPEED,
+                })
+                .collect());
+        };
+
+        // An entry is a type byte (0 for flats, 1 for textures), two nul terminated
+        // 9 byte names and a 32 bit speed. A type of 0xff ends the list.
+        let mut definitions = Vec::new();
+        for index in 0.. {
+            let record_offset = index * ANIMATED_RECORD_SIZE;
+            dir_entry.check_range(record_offset, 1)?;
+            let offset = dir_entry.offset as usize + record_offset;
+
+            let animation_type = wad_file.file[offset];
+            if animation_type == ANIMATED_TERMINATOR {
+                break;
+            }
+
+            dir_entry.check_range(record_offset, ANIMATED_RECORD_SIZE)?;
+            let speed = wad_file.read_u32(offset + 19);
+            if speed == 0 {
+                return Err(WadError::InvalidLump {
+                    name: dir_entry.name.clone(),
+                    offset: record_offset,
+                    reason: "animation speed is zero".into(),
+                });
+            }
+
+            definitions.push(AnimationDefinition {
+                is_texture: animation_type & 1 != 0,
+                end_name: wad_file.read_lump_name(offset + 1).to_ascii_uppercase(),
+                start_name: wad_file.read_lump_name(offset + 10).to_ascii_uppercase(),
+                speed,
+            });
+        }
+
+        Ok(definitions)
+    }
+}
+
+// A cycle of textures or flats
+#[derive(Debug)]
+struct Animation {
+    frames: Vec<String>, // Texture or flat names in the cycle
+    speed: u32,          // Tics per frame
+}
+
+// A map of texture or flat names to the animation they are part of
+#[derive(Debug, Default)]
+pub struct Animations {
+    map: HashMap<String, (Rc<Animation>, usize)>, // The animation and the position in the cycle
+}
+
+impl Animations {
+    // Make the animations of either textures or flats. The names are all textures or
+    // flats in the order of the WAD file. Definitions of which the start or end
+    // doesn't exist, or of which the end comes before the start, are skipped.
+    pub fn new(
+        definitions: &[AnimationDefinition],
+        is_texture: bool,
+        names: &[String],
+    ) -> Animations {
+        let mut map = HashMap::new();
+
+        for definition in definitions.iter().filter(|d| d.is_texture == is_texture) {
+            let position = |name: &str| names.iter().position(|n| n.eq_ignore_ascii_case(name));
+            let (Some(start), Some(end)) = (
+                position(&definition.start_name),
+                position(&definition.end_name),
+            ) else {
+                continue;
+            };
+
+            if end <= start {
+                continue;
+            }
+
+            let animation = Rc::new(Animation {
+                frames: names[start..=end]
+                    .iter()
+                    .map(|n| n.to_ascii_uppercase())
+                    .collect(),
+                speed: definition.speed,
+            });
+
+            for (i, name) in animation.frames.iter().enumerate() {
+                map.insert(name.clone(), (Rc::clone(&animation), i));
+            }
+        }
+
+        Animations { map }
+    }
+
+    // Get the name of the texture or flat to show at a tic. Every frame in the cycle
+    // moves on, so that all textures in a cycle can be used as a starting point.
+    // See P_UpdateSpecials in p_spec.c.
+    pub fn get(&self, name: &str, tic: u32) -> Option<&str> {
+        let (animation, position) = self.map.get(&name.to_ascii_uppercase())?;
+        let frame = (tic / animation.speed) as usize + position;
+
+        Some(&animation.frames[frame % animation.frames.len()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::{Flats, Textures};
+    use crate::test_maps::{add_graphics_to_wad, square_room};
+    use crate::wad_builder::{WadBuilder, WadType};
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn test_builtin_definitions() {
+        let wad_file = square_room().build();
+        let definitions = AnimationDefinition::load(&wad_file).unwrap();
+
+        assert_eq!(definitions.len(), 22);
+        assert_eq!(
+            definitions[10],
+            AnimationDefinition {
+                is_texture: true,
+                start_name: "SLADRIP1".into(),
+                end_name: "SLADRIP3".into(),
+                speed: 8,
+            }
+        );
+    }
+
+    #[test]
+    fn test_animated_lump() {
+        let mut animated = vec![1];
+        animated.extend(b"FIREBLU2\0");
+        animated.extend(b"FIREBLU1\0");
+        animated.extend(4u32.to_le_bytes());
+        animated.push(0);
+        animated.extend(b"FLOOR4_8\0");
+        animated.extend(b"MYFLAT1\0\0");
+        animated.extend(16u32.to_le_bytes());
+        animated.push(ANIMATED_TERMINATOR);
+
+        let mut wad_builder = WadBuilder::new(WadType::Pwad);
+        wad_builder.add_lump("ANIMATED", animated).unwrap();
+        let wad_file = WadFile::new(wad_builder.write().unwrap()).unwrap();
+
+        let definitions = AnimationDefinition::load(&wad_file).unwrap();
+        assert_eq!(definitions.len(), 2);
+        assert!(definitions[0].is_texture);
+        assert_eq!(definitions[0].start_name, "FIREBLU1");
+        assert_eq!(definitions[0].speed, 4);
+        assert!(!definitions[1].is_texture);
+        assert_eq!(definitions[1].start_name, "MYFLAT1");
+        assert_eq!(definitions[1].end_name, "FLOOR4_8");
+
+        // A missing terminator is an error
+        let mut wad_builder = WadBuilder::new(WadType::Pwad);
+        wad_builder.add_lump("ANIMATED", vec![1; 23]).unwrap();
+        let wad_file = WadFile::new(wad_builder.write().unwrap()).unwrap();
+        assert!(AnimationDefinition::load(&wad_file).is_err());
+    }
+
+    #[test]
+    fn test_cycle() {
+        let definitions = vec![AnimationDefinition {
+            is_texture: true,
+            start_name: "SLADRIP1".into(),
+            end_name: "SLADRIP3".into(),
+            speed: 8,
+        }];
+        let animations = Animations::new(
+            &definitions,
+            true,
+            &names(&["STARTAN3", "SLADRIP1", "SLADRIP2", "SLADRIP3", "SKY1"]),
+        );
+
+        assert_eq!(animations.get("STARTAN3", 0), None);
+        assert_eq!(animations.get("SLADRIP1", 0), Some("SLADRIP1"));
+        assert_eq!(animations.get("SLADRIP1", 7), Some("SLADRIP1"));
+        assert_eq!(animations.get("sladrip1", 8), Some("SLADRIP2"));
+        assert_eq!(animations.get("SLADRIP1", 16), Some("SLADRIP3"));
+        assert_eq!(animations.get("SLADRIP1", 24), Some("SLADRIP1"));
+
+        // Starting from the middle of the cycle
+        assert_eq!(animations.get("SLADRIP3", 0), Some("SLADRIP3"));
+        assert_eq!(animations.get("SLADRIP3", 8), Some("SLADRIP1"));
+
+        // Flat animations are ignored when making texture animations
+        assert!(
+            Animations::new(&definitions, false, &names(&["SLADRIP1", "SLADRIP3"]))
+                .get("SLADRIP1", 8)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_invalid_cycles_are_skipped() {
+        let definition = |start_name: &str, end_name: &str| AnimationDefinition {
+            is_texture: false,
+            start_name: start_name.into(),
+            end_name: end_name.into(),
+            speed: 8,
+        };
+        let definitions = vec![
+            definition("NUKAGE1", "NUKAGE3"),
+            definition("LAVA2", "LAVA1"),
+        ];
+        let animations =
+            Animations::new(&definitions, false, &names(&["LAVA1", "LAVA2", "NUKAGE1"]));
+
+        assert_eq!(animations.get("NUKAGE1", 8), None);
+        assert_eq!(animations.get("LAVA1", 8), None);
+    }
+
+    #[test]
+    fn test_animated_lump_in_pwad() {
+        // Animate the test textures and flats, in the order they are in the WAD
+        let mut animated = vec![1];
+        animated.extend(b"SKY1\0\0\0\0\0");
+        animated.extend(b"STARTAN3\0");
+        animated.extend(8u32.to_le_bytes());
+        animated.push(0);
+        animated.extend(b"CEIL3_5\0\0");
+        animated.extend(b"FLOOR4_8\0");
+        animated.extend(8u32.to_le_bytes());
+        animated.push(ANIMATED_TERMINATOR);
+
+        let mut wad_builder = WadBuilder::new(WadType::Iwad);
+        add_graphics_to_wad(&mut wad_builder);
+        let mut wad_file = WadFile::new(wad_builder.write().unwrap()).unwrap();
+        let mut pwad_builder = WadBuilder::new(WadType::Pwad);
+        pwad_builder.add_lump("ANIMATED", animated).unwrap();
+        wad_file.add_file(pwad_builder.write().unwrap()).unwrap();
+        let wad_file = Rc::new(wad_file);
+
+        let mut textures = Textures::new(&wad_file).unwrap();
+        assert_eq!(
+            textures.get_animated("STARTAN3", 7).unwrap().name,
+            "STARTAN3"
+        );
+        assert_eq!(textures.get_animated("STARTAN3", 8).unwrap().name, "SKY1");
+
+        let mut flats = Flats::new(&wad_file).unwrap();
+        assert_eq!(flats.get_animated("FLOOR4_8", 8).unwrap().name, "CEIL3_5");
+        assert_eq!(flats.get_animated("F_SKY1", 8).unwrap().name, "F_SKY1");
+    }
+}
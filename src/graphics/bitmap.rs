@@ -1,11 +1,9 @@
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
-use sdl2::render::Canvas;
-use sdl2::video::Window;
 use std::fmt;
 
 use crate::graphics::Palette;
 use crate::map::Vertex;
+use crate::renderer::Pixels;
 
 #[derive(Clone)]
 pub struct Bitmap {
@@ -25,28 +23,26 @@ impl Bitmap {
 
     // Draw the bitmap to the top-left corner
     #[allow(dead_code)]
-    pub fn test_flat_draw(&self, canvas: &mut Canvas<Window>, palette: &Palette, offset: &Vertex) {
-        canvas.set_draw_color(Color::RGB(0, 255, 255));
-        let rect = Rect::new(
+    pub fn test_flat_draw(&self, pixels: &mut Pixels, palette: &Palette, offset: &Vertex) {
+        pixels.fill_rect(
             offset.x as i32,
             offset.y as i32,
             self.width as u32 * 4,
             self.height as u32 * 4,
+            &Color::RGB(0, 255, 255),
         );
-        canvas.fill_rect(rect).unwrap();
 
         for x in 0..self.width as usize {
             for y in 0..self.height as usize {
                 if let Some(value) = self.pixels[y][x] {
                     let color = palette.colors[value as usize];
-                    canvas.set_draw_color(color);
-                    let rect = Rect::new(
+                    pixels.fill_rect(
                         offset.x as i32 + x as i32 * 4,
                         offset.y as i32 + y as i32 * 4,
                         4,
                         4,
+                        &color,
                     );
-                    canvas.fill_rect(rect).unwrap();
                 }
             }
         }
@@ -0,0 +1,27 @@
+use crate::wad::{WadError, WadFile};
+
+pub const COLORMAP_SIZE: usize = 256;
+pub const LIGHT_COLORMAPS: usize = 32; // Colormaps from full bright to full dark
+pub const INVULNERABILITY_COLORMAP: usize = 32; // Inverse grayscale
+
+pub struct Colormaps {
+    pub maps: Vec<[u8; COLORMAP_SIZE]>, // Maps of palette indexes to palette indexes
+}
+
+impl Colormaps {
+    pub fn new(wad_file: &WadFile) -> Result<Colormaps, WadError> {
+        // The COLORMAP lump has 34 maps of 256 bytes. The first 32 are light
+        // levels, followed by the invulnerability map and an all-black map
+        // which isn't used. Require everything up to the invulnerability map.
+        let dir_entry = wad_file.get_dir_entry("COLORMAP")?;
+        dir_entry.check_range(0, (INVULNERABILITY_COLORMAP + 1) * COLORMAP_SIZE)?;
+        let data = wad_file.get_lump_data(dir_entry);
+
+        let maps = data
+            .chunks_exact(COLORMAP_SIZE)
+            .map(|chunk| chunk.try_into().unwrap())
+            .collect();
+
+        Ok(Colormaps { maps })
+    }
+}
@@ -1,4 +1,6 @@
+mod animations;
 mod bitmap;
+mod colormaps;
 mod flats;
 mod palette;
 mod pictures;
@@ -6,6 +8,7 @@ mod sprites;
 mod textures;
 
 pub use bitmap::Bitmap;
+pub use colormaps::{Colormaps, INVULNERABILITY_COLORMAP, LIGHT_COLORMAPS};
 pub use flats::{Flat, Flats, FLAT_SIZE};
 pub use palette::Palette;
 pub use pictures::{Picture, Pictures};
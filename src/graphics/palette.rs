@@ -1,41 +1,64 @@
-use crate::game::Game;
-use crate::wad::WadFile;
+use crate::renderer::Pixels;
+use crate::wad::{WadError, WadFile};
 use sdl2::pixels::Color;
-use sdl2::rect::Rect;
+
+const NUM_PALETTES: usize = 14; // Palettes in the PLAYPAL lump
+const PALETTE_SIZE: usize = 768; // 256 colors of 8-bit R, G, B values
 
 pub struct Palette {
-    pub colors: [Color; 256], // Palette 0 in the PLAYPAL lump
+    pub colors: [Color; 256],    // The current palette
+    palettes: Vec<[Color; 256]>, // All palettes in the PLAYPAL lump
+    current: usize,              // Index of the current palette
 }
 
 impl Palette {
-    pub fn new(wad_file: &WadFile) -> Palette {
-        // Read the first palette, 768 bytes of 8-bit R, G, B values
-
-        let playpal_dir_entry = wad_file.get_dir_entry("PLAYPAL").unwrap();
+    pub fn new(wad_file: &WadFile) -> Result<Palette, WadError> {
+        // Read all palettes. Palette 0 is the normal one, the others are used
+        // for damage, bonus pickup and radiation suit effects.
+        let playpal_dir_entry = wad_file.get_dir_entry("PLAYPAL")?;
+        playpal_dir_entry.check_range(0, NUM_PALETTES * PALETTE_SIZE)?;
         let offset = playpal_dir_entry.offset as usize;
 
-        let mut colors = [Color::RGB(0, 0, 0); 256];
+        let mut palettes = vec![[Color::RGB(0, 0, 0); 256]; NUM_PALETTES];
 
-        for (i, color) in colors.iter_mut().enumerate() {
-            *color = Color::RGB(
-                wad_file.file[offset + i * 3],
-                wad_file.file[offset + i * 3 + 1],
-                wad_file.file[offset + i * 3 + 2],
-            );
+        for (p, palette) in palettes.iter_mut().enumerate() {
+            let palette_offset = offset + p * PALETTE_SIZE;
+            for (i, color) in palette.iter_mut().enumerate() {
+                *color = Color::RGB(
+                    wad_file.file[palette_offset + i * 3],
+                    wad_file.file[palette_offset + i * 3 + 1],
+                    wad_file.file[palette_offset + i * 3 + 2],
+                );
+            }
         }
 
-        Palette { colors }
+        Ok(Palette {
+            colors: palettes[0],
+            palettes,
+            current: 0,
+        })
+    }
+
+    // Switch to one of the palettes in the PLAYPAL lump
+    pub fn set_palette(&mut self, index: usize) {
+        if index != self.current {
+            self.colors = self.palettes[index];
+            self.current = index;
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn current(&self) -> usize {
+        self.current
     }
 }
 
 #[allow(dead_code)]
-pub fn render_test(game: &mut Game) {
+pub fn render_test(pixels: &mut Pixels, palette: &Palette) {
     for i in 0..16 {
         for j in 0..16 {
-            let color = game.palette.colors[i * 16 + j];
-            game.canvas.set_draw_color(color);
-            let rect = Rect::new(i as i32 * 16, j as i32 * 16, 16, 16);
-            game.canvas.fill_rect(rect).unwrap();
+            let color = palette.colors[i * 16 + j];
+            pixels.fill_rect(i as i32 * 16, j as i32 * 16, 16, 16, &color);
         }
     }
 }
@@ -1,18 +1,18 @@
-use sdl2::render::Canvas;
-use sdl2::video::Window;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::{fmt, str};
 
 use crate::graphics::{Bitmap, Palette};
 use crate::map::Vertex;
-use crate::wad::WadFile;
+use crate::renderer::Pixels;
+use crate::wad::{DirEntry, Namespace, WadError, WadFile};
 
 // Lazy loaded hashmap of pictures
 #[allow(dead_code)]
 pub struct Pictures {
     map: HashMap<String, Rc<Picture>>, // The reference counted pictures
     wad_file: Rc<WadFile>,             // Needed to be able to lazy load the pictures
+    namespace: Namespace,              // The namespace the pictures are looked up in
 }
 
 // A picture (aka patch)
@@ -27,17 +27,18 @@ pub struct Picture {
 }
 
 impl Pictures {
-    pub fn new(wad_file: &Rc<WadFile>) -> Pictures {
+    pub fn new(wad_file: &Rc<WadFile>, namespace: Namespace) -> Pictures {
         Pictures {
             wad_file: Rc::clone(wad_file),
             map: HashMap::new(),
+            namespace,
         }
     }
 
     #[allow(dead_code)]
-    pub fn get(&mut self, name: &str) -> Result<Rc<Picture>, String> {
+    pub fn get(&mut self, name: &str) -> Result<Rc<Picture>, WadError> {
         if !self.map.contains_key(name) {
-            let picture = Picture::new(&self.wad_file, name)?;
+            let picture = Picture::new(&self.wad_file, name, self.namespace)?;
 
             // Create the picture & insert it
             self.map.insert(name.to_string(), Rc::new(picture));
@@ -49,7 +50,7 @@ impl Pictures {
     #[allow(dead_code)]
     pub fn test_draw(
         &mut self,
-        canvas: &mut Canvas<Window>,
+        pixels: &mut Pixels,
         palette: &Palette,
         name: &str,
         offset: &Vertex,
@@ -57,22 +58,33 @@ impl Pictures {
         self.get(name)
             .unwrap()
             .bitmap
-            .test_flat_draw(canvas, palette, offset);
+            .test_flat_draw(pixels, palette, offset);
     }
 }
 
 impl Picture {
     // Create a new picture and load the pixels
-    pub fn new(wad_file: &WadFile, name: &str) -> Result<Picture, String> {
-        let dir_entry = wad_file.get_dir_entry(name)?;
+    pub fn new(wad_file: &WadFile, name: &str, namespace: Namespace) -> Result<Picture, WadError> {
+        let dir_entry = wad_file.get_dir_entry_in_namespace(name, namespace)?;
         let offset = dir_entry.offset as usize;
         let wad_file = &wad_file;
 
+        // The header is 8 bytes long
+        dir_entry.check_range(0, 8)?;
+
         let width = wad_file.read_i16(offset);
         let height = wad_file.read_i16(offset + 2);
         let left_offset = wad_file.read_i16(offset + 4);
         let top_offset = wad_file.read_i16(offset + 6);
 
+        if width < 0 || height < 0 {
+            return Err(WadError::InvalidLump {
+                name: dir_entry.name.clone(),
+                offset: 0,
+                reason: format!("invalid dimensions {} x {}", width, height),
+            });
+        }
+
         let mut pixels: Vec<Vec<Option<u8>>> = Vec::with_capacity(height as usize);
         for _ in 0..height as usize {
             let mut row = Vec::new();
@@ -82,7 +94,7 @@ impl Picture {
 
         let mut bitmap = Bitmap::new(width, height, pixels);
 
-        Self::read_pixels(wad_file, dir_entry.offset, &mut bitmap);
+        Self::read_pixels(wad_file, dir_entry, &mut bitmap)?;
 
         let picture = Picture {
             name: name.to_string(),
@@ -97,32 +109,46 @@ impl Picture {
 
     // https://doomwiki.org/wiki/Picture_format
     // Decode a "picture format" lump
-    pub fn read_pixels(wad_file: &WadFile, wad_offset: u32, bitmap: &mut Bitmap) {
+    pub fn read_pixels(
+        wad_file: &WadFile,
+        dir_entry: &DirEntry,
+        bitmap: &mut Bitmap,
+    ) -> Result<(), WadError> {
+        let wad_offset = dir_entry.offset as usize;
+
         // Loop over columns
         for column in 0..bitmap.width as usize {
-            let mut column_offset = wad_offset as usize
-                + wad_file.read_u32(wad_offset as usize + column * 4 + 8) as usize;
+            dir_entry.check_range(column * 4 + 8, 4)?;
+            let mut column_offset = wad_file.read_u32(wad_offset + column * 4 + 8) as usize;
 
             // Loop over posts
             loop {
-                let y_offset = wad_file.file[column_offset];
+                dir_entry.check_range(column_offset, 1)?;
+                let y_offset = wad_file.file[wad_offset + column_offset];
                 if y_offset == 0xff {
                     break;
                 }
 
-                let length = wad_file.file[column_offset + 1];
+                dir_entry.check_range(column_offset, 2)?;
+                let length = wad_file.file[wad_offset + column_offset + 1];
+                dir_entry.check_range(column_offset, length as usize + 4)?;
 
                 for row in 0..length as usize {
-                    let value = wad_file.file[column_offset + row + 3];
-                    let x = column as i32;
-                    let y = row as i32 + y_offset as i32;
-
-                    bitmap.pixels[y as usize][x as usize] = Some(value);
+                    let value = wad_file.file[wad_offset + column_offset + row + 3];
+                    let x = column;
+                    let y = row + y_offset as usize;
+
+                    // Posts that stick out of the bottom of the picture are cropped
+                    if y < bitmap.height as usize {
+                        bitmap.pixels[y][x] = Some(value);
+                    }
                 }
 
                 column_offset += length as usize + 4;
             }
         }
+
+        Ok(())
     }
 
     // Create new picture with a mirror image of the picture
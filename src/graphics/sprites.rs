@@ -3,7 +3,7 @@ use std::rc::Rc;
 
 use crate::graphics::{Picture, Pictures};
 use crate::info::{SpriteId, SPRITES};
-use crate::wad::WadFile;
+use crate::wad::{Namespace, WadError, WadFile};
 
 pub struct Sprites {
     map: HashMap<SpriteId, Sprite>,
@@ -23,19 +23,38 @@ pub struct SpriteFrame {
 }
 
 impl Sprites {
-    pub fn new(wad_file: &WadFile, pictures: &mut Pictures) -> Sprites {
+    pub fn new(wad_file: &WadFile, pictures: &mut Pictures) -> Result<Sprites, WadError> {
         let mut map: HashMap<SpriteId, Sprite> = HashMap::new();
 
+        let sprite_dir_entries = wad_file.get_dir_entries_in_namespace(Namespace::Sprites);
+
         for sprite_id in SPRITES {
             let sprite_name = format!("{:?}", sprite_id);
 
             // Indexed on frame, rotation
             let mut found_sprites: HashMap<u8, HashMap<u8, Rc<Picture>>> = HashMap::new();
 
-            for index in wad_file.first_sprite_lump..wad_file.last_sprite_lump {
-                let dir_entry = &wad_file.dirs_list[index as usize];
+            for dir_entry in &sprite_dir_entries {
                 if dir_entry.name.starts_with(&sprite_name) {
-                    let picture = pictures.get(&dir_entry.name).unwrap();
+                    // A sprite lump name is the sprite name, frame and rotation,
+                    // optionally followed by a mirrored frame and rotation. Frames
+                    // go from A to ], the 29 characters that follow A in ASCII.
+                    let name = dir_entry.name.as_bytes();
+                    let is_valid_frame = |i: usize| {
+                        (b'A'..=b']').contains(&name[i]) && (b'0'..=b'8').contains(&name[i + 1])
+                    };
+                    if (name.len() != 6 && name.len() != 8)
+                        || !is_valid_frame(4)
+                        || (name.len() == 8 && !is_valid_frame(6))
+                    {
+                        return Err(WadError::InvalidLump {
+                            name: dir_entry.name.clone(),
+                            offset: dir_entry.offset as usize,
+                            reason: "invalid sprite lump name".into(),
+                        });
+                    }
+
+                    let picture = pictures.get(&dir_entry.name)?;
 
                     let frame = dir_entry.name.as_bytes()[4] - 65;
                     let rotation = dir_entry.name.as_bytes()[5] - 48;
@@ -69,22 +88,28 @@ impl Sprites {
                     pictures: Vec::with_capacity(8),
                 };
 
-                if rotate {
-                    if rotations.keys().len() != 8 {
-                        panic!(
-                            "Got something other than 8 rotations for {}/{}: {}",
-                            sprite_name,
-                            frame,
+                // Either a single rotation 0 or all 8 rotations 1..8 must be present
+                let expected_rotations = if rotate { 1..9_u8 } else { 0..1_u8 };
+                if rotations.keys().len() != expected_rotations.len() {
+                    return Err(WadError::InvalidLump {
+                        name: format!("{}{}", sprite_name, (frame + 65) as char),
+                        offset: 0,
+                        reason: format!(
+                            "expected a single rotation 0 or 8 rotations, got {} rotations",
                             rotations.keys().len()
-                        );
-                    }
+                        ),
+                    });
+                }
 
-                    for rotation in 1..9_u8 {
-                        let sprite_frame_picture = rotations.get(&rotation).unwrap();
-                        sprite_frame.pictures.push(sprite_frame_picture.clone());
-                    }
-                } else {
-                    let sprite_frame_picture = rotations.get(&0u8).unwrap();
+                for rotation in expected_rotations {
+                    let sprite_frame_picture =
+                        rotations
+                            .get(&rotation)
+                            .ok_or_else(|| WadError::InvalidLump {
+                                name: format!("{}{}", sprite_name, (frame + 65) as char),
+                                offset: 0,
+                                reason: format!("missing rotation {}", rotation),
+                            })?;
                     sprite_frame.pictures.push(sprite_frame_picture.clone());
                 }
                 sprite.frames.insert(*frame, sprite_frame);
@@ -93,7 +118,7 @@ impl Sprites {
             map.insert(sprite_id, sprite);
         }
 
-        Sprites { map }
+        Ok(Sprites { map })
     }
 
     pub fn get_picture(&self, sprite_id: &SpriteId, frame_id: u8, rotation: u8) -> Rc<Picture> {
@@ -1,11 +1,11 @@
-use sdl2::rect::Rect;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::{fmt, str};
 
-use crate::game::Game;
-use crate::graphics::{Bitmap, Picture};
-use crate::wad::{DirEntry, WadFile};
+use crate::graphics::animations::{AnimationDefinition, Animations};
+use crate::graphics::{Bitmap, Palette, Picture};
+use crate::renderer::Pixels;
+use crate::wad::{DirEntry, Namespace, WadError, WadFile};
 
 // A texture consists of a list of patches. Each patch has an origin (x,y) and refers to
 // an entry in the PNAMES lump which has as lump name of a picture.
@@ -49,29 +49,47 @@ pub struct Texture {
 // A struct to handle lazy loaded textures
 pub struct Textures {
     definitions: HashMap<String, TextureDefinition>, // The available textures
+    names: Vec<String>,                              // Texture names in the order of definition
     wad_file: Rc<WadFile>,                           // Needed to be able to lazy load textures
     pub pnames: Vec<Pname>,                          // Parsed contents of the PNAMES lump
+    animations: Animations,                          // Animated textures
 }
 
 impl Patch {
     // Lazy load the picture if not already done
-    pub fn get_picture(&mut self, pnames: &[Pname]) -> Rc<Picture> {
+    pub fn get_picture(&mut self, pnames: &[Pname]) -> Result<Rc<Picture>, WadError> {
         if let Some(picture) = &self.picture {
-            return Rc::clone(picture);
+            return Ok(Rc::clone(picture));
         };
 
-        let patch_name = &pnames[self.patch_number as usize].name;
-        let rc_picture = Rc::new(Picture::new(&self.wad_file, patch_name).unwrap());
+        let pname = usize::try_from(self.patch_number)
+            .ok()
+            .and_then(|i| pnames.get(i))
+            .ok_or_else(|| WadError::InvalidLump {
+                name: "PNAMES".into(),
+                offset: 0,
+                reason: format!("unknown patch number {}", self.patch_number),
+            })?;
+
+        let rc_picture = Rc::new(Picture::new(
+            &self.wad_file,
+            &pname.name,
+            Namespace::Patches,
+        )?);
         self.picture = Some(Rc::clone(&rc_picture));
 
-        rc_picture
+        Ok(rc_picture)
     }
 }
 
 impl Texture {
     // Load a texture by first loading all the patches, then setting
     // the pixels from the patches.
-    fn load(definition: &mut TextureDefinition, pnames: &[Pname], bitmap: &mut Bitmap) {
+    fn load(
+        definition: &mut TextureDefinition,
+        pnames: &[Pname],
+        bitmap: &mut Bitmap,
+    ) -> Result<(), WadError> {
         bitmap.pixels = Vec::with_capacity(bitmap.height as usize);
         for _ in 0..bitmap.height as usize {
             let mut row = Vec::new();
@@ -80,7 +98,7 @@ impl Texture {
         }
 
         for patch in &mut definition.patches {
-            let picture = patch.get_picture(pnames);
+            let picture = patch.get_picture(pnames)?;
 
             for x in 0..picture.bitmap.width as usize {
                 for y in 0..picture.bitmap.height as usize {
@@ -100,18 +118,18 @@ impl Texture {
                 }
             }
         }
+
+        Ok(())
     }
 
     // Draw the picture to the top-left corner
     #[allow(dead_code)]
-    pub fn test_flat_draw(&self, game: &mut Game) {
+    pub fn test_flat_draw(&self, pixels: &mut Pixels, palette: &Palette) {
         for x in 0..self.bitmap.width as usize {
             for y in 0..self.bitmap.height as usize {
                 if let Some(value) = self.bitmap.pixels[y][x] {
-                    let color = game.palette.colors[value as usize];
-                    game.canvas.set_draw_color(color);
-                    let rect = Rect::new(x as i32 * 4, y as i32 * 4, 4, 4);
-                    game.canvas.fill_rect(rect).unwrap();
+                    let color = palette.colors[value as usize];
+                    pixels.fill_rect(x as i32 * 4, y as i32 * 4, 4, 4, &color);
                 }
             }
         }
@@ -129,42 +147,51 @@ impl fmt::Debug for Texture {
 }
 
 impl Textures {
-    pub fn new(wad_file: &Rc<WadFile>) -> Textures {
+    pub fn new(wad_file: &Rc<WadFile>) -> Result<Textures, WadError> {
         let mut textures = Textures {
             wad_file: Rc::clone(wad_file),
             definitions: HashMap::new(),
+            names: Vec::new(),
             pnames: Vec::new(),
+            animations: Animations::default(),
         };
 
-        textures.load_pnames();
+        textures.load_pnames()?;
 
         // TEXTURE1 is always present
-        let texture1_dir_entry = wad_file.get_dir_entry("TEXTURE1").unwrap();
-        textures.load_texture_list(texture1_dir_entry);
+        let texture1_dir_entry = wad_file.get_dir_entry("TEXTURE1")?;
+        textures.load_texture_list(texture1_dir_entry)?;
 
         // TEXTURE2 is only present in the registered version of Doom 1
         if let Ok(dir_entry) = wad_file.get_dir_entry("TEXTURE2") {
-            textures.load_texture_list(dir_entry);
+            textures.load_texture_list(dir_entry)?;
         }
 
-        textures
+        // Animated textures cycle through the textures between a start and end
+        // texture, in the order of TEXTURE1 and TEXTURE2.
+        let definitions = AnimationDefinition::load(wad_file)?;
+        textures.animations = Animations::new(&definitions, true, &textures.names);
+
+        Ok(textures)
     }
 
     // Return a texture from the cache, otherwise load it
-    pub fn get(&mut self, name: &str) -> Rc<Texture> {
+    pub fn get(&mut self, name: &str) -> Result<Rc<Texture>, WadError> {
         let definition: &mut TextureDefinition = self
             .definitions
             .get_mut(&name.to_ascii_uppercase())
-            .unwrap_or_else(|| panic!("Unknown texture {}", &name));
+            .ok_or_else(|| WadError::UnknownTexture {
+                name: name.to_string(),
+            })?;
 
         // Already loaded
         if let Some(texture) = &definition.texture {
-            return Rc::clone(texture);
+            return Ok(Rc::clone(texture));
         }
 
         let mut bitmap = Bitmap::new(definition.width, definition.height, Vec::new());
 
-        Texture::load(definition, &self.pnames, &mut bitmap);
+        Texture::load(definition, &self.pnames, &mut bitmap)?;
 
         // Load the texture
         let texture = Texture {
@@ -175,22 +202,37 @@ impl Textures {
         let rc_texture = Rc::new(texture);
         definition.texture = Some(Rc::clone(&rc_texture));
 
-        Rc::clone(&rc_texture)
+        Ok(Rc::clone(&rc_texture))
+    }
+
+    // Get a texture which may be animated
+    pub fn get_animated(&mut self, name: &str, tic: u32) -> Result<Rc<Texture>, WadError> {
+        match self.animations.get(name, tic) {
+            Some(frame) => {
+                let frame = frame.to_string();
+                self.get(&frame)
+            }
+            None => self.get(name),
+        }
     }
 
     // Load and parse PNAMES section. Look up the lump names in the WAD file.
-    fn load_pnames(&mut self) {
+    fn load_pnames(&mut self) -> Result<(), WadError> {
         let wad_file = &self.wad_file;
 
-        let pnames_dir_entry = wad_file.get_dir_entry("PNAMES").unwrap();
+        let pnames_dir_entry = wad_file.get_dir_entry("PNAMES")?;
 
         let offset = pnames_dir_entry.offset as usize;
+        pnames_dir_entry.check_range(0, 4)?;
         let count = wad_file.read_u32(offset);
 
+        // A name is 8 bytes long
+        pnames_dir_entry.check_range(4, count as usize * 8)?;
+
         let mut pnames: Vec<Pname> = Vec::new();
         for i in 0..count as usize {
             let name = wad_file.read_lump_name(offset + 4 + i * 8);
-            let dir_entry = wad_file.get_dir_entry(&name);
+            let dir_entry = wad_file.get_dir_entry_in_namespace(&name, Namespace::Patches);
             let wad_offset = if let Ok(dir_entry) = dir_entry {
                 Some(dir_entry.offset)
             } else {
@@ -201,26 +243,44 @@ impl Textures {
         }
 
         self.pnames = pnames;
+
+        Ok(())
     }
 
     // Load TEXTURE1 or TEXTURE2 lump. This contains names of all the textures + patches they
     // are made up of.
-    fn load_texture_list(&mut self, dir_entry: &DirEntry) {
+    fn load_texture_list(&mut self, dir_entry: &DirEntry) -> Result<(), WadError> {
         let wad_file = &self.wad_file;
 
         let texture_list_offset = dir_entry.offset as usize;
 
+        dir_entry.check_range(0, 4)?;
         let texture_count = wad_file.read_u32(texture_list_offset);
+        dir_entry.check_range(4, texture_count as usize * 4)?;
 
         for i in 0..texture_count as usize {
             let map_texture_offset = wad_file.read_u32(texture_list_offset + 4 + 4 * i) as usize;
             let offset = texture_list_offset + map_texture_offset;
+
+            // The texture header is 22 bytes long
+            dir_entry.check_range(map_texture_offset, 22)?;
             let name = wad_file.read_lump_name(offset);
 
             let width = wad_file.read_i16(offset + 12);
             let height = wad_file.read_i16(offset + 14);
             let patch_count = wad_file.read_i16(offset + 20);
 
+            if width < 0 || height < 0 || patch_count < 0 {
+                return Err(WadError::InvalidLump {
+                    name: dir_entry.name.clone(),
+                    offset: map_texture_offset,
+                    reason: format!("invalid texture {}", name),
+                });
+            }
+
+            // A patch is 10 bytes long
+            dir_entry.check_range(map_texture_offset + 22, patch_count as usize * 10)?;
+
             let patch0_offset = offset + 22;
             let mut patches: Vec<Patch> = Vec::new();
 
@@ -249,8 +309,13 @@ impl Textures {
                 texture: None,
             };
 
-            self.definitions
-                .insert(name.to_ascii_uppercase(), texture_definition);
+            let name = name.to_ascii_uppercase();
+            if !self.definitions.contains_key(&name) {
+                self.names.push(name.clone());
+            }
+            self.definitions.insert(name, texture_definition);
         }
+
+        Ok(())
     }
 }
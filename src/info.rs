@@ -1272,7 +1272,7 @@ pub struct State {
     pub next_state: StateId,   // Next state
 }
 
-#[allow(dead_code)]
+#[allow(dead_code, clippy::large_const_arrays)]
 pub const STATES: [State; 967] = [
     State{id: StateId::S_NULL, sprite: SpriteId::TROO, frame: 0, full_bright: false, tics: -1, action: "NULL", next_state: StateId::S_NULL},
     State{id: StateId::S_LIGHTDONE, sprite: SpriteId::SHTG, frame: 4, full_bright: false, tics: 0, action: "A_Light0", next_state: StateId::S_NULL},
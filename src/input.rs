@@ -0,0 +1,310 @@
+// Player actions, the keys they are bound to and the mouse. The mouse turns the
+// player, or strafes while the strafe action is held, and moves forward and
+// backward unless novert is on. See G_BuildTiccmd in g_game.c.
+use clap::ValueEnum;
+use sdl2::keyboard::Keycode;
+use sdl2::mouse::MouseButton;
+use std::collections::{HashMap, HashSet};
+use std::f32::consts::PI;
+
+pub const MOUSE_TURN_SPEED: f32 = 8.0 * 2.0 * PI / 65536.0; // Radians per mouse count
+pub const MOUSE_MOVE_SPEED: f32 = 0.25; // Map units per mouse count
+pub const MOUSE_LOOK_SPEED: f32 = MOUSE_TURN_SPEED / 2.0; // Radians per mouse count
+const DEFAULT_MOUSE_SENSITIVITY: f32 = 5.0; // See mouseSensitivity in m_misc.c
+
+// Actions that can be bound to keys and mouse buttons. Some are held down, like
+// moving, others happen once when the key is pressed, like toggling the map.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Action {
+    Forward,         // Move forward
+    Back,            // Move backward
+    TurnLeft,        // Turn left, or strafe left while strafe is held
+    TurnRight,       // Turn right, or strafe right while strafe is held
+    StrafeLeft,      // Move left
+    StrafeRight,     // Move right
+    Strafe,          // Turning strafes instead
+    Run,             // Move and turn twice as fast
+    Use,             // Open doors and flip switches
+    LookUp,          // Look up
+    LookDown,        // Look down
+    CenterView,      // Look straight ahead
+    FlyUp,           // Move up with the fly camera
+    FlyDown,         // Move down with the fly camera
+    Automap,         // Toggle the 2D map
+    Screenshot,      // Save a screenshot
+    Invulnerability, // Toggle the invulnerability colormap
+    KillAll,         // Kill all monsters
+    ExplodeAll,      // Explode all barrels and monsters
+    Respawn,         // Respawn all monsters
+    Noclip,          // Toggle walking through walls
+    Fly,             // Toggle the fly camera
+    GrabMouse,       // Release or grab the mouse
+    Quit,            // Quit the game
+}
+
+// Names in the config file and the default keys. Names that exist in doom's
+// default.cfg or Heretic's are the same.
+const ACTIONS: [(Action, &str, &[Keycode]); 24] = [
+    (Action::Forward, "key_up", &[Keycode::Up]),
+    (Action::Back, "key_down", &[Keycode::Down]),
+    (Action::TurnLeft, "key_left", &[Keycode::Left]),
+    (Action::TurnRight, "key_right", &[Keycode::Right]),
+    (Action::StrafeLeft, "key_strafeleft", &[Keycode::Comma]),
+    (Action::StrafeRight, "key_straferight", &[Keycode::Period]),
+    (
+        Action::Strafe,
+        "key_strafe",
+        &[Keycode::LAlt, Keycode::RAlt],
+    ),
+    (
+        Action::Run,
+        "key_speed",
+        &[Keycode::LShift, Keycode::RShift],
+    ),
+    (Action::Use, "key_use", &[Keycode::Space]),
+    (Action::LookUp, "key_lookup", &[Keycode::Home]),
+    (Action::LookDown, "key_lookdown", &[Keycode::End]),
+    (Action::CenterView, "key_lookcenter", &[Keycode::Delete]),
+    (Action::FlyUp, "key_flyup", &[Keycode::PageUp]),
+    (Action::FlyDown, "key_flydown", &[Keycode::PageDown]),
+    (Action::Automap, "key_map", &[Keycode::Tab]),
+    (Action::Screenshot, "key_screenshot", &[Keycode::F12]),
+    (
+        Action::Invulnerability,
+        "key_invulnerability",
+        &[Keycode::I],
+    ),
+    (Action::KillAll, "key_killall", &[Keycode::K]),
+    (Action::ExplodeAll, "key_explodeall", &[Keycode::X]),
+    (Action::Respawn, "key_respawn", &[Keycode::R]),
+    (Action::Noclip, "key_noclip", &[Keycode::N]),
+    (Action::Fly, "key_fly", &[Keycode::F]),
+    (Action::GrabMouse, "key_grabmouse", &[Keycode::G]),
+    (Action::Quit, "key_quit", &[Keycode::Escape, Keycode::Q]),
+];
+
+impl Action {
+    // Name of the key binding in the config file, e.g. key_up
+    pub fn config_name(&self) -> &'static str {
+        ACTIONS.iter().find(|(a, ..)| a == self).unwrap().1
+    }
+
+    // Find an action by its config file name
+    pub fn from_config_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .find(|(_, n, _)| *n == name)
+            .map(|(action, ..)| *action)
+    }
+}
+
+// The keys of each action
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    keys: HashMap<Action, Vec<Keycode>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            keys: ACTIONS
+                .iter()
+                .map(|(action, _, keys)| (*action, keys.to_vec()))
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    // Replace the keys of an action
+    pub fn bind(&mut self, action: Action, keys: Vec<Keycode>) {
+        self.keys.insert(action, keys);
+    }
+
+    pub fn keys(&self, action: Action) -> &[Keycode] {
+        self.keys.get(&action).map_or(&[], |keys| keys.as_slice())
+    }
+
+    // The actions a key is bound to
+    pub fn actions(&self, keycode: Keycode) -> Vec<Action> {
+        let mut actions: Vec<Action> = self
+            .keys
+            .iter()
+            .filter(|(_, keys)| keys.contains(&keycode))
+            .map(|(action, _)| *action)
+            .collect();
+        actions.sort_by_key(|action| *action as usize);
+
+        actions
+    }
+
+    // Is a key bound to the action held down?
+    pub fn is_action_down(&self, action: Action, pressed_keys: &HashSet<Keycode>) -> bool {
+        self.keys(action)
+            .iter()
+            .any(|key| pressed_keys.contains(key))
+    }
+}
+
+pub struct Mouse {
+    pub sensitivity: f32, // Doom's mouse sensitivity, 0 to 9
+    pub novert: bool,     // Don't move forward and backward
+    pub look: bool,       // Look up and down instead of moving
+    pub grabbed: bool,    // Hidden and kept in the window with relative mode
+    pub bindings: HashMap<MouseButton, Action>,
+    pressed_buttons: HashSet<MouseButton>,
+    dx: i32, // Motion since the last tic
+    dy: i32, // Motion since the last tic
+}
+
+impl Mouse {
+    // The right button strafes and the middle button moves forward, like doom
+    pub fn new() -> Mouse {
+        Mouse {
+            sensitivity: DEFAULT_MOUSE_SENSITIVITY,
+            novert: false,
+            look: false,
+            grabbed: true,
+            bindings: HashMap::from([
+                (MouseButton::Right, Action::Strafe),
+                (MouseButton::Middle, Action::Forward),
+            ]),
+            pressed_buttons: HashSet::new(),
+            dx: 0,
+            dy: 0,
+        }
+    }
+
+    // Add relative motion. Motion is ignored while the mouse isn't grabbed.
+    pub fn motion(&mut self, xrel: i32, yrel: i32) {
+        if self.grabbed {
+            self.dx += xrel;
+            self.dy += yrel;
+        }
+    }
+
+    pub fn button_down(&mut self, button: MouseButton) {
+        if self.grabbed {
+            self.pressed_buttons.insert(button);
+        }
+    }
+
+    pub fn button_up(&mut self, button: MouseButton) {
+        self.pressed_buttons.remove(&button);
+    }
+
+    pub fn toggle_grab(&mut self) {
+        self.grabbed = !self.grabbed;
+        if !self.grabbed {
+            self.pressed_buttons.clear();
+            (self.dx, self.dy) = (0, 0);
+        }
+    }
+
+    // Is a button bound to the action held down?
+    pub fn is_action_down(&self, action: Action) -> bool {
+        self.pressed_buttons
+            .iter()
+            .any(|button| self.bindings.get(button) == Some(&action))
+    }
+
+    // Get the motion since the last tic, scaled by the sensitivity, and reset it.
+    // Positive y is towards the player.
+    pub fn take_motion(&mut self) -> (f32, f32) {
+        let scale = (self.sensitivity + 5.0) / 10.0;
+        let motion = (self.dx as f32 * scale, self.dy as f32 * scale);
+        (self.dx, self.dy) = (0, 0);
+
+        motion
+    }
+}
+
+// Parse a mouse button binding from the command line, e.g. right=strafe
+pub fn parse_mouse_binding(binding: &str) -> Result<(MouseButton, Action), String> {
+    let (button, action) = binding
+        .split_once('=')
+        .ok_or_else(|| format!("Expected button=action, got {}", binding))?;
+
+    let button = match button.to_ascii_lowercase().as_str() {
+        "left" => MouseButton::Left,
+        "middle" => MouseButton::Middle,
+        "right" => MouseButton::Right,
+        "x1" => MouseButton::X1,
+        "x2" => MouseButton::X2,
+        _ => return Err(format!("Unknown mouse button {}", button)),
+    };
+    let action = Action::from_str(action, true)?;
+
+    Ok((button, action))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensitivity() {
+        let mut mouse = Mouse::new();
+        mouse.motion(10, -4);
+        mouse.motion(10, 0);
+        assert_eq!(mouse.take_motion(), (20.0, -4.0));
+        assert_eq!(mouse.take_motion(), (0.0, 0.0));
+
+        mouse.sensitivity = 9.0;
+        mouse.motion(10, 10);
+        assert_eq!(mouse.take_motion(), (14.0, 14.0));
+
+        // Motion is ignored when the mouse isn't grabbed
+        mouse.toggle_grab();
+        mouse.motion(10, 10);
+        assert_eq!(mouse.take_motion(), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_bindings() {
+        let mut mouse = Mouse::new();
+        mouse.button_down(MouseButton::Right);
+        assert!(mouse.is_action_down(Action::Strafe));
+        mouse.button_up(MouseButton::Right);
+        assert!(!mouse.is_action_down(Action::Strafe));
+        mouse.button_down(MouseButton::Right);
+        assert!(!mouse.is_action_down(Action::Forward));
+
+        let (button, action) = parse_mouse_binding("left=run").unwrap();
+        mouse.bindings.insert(button, action);
+        mouse.button_down(MouseButton::Left);
+        assert!(mouse.is_action_down(Action::Run));
+
+        // Releasing the grab releases the buttons
+        mouse.toggle_grab();
+        assert!(!mouse.is_action_down(Action::Strafe));
+
+        assert!(parse_mouse_binding("left").is_err());
+        assert!(parse_mouse_binding("wheel=run").is_err());
+        assert!(parse_mouse_binding("left=jump").is_err());
+    }
+
+    #[test]
+    fn test_key_bindings() {
+        let mut key_bindings = KeyBindings::default();
+        let pressed_keys = HashSet::from([Keycode::RAlt, Keycode::W]);
+        assert!(key_bindings.is_action_down(Action::Strafe, &pressed_keys));
+        assert!(!key_bindings.is_action_down(Action::Forward, &pressed_keys));
+        assert_eq!(key_bindings.actions(Keycode::Tab), vec![Action::Automap]);
+
+        key_bindings.bind(Action::Forward, vec![Keycode::W, Keycode::Up]);
+        key_bindings.bind(Action::Strafe, vec![]);
+        assert!(key_bindings.is_action_down(Action::Forward, &pressed_keys));
+        assert!(!key_bindings.is_action_down(Action::Strafe, &pressed_keys));
+        assert!(key_bindings.actions(Keycode::RAlt).is_empty());
+    }
+
+    #[test]
+    fn test_config_names() {
+        for (action, name, _) in ACTIONS {
+            assert_eq!(action.config_name(), name);
+            assert_eq!(Action::from_config_name(name), Some(action));
+        }
+        assert_eq!(Action::from_config_name("key_fire"), None);
+    }
+}
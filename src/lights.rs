@@ -3,8 +3,9 @@ use rand::Rng;
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::map::{Map, Sector};
-use crate::thinkers::Thinker;
+use crate::map::{Linedef, Map, Sector};
+use crate::planes::neighbor_sectors;
+use crate::thinkers::{is_sector_moving, Thinker};
 
 pub const SLOW_DARK: i16 = 35;
 pub const FAST_DARK: i16 = 15;
@@ -18,9 +19,11 @@ fn find_min_surrounding_light(map: &Map, sector_id: i16, max: i16) -> i16 {
 
     for linedef in &map.linedefs {
         if let Some(front_sidedef) = &linedef.front_sidedef {
+            let front_sidedef = front_sidedef.borrow();
             let sector = front_sidedef.sector.borrow();
             if sector.id == sector_id {
                 if let Some(back_sidedef) = &linedef.back_sidedef {
+                    let back_sidedef = back_sidedef.borrow();
                     let sector = back_sidedef.sector.borrow();
                     light_level = light_level.min(sector.light_level)
                 }
@@ -28,9 +31,11 @@ fn find_min_surrounding_light(map: &Map, sector_id: i16, max: i16) -> i16 {
         }
 
         if let Some(back_sidedef) = &linedef.back_sidedef {
+            let back_sidedef = back_sidedef.borrow();
             let sector = back_sidedef.sector.borrow();
             if sector.id == sector_id {
                 if let Some(front_sidedef) = &linedef.front_sidedef {
+                    let front_sidedef = front_sidedef.borrow();
                     let sector = front_sidedef.sector.borrow();
                     light_level = light_level.min(sector.light_level)
                 }
@@ -257,3 +262,43 @@ impl Thinker for FireFlicker {
         self.count = 4;
     }
 }
+
+// Set the light level of the sectors with the linedef's tag, or to the brightest
+// neighboring sector if there is no level. See EV_LightTurnOn in p_lights.c.
+pub fn light_turn_on(map: &Map, linedef: &Linedef, light_level: Option<i16>) {
+    for sector in map.get_tagged_sectors(linedef.sector_tag) {
+        let light_level = light_level.unwrap_or_else(|| {
+            let sector = sector.borrow();
+            neighbor_sectors(map, &sector)
+                .iter()
+                .map(|neighbor| neighbor.borrow().light_level)
+                .max()
+                .unwrap_or(sector.light_level)
+        });
+        sector.borrow_mut().light_level = light_level;
+    }
+}
+
+// Set the light level of the sectors with the linedef's tag to that of the darkest
+// neighboring sector. See EV_TurnTagLightsOff in p_lights.c.
+pub fn turn_tag_lights_off(map: &Map, linedef: &Linedef) {
+    for sector in map.get_tagged_sectors(linedef.sector_tag) {
+        let light_level = {
+            let sector = sector.borrow();
+            find_min_surrounding_light(map, sector.id, sector.light_level)
+        };
+        sector.borrow_mut().light_level = light_level;
+    }
+}
+
+// Make the lights of the sectors with the linedef's tag blink slowly.
+// See EV_StartLightStrobing in p_lights.c.
+pub fn start_light_strobing(map: &Map, linedef: &Linedef, thinkers: &mut Vec<Box<dyn Thinker>>) {
+    for sector in map.get_tagged_sectors(linedef.sector_tag) {
+        if is_sector_moving(thinkers, &sector.borrow()) {
+            continue;
+        }
+
+        thinkers.push(Box::new(StrobeFlash::new(map, sector, SLOW_DARK, false)));
+    }
+}
@@ -1,29 +1,61 @@
-use clap::{arg, command, Parser};
+use clap::Parser;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
 use std::rc::Rc;
-use std::{fs::metadata, fs::File, io::Read};
+use std::time::Instant;
+use std::{fs::metadata, fs::File, io, io::Read};
 
+mod ceilings;
+mod config;
+mod doors;
+mod floors;
 mod game;
 mod geometry;
+#[cfg(test)]
+mod golden_tests;
 mod graphics;
+#[rustfmt::skip] // Generated by multigen
 mod info;
+mod input;
 mod lights;
 mod map;
 mod map_objects;
+mod movement;
+mod palette_effects;
+mod planes;
+mod platforms;
 mod renderer;
+mod screenshot;
+mod scrollers;
+mod specials;
+mod switches;
+mod teleports;
+#[cfg(test)]
+mod test_maps;
 mod thinkers;
 mod wad;
+mod wad_builder;
 
-use game::{Game, OverridePlayer};
+use config::{parse_config, write_config, DEFAULT_CONFIG_FILE};
+use game::{CameraMode, Game, OverridePlayer};
+use input::{parse_mouse_binding, Action, KeyBindings};
+use palette_effects::PaletteEffect;
+use renderer::LightingMode;
+use screenshot::ScreenshotFormat;
+use sdl2::mouse::MouseButton;
 use wad::WadFile;
 
 // Read a file into a u8 vector
-fn read_file(filename: &str) -> Vec<u8> {
-    let mut f = File::open(filename).expect("Unable to open file");
-    let metadata = metadata(filename).expect("Unable to get metadata");
+fn read_file(filename: &str) -> Result<Vec<u8>, io::Error> {
+    let with_filename = |e: io::Error| io::Error::new(e.kind(), format!("{}: {}", filename, e));
+
+    let mut f = File::open(filename).map_err(with_filename)?;
+    let metadata = metadata(filename).map_err(with_filename)?;
     let mut result = vec![0; metadata.len() as usize];
-    f.read_exact(&mut result).expect("Unable to read file");
+    f.read_exact(&mut result).map_err(with_filename)?;
 
-    result
+    Ok(result)
 }
 
 #[derive(Parser, Debug)]
@@ -37,6 +69,10 @@ struct Args {
     #[arg(short, long, default_value_t = String::from("doom1.wad") )]
     wad: String,
 
+    // PWAD files loaded on top of the wad file, may be repeated
+    #[arg(short, long)]
+    file: Vec<String>,
+
     // Turbo
     #[arg(short, long, default_value_t = 100)]
     turbo: i16,
@@ -51,17 +87,94 @@ struct Args {
 
     #[arg(long)]
     player_position: Option<String>,
+
+    // Walk through walls with noclip, or fly around. A view_z in --player-position
+    // starts with the fly camera.
+    #[arg(long, value_enum)]
+    camera_mode: Option<CameraMode>,
+
+    // Config file with key bindings, like doom's default.cfg. Defaults to default.cfg
+    // if it exists.
+    #[arg(long)]
+    config: Option<String>,
+
+    // Print the key bindings in the config file format and exit
+    #[arg(long, default_value_t = false)]
+    print_config: bool,
+
+    // Mouse sensitivity, 0 to 9 like doom
+    #[arg(long, default_value_t = 5.0)]
+    mouse_sensitivity: f32,
+
+    // Don't move forward and backward with the mouse
+    #[arg(long, default_value_t = false)]
+    novert: bool,
+
+    // Look up and down with the mouse instead of moving forward and backward
+    #[arg(long, default_value_t = false)]
+    mouse_look: bool,
+
+    // Bind a mouse button to an action, e.g. left=forward, may be repeated
+    #[arg(long, value_parser = parse_mouse_binding)]
+    mouse_button: Vec<(MouseButton, Action)>,
+
+    // Light diminishing with the COLORMAP lump like doom, or smooth
+    #[arg(long, value_enum, default_value_t = LightingMode::Colormap)]
+    lighting: LightingMode,
+
+    // Start a damage, bonus or radiation suit palette effect, may be repeated
+    #[arg(long, value_enum)]
+    palette_effect: Vec<PaletteEffect>,
+
+    // Render without a window: run the game for a number of tics, render one frame and exit
+    #[arg(long, default_value_t = false)]
+    headless: bool,
+
+    // Amount of tics to run before rendering in headless mode
+    #[arg(long, default_value_t = 0)]
+    tics: u32,
+
+    // Save the rendered frame and the 2D map in headless mode
+    #[arg(long, default_value_t = false)]
+    screenshot: bool,
+
+    // Screenshot file format, also used for screenshots taken with F12
+    #[arg(long, value_enum, default_value_t = ScreenshotFormat::Png)]
+    screenshot_format: ScreenshotFormat,
+
+    // Directory screenshots are saved to
+    #[arg(long, default_value_t = String::from("."))]
+    screenshot_dir: String,
 }
 
-pub fn main() {
+fn run() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
 
-    let file = read_file(&args.wad);
-    let wad_file = Rc::new(WadFile::new(file));
+    // The key bindings don't need a WAD file, so they can be printed without one
+    let key_bindings = match &args.config {
+        Some(filename) => parse_config(&String::from_utf8_lossy(&read_file(filename)?))?,
+        None => match read_file(DEFAULT_CONFIG_FILE) {
+            Ok(data) => parse_config(&String::from_utf8_lossy(&data))?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => KeyBindings::default(),
+            Err(error) => return Err(error.into()),
+        },
+    };
+    if args.print_config {
+        print!("{}", write_config(&key_bindings));
+        return Ok(());
+    }
+
+    let file = read_file(&args.wad)?;
+    let mut wad_file = WadFile::new(file)?;
+    for filename in &args.file {
+        wad_file.add_file(read_file(filename)?)?;
+    }
+    let wad_file = Rc::new(wad_file);
 
-    let override_player: Option<OverridePlayer> = args
-        .player_position
-        .map(|player_position| serde_json::from_str(&player_position).unwrap());
+    let override_player: Option<OverridePlayer> = match args.player_position {
+        Some(player_position) => Some(serde_json::from_str(&player_position)?),
+        None => None,
+    };
 
     let mut game = Game::new(
         wad_file,
@@ -70,6 +183,59 @@ pub fn main() {
         args.print_fps,
         args.print_player_position,
         override_player,
-    );
-    game.main_loop();
+    )?;
+    game.key_bindings = key_bindings;
+    game.lighting.mode = args.lighting;
+    game.mouse.sensitivity = args.mouse_sensitivity;
+    game.mouse.novert = args.novert;
+    game.mouse.look = args.mouse_look;
+    game.mouse.bindings.extend(args.mouse_button);
+    if let Some(camera_mode) = args.camera_mode {
+        game.set_camera_mode(camera_mode);
+    }
+    game.screenshot_format = args.screenshot_format;
+    for effect in &args.palette_effect {
+        game.palette_effects.start(*effect);
+    }
+    game.screenshot_directory = PathBuf::from(&args.screenshot_dir);
+
+    if args.headless {
+        let t0 = Instant::now();
+        game.run_tics(args.tics);
+        let pixels = game.render_frame();
+
+        if args.print_fps {
+            println!(
+                "Rendered {} after {} tics in {:?}",
+                args.map,
+                args.tics,
+                t0.elapsed()
+            );
+        }
+
+        if args.screenshot {
+            println!(
+                "Saved screenshot {}",
+                game.save_screenshot(&pixels)?.display()
+            );
+
+            game.set_viewing_map(true);
+            let pixels = game.render_frame();
+            println!(
+                "Saved screenshot {}",
+                game.save_screenshot(&pixels)?.display()
+            );
+        }
+    } else {
+        game.main_loop()?;
+    }
+
+    Ok(())
+}
+
+pub fn main() {
+    if let Err(error) = run() {
+        eprintln!("Error: {}", error);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,152 @@
+use std::rc::Rc;
+
+use crate::map::{Linedef, MapError, Vertex};
+use crate::wad::{MapLumpName, WadFile};
+
+pub const BLOCK_SIZE: f32 = 128.0; // Width and height of a block in map units
+const END_OF_LIST: u16 = 0xffff; // Ends the list of linedefs of a block
+
+// A grid of 128x128 blocks with the linedefs that pass through each block.
+// Collision detection only needs to check the linedefs in the blocks it touches.
+// https://doomwiki.org/wiki/Blockmap
+#[derive(Debug)]
+pub struct Blockmap {
+    pub origin: Vertex,      // Bottom-left corner of the grid
+    pub columns: usize,      // Amount of blocks in the x direction
+    pub rows: usize,         // Amount of blocks in the y direction
+    blocks: Vec<Vec<usize>>, // Linedef indexes per block, row by row from the bottom
+}
+
+impl Blockmap {
+    // Load the BLOCKMAP lump. Some map editors leave it empty, in which case the
+    // blockmap is built from the linedefs.
+    pub fn load(
+        wad_file: &WadFile,
+        linedefs: &[Rc<Linedef>],
+        map_name: &str,
+    ) -> Result<Blockmap, MapError> {
+        let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Blockmap)?;
+        if dir_entry.size == 0 {
+            return Ok(Blockmap::build(linedefs));
+        }
+
+        // The header is the origin and the amount of columns and rows, followed by an
+        // offset in 16 bit words for each block.
+        dir_entry.check_range(0, 8)?;
+        let offset = dir_entry.offset as usize;
+        let origin = Vertex::new(
+            wad_file.read_f32_from_i16(offset),
+            wad_file.read_f32_from_i16(offset + 2),
+        );
+        let columns = wad_file.read_u16(offset + 4) as usize;
+        let rows = wad_file.read_u16(offset + 6) as usize;
+        dir_entry.check_range(8, columns * rows * 2)?;
+
+        let mut blocks = Vec::with_capacity(columns * rows);
+        for i in 0..columns * rows {
+            let list_offset = wad_file.read_u16(offset + 8 + i * 2) as usize * 2;
+
+            // A list starts with a 0 and ends with 0xffff. The leading 0 isn't a
+            // linedef, see P_BlockLinesIterator in Boom's p_maputl.c.
+            let mut block = Vec::new();
+            for j in 1.. {
+                dir_entry.check_range(list_offset + j * 2, 2)?;
+                let index = wad_file.read_u16(offset + list_offset + j * 2);
+                if index == END_OF_LIST {
+                    break;
+                }
+
+                if index as usize >= linedefs.len() {
+                    return Err(MapError::InvalidReference {
+                        map_name: map_name.to_string(),
+                        lump_name: MapLumpName::Blockmap.to_string(),
+                        entry: i,
+                        index: index as i16,
+                    });
+                }
+                block.push(index as usize);
+            }
+
+            blocks.push(block);
+        }
+
+        Ok(Blockmap {
+            origin,
+            columns,
+            rows,
+            blocks,
+        })
+    }
+
+    // Make a blockmap with each linedef in all blocks its bounding box touches
+    pub fn build(linedefs: &[Rc<Linedef>]) -> Blockmap {
+        let mut min = Vertex::new(f32::MAX, f32::MAX);
+        let mut max = Vertex::new(f32::MIN, f32::MIN);
+        for linedef in linedefs {
+            for vertex in [&linedef.start_vertex, &linedef.end_vertex] {
+                min = Vertex::new(min.x.min(vertex.x), min.y.min(vertex.y));
+                max = Vertex::new(max.x.max(vertex.x), max.y.max(vertex.y));
+            }
+        }
+
+        if linedefs.is_empty() {
+            min = Vertex::new(0.0, 0.0);
+            max = Vertex::new(0.0, 0.0);
+        }
+
+        let mut blockmap = Blockmap {
+            columns: ((max.x - min.x) / BLOCK_SIZE) as usize + 1,
+            rows: ((max.y - min.y) / BLOCK_SIZE) as usize + 1,
+            origin: min,
+            blocks: Vec::new(),
+        };
+        blockmap.blocks = vec![Vec::new(); blockmap.columns * blockmap.rows];
+
+        for (index, linedef) in linedefs.iter().enumerate() {
+            let start = &linedef.start_vertex;
+            let end = &linedef.end_vertex;
+            let line_min = Vertex::new(start.x.min(end.x), start.y.min(end.y));
+            let line_max = Vertex::new(start.x.max(end.x), start.y.max(end.y));
+
+            for block in blockmap.get_blocks(&line_min, &line_max) {
+                blockmap.blocks[block].push(index);
+            }
+        }
+
+        blockmap
+    }
+
+    // Indexes of the blocks that overlap with a box
+    fn get_blocks(&self, min: &Vertex, max: &Vertex) -> Vec<usize> {
+        let column = |x: f32| ((x - self.origin.x) / BLOCK_SIZE).floor() as i32;
+        let row = |y: f32| ((y - self.origin.y) / BLOCK_SIZE).floor() as i32;
+
+        let min_column = column(min.x).max(0);
+        let max_column = column(max.x).min(self.columns as i32 - 1);
+        let min_row = row(min.y).max(0);
+        let max_row = row(max.y).min(self.rows as i32 - 1);
+
+        let mut blocks = Vec::new();
+        for row in min_row..=max_row {
+            for column in min_column..=max_column {
+                blocks.push(row as usize * self.columns + column as usize);
+            }
+        }
+
+        blocks
+    }
+
+    // Indexes of the linedefs in the blocks that overlap with a box, without duplicates
+    pub fn get_linedefs(&self, min: &Vertex, max: &Vertex) -> Vec<usize> {
+        let mut linedefs: Vec<usize> = self
+            .get_blocks(min, max)
+            .iter()
+            .flat_map(|&block| self.blocks[block].iter().copied())
+            .collect();
+
+        linedefs.sort_unstable();
+        linedefs.dedup();
+
+        linedefs
+    }
+}
@@ -1,6 +1,7 @@
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
-use crate::map::{Sidedef, Vertex};
+use crate::map::{get_by_index, MapError, Sidedef, Vertex};
 use crate::wad::{MapLumpName, WadFile};
 
 pub struct Flags;
@@ -25,19 +26,19 @@ pub struct Linedef {
     pub start_vertex: Rc<Vertex>,
     pub end_vertex: Rc<Vertex>,
     pub flags: i16,
-    pub special_type: i16,
+    pub special_type: Cell<i16>, // Cleared when a one-time special is used
     pub sector_tag: i16,
-    pub front_sidedef: Option<Rc<Sidedef>>,
-    pub back_sidedef: Option<Rc<Sidedef>>,
+    pub front_sidedef: Option<Rc<RefCell<Sidedef>>>,
+    pub back_sidedef: Option<Rc<RefCell<Sidedef>>>,
 }
 
 pub fn load_linedefs(
     wad_file: &WadFile,
     vertexes: &[Rc<Vertex>],
-    sidedefs: &[Rc<Sidedef>],
+    sidedefs: &[Rc<RefCell<Sidedef>>],
     map_name: &str,
-) -> Vec<Rc<Linedef>> {
-    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Linedefs);
+) -> Result<Vec<Rc<Linedef>>, MapError> {
+    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Linedefs)?;
     let count = dir_entry.size as usize / 14; // A linedef is 14 bytes long
 
     let mut results = Vec::new();
@@ -50,20 +51,47 @@ pub fn load_linedefs(
         let front_sidedef = if front_sidedef_index == -1 {
             None
         } else {
-            Some(Rc::clone(&sidedefs[front_sidedef_index as usize]))
+            Some(get_by_index(
+                sidedefs,
+                front_sidedef_index,
+                map_name,
+                MapLumpName::Linedefs,
+                i,
+            )?)
         };
         let back_sidedef = if back_sidedef_index == -1 {
             None
         } else {
-            Some(Rc::clone(&sidedefs[back_sidedef_index as usize]))
+            Some(get_by_index(
+                sidedefs,
+                back_sidedef_index,
+                map_name,
+                MapLumpName::Linedefs,
+                i,
+            )?)
         };
 
+        let start_vertex_index = wad_file.read_i16(offset);
+        let end_vertex_index = wad_file.read_i16(offset + 2);
+
         let linedef = Linedef {
             id: i as i16,
-            start_vertex: Rc::clone(&vertexes[wad_file.read_i16(offset) as usize]),
-            end_vertex: Rc::clone(&vertexes[wad_file.read_i16(offset + 2) as usize]),
+            start_vertex: get_by_index(
+                vertexes,
+                start_vertex_index,
+                map_name,
+                MapLumpName::Linedefs,
+                i,
+            )?,
+            end_vertex: get_by_index(
+                vertexes,
+                end_vertex_index,
+                map_name,
+                MapLumpName::Linedefs,
+                i,
+            )?,
             flags: wad_file.read_i16(offset + 4),
-            special_type: wad_file.read_i16(offset + 6),
+            special_type: Cell::new(wad_file.read_i16(offset + 6)),
             sector_tag: wad_file.read_i16(offset + 8),
             front_sidedef,
             back_sidedef,
@@ -71,5 +99,5 @@ pub fn load_linedefs(
         results.push(Rc::new(linedef));
     }
 
-    results
+    Ok(results)
 }
@@ -1,3 +1,4 @@
+mod blockmap;
 mod linedefs;
 mod nodes;
 mod sectors;
@@ -8,6 +9,8 @@ mod things;
 mod vertexes;
 
 use std::cell::RefCell;
+use std::error::Error;
+use std::fmt;
 use std::rc::Rc;
 
 use crate::map::{
@@ -17,7 +20,9 @@ use crate::map::{
 };
 
 pub use crate::geometry::BoundingBox;
+use crate::geometry::Line;
 pub use crate::map::{
+    blockmap::Blockmap,
     linedefs::{Flags, Linedef},
     nodes::{Node, NodeChild},
     sectors::Sector,
@@ -28,33 +33,127 @@ pub use crate::map::{
     vertexes::Vertex,
 };
 pub use crate::wad::WadFile;
+use crate::wad::{MapLumpName, WadError};
+
+// Errors that can happen while loading a map
+#[derive(Debug)]
+pub enum MapError {
+    Wad(WadError), // Reading the WAD file failed
+    InvalidReference {
+        map_name: String,  // Map name
+        lump_name: String, // The lump with the reference, e.g. LINEDEFS
+        entry: usize,      // Index of the entry in the lump
+        index: i16,        // The invalid index
+    },
+    NoNodes {
+        map_name: String, // Map name
+    },
+    MissingThing {
+        map_name: String, // Map name
+        thing_type: i16,  // The thing type that is required, e.g. the player 1 start
+    },
+    UnknownThingType {
+        thing_type: i16, // The thing type that isn't in MAP_OBJECT_INFOS
+        x: f32,          // Position of the thing
+        y: f32,          // Position of the thing
+    },
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::Wad(error) => write!(f, "{}", error),
+            MapError::InvalidReference {
+                map_name,
+                lump_name,
+                entry,
+                index,
+            } => write!(
+                f,
+                "Invalid index {} in entry {} of {} in map {}",
+                index, entry, lump_name, map_name
+            ),
+            MapError::NoNodes { map_name } => write!(f, "Map {} has no nodes", map_name),
+            MapError::MissingThing {
+                map_name,
+                thing_type,
+            } => write!(f, "Map {} has no thing of type {}", map_name, thing_type),
+            MapError::UnknownThingType { thing_type, x, y } => {
+                write!(f, "Unknown thing type {} at ({}, {})", thing_type, x, y)
+            }
+        }
+    }
+}
+
+impl Error for MapError {}
+
+impl From<WadError> for MapError {
+    fn from(error: WadError) -> MapError {
+        MapError::Wad(error)
+    }
+}
+
+// Look up an item that is referenced by index from an entry in a map lump
+fn get_by_index<T>(
+    items: &[Rc<T>],
+    index: i16,
+    map_name: &str,
+    lump_name: MapLumpName,
+    entry: usize,
+) -> Result<Rc<T>, MapError> {
+    match usize::try_from(index).ok().and_then(|i| items.get(i)) {
+        Some(item) => Ok(Rc::clone(item)),
+        None => Err(MapError::InvalidReference {
+            map_name: map_name.to_string(),
+            lump_name: lump_name.to_string(),
+            entry,
+            index,
+        }),
+    }
+}
+
+// A linedef crossed by a trace. See intercept_t in p_local.h.
+#[derive(Debug)]
+pub struct Intercept {
+    pub fraction: f32, // Distance along the trace, from 0 at the start to 1 at the end
+    pub linedef: Rc<Linedef>, // The crossed linedef
+}
 
 #[allow(dead_code)]
 pub struct Map {
-    pub things: Vec<Rc<Thing>>,            // Monsters, weapons, keys, etc
-    pub vertexes: Vec<Rc<Vertex>>,         // Vertexes that make up the lines
-    pub linedefs: Vec<Rc<Linedef>>,        // Lines
-    pub sidedefs: Vec<Rc<Sidedef>>,        // What's on the side of a line
-    pub segs: Vec<Rc<Seg>>,                // Lines, split by the BSP builder
-    pub subsectors: Vec<Rc<SubSector>>,    // Sectors, split by the BSP builder
-    pub nodes: Vec<Rc<Node>>,              // BSP tree
+    pub things: Vec<Rc<Thing>>,              // Monsters, weapons, keys, etc
+    pub vertexes: Vec<Rc<Vertex>>,           // Vertexes that make up the lines
+    pub linedefs: Vec<Rc<Linedef>>,          // Lines
+    pub sidedefs: Vec<Rc<RefCell<Sidedef>>>, // What's on the side of a line
+    pub segs: Vec<Rc<Seg>>,                  // Lines, split by the BSP builder
+    pub subsectors: Vec<Rc<SubSector>>,      // Sectors, split by the BSP builder
+    pub nodes: Vec<Rc<Node>>,                // BSP tree
     pub sectors: Vec<Rc<RefCell<Sector>>>, // Closed polygons made up of linedefs, mutatable with RefCell
     pub root_node: Rc<Node>,               // Root node of the BSP tree
     pub bounding_box: BoundingBox,         // Bounding box for the whole map
+    pub blockmap: Blockmap,                // Linedefs by 128x128 block, for collision detection
 }
 
 impl Map {
     // Load map
-    pub fn new(wad_file: &WadFile, map_name: &str) -> Map {
-        let things = load_things(wad_file, map_name);
-        let vertexes = load_vertexes(wad_file, map_name);
-        let sectors = load_sectors(wad_file, map_name);
-        let sidedefs = load_sidedefs(wad_file, &sectors, map_name);
-        let linedefs = load_linedefs(wad_file, &vertexes, &sidedefs, map_name);
-        let segs = load_segs(wad_file, &vertexes, &linedefs, map_name);
-        let subsectors = load_subsectors(wad_file, &segs, map_name);
-        let nodes = load_nodes(wad_file, &subsectors, map_name);
-        let root_node = Rc::clone(&nodes[nodes.len() - 1]);
+    pub fn new(wad_file: &WadFile, map_name: &str) -> Result<Map, MapError> {
+        let things = load_things(wad_file, map_name)?;
+        let vertexes = load_vertexes(wad_file, map_name)?;
+        let sectors = load_sectors(wad_file, map_name)?;
+        let sidedefs = load_sidedefs(wad_file, &sectors, map_name)?;
+        let linedefs = load_linedefs(wad_file, &vertexes, &sidedefs, map_name)?;
+        let segs = load_segs(wad_file, &vertexes, &linedefs, map_name)?;
+        let subsectors = load_subsectors(wad_file, &segs, map_name)?;
+        let nodes = load_nodes(wad_file, &subsectors, map_name)?;
+        let blockmap = Blockmap::load(wad_file, &linedefs, map_name)?;
+        let root_node = match nodes.last() {
+            Some(node) => Rc::clone(node),
+            None => {
+                return Err(MapError::NoNodes {
+                    map_name: map_name.to_string(),
+                })
+            }
+        };
 
         let mut bounding_box = BoundingBox::extendable_new();
 
@@ -63,7 +162,7 @@ impl Map {
             bounding_box.extend(&linedef.end_vertex);
         }
 
-        Map {
+        Ok(Map {
             things,
             vertexes,
             linedefs,
@@ -74,6 +173,288 @@ impl Map {
             sectors,
             root_node,
             bounding_box,
+            blockmap,
+        })
+    }
+
+    // The linedefs crossed by a line from start to end, nearest first. Only the
+    // linedefs in the blocks around the line are checked. See P_PathTraverse in
+    // p_maputl.c.
+    pub fn trace_line(&self, start: &Vertex, end: &Vertex) -> Vec<Intercept> {
+        let min = Vertex::new(start.x.min(end.x), start.y.min(end.y));
+        let max = Vertex::new(start.x.max(end.x), start.y.max(end.y));
+        let trace = Line::new(start, end);
+
+        let mut intercepts: Vec<Intercept> = self
+            .blockmap
+            .get_linedefs(&min, &max)
+            .into_iter()
+            .filter_map(|index| {
+                let linedef = &self.linedefs[index];
+                let line = Line::new(&linedef.start_vertex, &linedef.end_vertex);
+                trace.crossing_fraction(&line).map(|fraction| Intercept {
+                    fraction,
+                    linedef: Rc::clone(linedef),
+                })
+            })
+            .collect();
+        intercepts.sort_by(|a, b| a.fraction.total_cmp(&b.fraction));
+
+        intercepts
+    }
+
+    // The sectors with a tag number. Like Boom, tag 0 doesn't match the sectors
+    // without a tag. See P_FindSectorFromLineTag in p_spec.c.
+    pub fn get_tagged_sectors(&self, tag: i16) -> Vec<Rc<RefCell<Sector>>> {
+        if tag == 0 {
+            return Vec::new();
         }
+
+        self.sectors
+            .iter()
+            .filter(|sector| sector.borrow().tag_number == tag)
+            .map(Rc::clone)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_maps::{square_room, two_rooms, TestLinedef, TEST_MAP_NAME};
+    use crate::wad::MapLumpName;
+    use crate::wad_builder::{WadBuilder, WadType};
+    use std::f32::consts::PI;
+
+    #[test]
+    fn test_load_square_room() {
+        let map = Map::new(&square_room().build(), TEST_MAP_NAME).unwrap();
+
+        assert_eq!(map.things.len(), 1);
+        assert_eq!(map.vertexes.len(), 4);
+        assert_eq!(map.linedefs.len(), 4);
+        assert_eq!(map.sidedefs.len(), 4);
+        assert_eq!(map.sectors.len(), 1);
+        assert_eq!(map.segs.len(), 4);
+        assert_eq!(map.subsectors.len(), 2);
+        assert_eq!(map.nodes.len(), 1);
+
+        assert_eq!(map.bounding_box.left, 0.0);
+        assert_eq!(map.bounding_box.right, 128.0);
+        assert_eq!(map.bounding_box.top, 0.0);
+        assert_eq!(map.bounding_box.bottom, 128.0);
+    }
+
+    #[test]
+    fn test_load_things() {
+        let map = Map::new(&square_room().build(), TEST_MAP_NAME).unwrap();
+
+        let thing = get_thing_by_type(&map.things, ThingTypes::Player1Start).unwrap();
+        assert_eq!((thing.x, thing.y), (64.0, 64.0));
+        assert_eq!(thing.angle, 0.0);
+        assert!(get_thing_by_type(&map.things, ThingTypes::Player2Start).is_none());
+    }
+
+    #[test]
+    fn test_load_sectors() {
+        let map = Map::new(&two_rooms().build(), TEST_MAP_NAME).unwrap();
+
+        let east = map.sectors[1].borrow();
+        assert_eq!(east.id, 1);
+        assert_eq!(east.floor_height, 16);
+        assert_eq!(east.ceiling_height, 112);
+        assert_eq!(east.floor_texture, "FLOOR4_8");
+        assert_eq!(east.ceiling_texture, "CEIL3_5");
+        assert_eq!(east.light_level, 255);
+    }
+
+    #[test]
+    fn test_load_linedefs_and_sidedefs() {
+        let map = Map::new(&two_rooms().build(), TEST_MAP_NAME).unwrap();
+
+        // A wall only has a front side
+        let wall = &map.linedefs[0];
+        assert_eq!(wall.flags, Flags::BLOCKING);
+        assert_eq!((wall.start_vertex.x, wall.start_vertex.y), (0.0, 0.0));
+        assert_eq!((wall.end_vertex.x, wall.end_vertex.y), (0.0, 128.0));
+        let front_sidedef = wall.front_sidedef.as_ref().unwrap().borrow();
+        assert_eq!(front_sidedef.middle_texture, "STARTAN3");
+        assert_eq!(front_sidedef.upper_texture, "-");
+        assert_eq!(front_sidedef.sector.borrow().id, 0);
+        assert!(wall.back_sidedef.is_none());
+
+        // The portal joins the two sectors
+        let portal = &map.linedefs[3];
+        assert_eq!(portal.flags, Flags::TWOSIDED);
+        let front_sidedef = portal.front_sidedef.as_ref().unwrap().borrow();
+        let back_sidedef = portal.back_sidedef.as_ref().unwrap().borrow();
+        assert_eq!(front_sidedef.sector.borrow().id, 0);
+        assert_eq!(back_sidedef.sector.borrow().id, 1);
+        assert_eq!(back_sidedef.middle_texture, "-");
+    }
+
+    #[test]
+    fn test_load_segs_and_subsectors() {
+        let map = Map::new(&two_rooms().build(), TEST_MAP_NAME).unwrap();
+
+        // The east subsector starts with the back side of the portal, going north
+        let east = &map.subsectors[1];
+        assert_eq!(east.segs.len(), 4);
+        let seg = &east.segs[0];
+        assert!(seg.direction);
+        assert_eq!(seg.linedef.id, 3);
+        assert_eq!((seg.start_vertex.x, seg.start_vertex.y), (128.0, 0.0));
+        assert_eq!((seg.end_vertex.x, seg.end_vertex.y), (128.0, 128.0));
+        assert_eq!(seg.angle, 16384); // North
+        assert_eq!(seg.offset, 0);
+    }
+
+    #[test]
+    fn test_load_nodes() {
+        let map = Map::new(&two_rooms().build(), TEST_MAP_NAME).unwrap();
+
+        let root_node = &map.root_node;
+        assert_eq!((root_node.x, root_node.y), (128.0, 128.0));
+        assert_eq!((root_node.dx, root_node.dy), (0.0, -128.0));
+
+        // Bounding boxes are in WAD order, with top the highest y
+        assert_eq!(root_node.right_bounding_box.left, 0.0);
+        assert_eq!(root_node.right_bounding_box.right, 128.0);
+        assert_eq!(root_node.left_bounding_box.left, 128.0);
+        assert_eq!(root_node.left_bounding_box.right, 256.0);
+        assert_eq!(root_node.left_bounding_box.top, 128.0);
+
+        match (&root_node.right_child, &root_node.left_child) {
+            (NodeChild::SubSector(right), NodeChild::SubSector(left)) => {
+                assert!(Rc::ptr_eq(right, &map.subsectors[0]));
+                assert!(Rc::ptr_eq(left, &map.subsectors[1]));
+            }
+            _ => panic!("Expected two subsectors"),
+        }
+    }
+
+    #[test]
+    fn test_thing_angle() {
+        let mut builder = square_room();
+        builder.add_thing(32, 32, 90, 2);
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+
+        let thing = get_thing_by_type(&map.things, ThingTypes::Player2Start).unwrap();
+        assert!((thing.angle - PI / 2.0).abs() < 0.0001);
+    }
+
+    #[test]
+    fn test_missing_map() {
+        match Map::new(&square_room().build(), "E1M2") {
+            Err(MapError::Wad(WadError::MapLumpNotFound { map_name, .. })) => {
+                assert_eq!(map_name, "E1M2")
+            }
+            _ => panic!("Expected a missing map"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_reference() {
+        let mut builder = square_room();
+        builder.add_linedef(TestLinedef {
+            start_vertex: 0,
+            end_vertex: 2,
+            flags: 0,
+            special_type: 0,
+            sector_tag: 0,
+            front_sidedef: 99,
+            back_sidedef: -1,
+        });
+
+        match Map::new(&builder.build(), TEST_MAP_NAME) {
+            Err(MapError::InvalidReference {
+                lump_name,
+                entry,
+                index,
+                ..
+            }) => {
+                assert_eq!(lump_name, "LINEDEFS");
+                assert_eq!(entry, 4);
+                assert_eq!(index, 99);
+            }
+            _ => panic!("Expected an invalid reference"),
+        }
+    }
+
+    // Replace the empty BLOCKMAP of the square room
+    fn square_room_with_blockmap(blockmap: &[i16]) -> Result<Map, MapError> {
+        let mut wad_builder =
+            WadBuilder::from_wad_file(&square_room().build(), WadType::Iwad).unwrap();
+        let data = blockmap.iter().flat_map(|i| i.to_le_bytes()).collect();
+        wad_builder
+            .replace_map_lump(TEST_MAP_NAME, MapLumpName::Blockmap, data)
+            .unwrap();
+
+        let wad_file = WadFile::new(wad_builder.write().unwrap()).unwrap();
+        Map::new(&wad_file, TEST_MAP_NAME)
+    }
+
+    #[test]
+    fn test_trace_line() {
+        let map = Map::new(&two_rooms().build(), TEST_MAP_NAME).unwrap();
+
+        // From the west room through the portal to the east wall
+        let intercepts = map.trace_line(&Vertex::new(64.0, 64.0), &Vertex::new(320.0, 64.0));
+        let linedefs: Vec<i16> = intercepts.iter().map(|i| i.linedef.id).collect();
+        assert_eq!(linedefs, vec![3, 5]);
+        assert_eq!(intercepts[0].fraction, 0.25);
+        assert_eq!(intercepts[1].fraction, 0.75);
+
+        // A short trace doesn't reach any lines
+        assert!(map
+            .trace_line(&Vertex::new(64.0, 64.0), &Vertex::new(100.0, 64.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_build_blockmap() {
+        // The test maps have an empty BLOCKMAP, so it's built from the linedefs
+        let map = Map::new(&two_rooms().build(), TEST_MAP_NAME).unwrap();
+        let blockmap = &map.blockmap;
+
+        assert_eq!((blockmap.origin.x, blockmap.origin.y), (0.0, 0.0));
+        assert_eq!((blockmap.columns, blockmap.rows), (3, 2));
+
+        // The bottom-left corner has the west and south walls of the west room
+        let linedefs = blockmap.get_linedefs(&Vertex::new(10.0, 10.0), &Vertex::new(20.0, 20.0));
+        assert_eq!(linedefs, vec![0, 2]);
+
+        // Blocks outside of the map are ignored
+        let linedefs =
+            blockmap.get_linedefs(&Vertex::new(-100.0, -100.0), &Vertex::new(-50.0, -50.0));
+        assert!(linedefs.is_empty());
+    }
+
+    #[test]
+    fn test_load_blockmap() {
+        // One block at (-8, -8) with linedefs 1 and 3, after the 4 word header
+        // and 1 word offset
+        let map = square_room_with_blockmap(&[-8, -8, 1, 1, 5, 0, 1, 3, -1]).unwrap();
+        let blockmap = &map.blockmap;
+
+        assert_eq!((blockmap.origin.x, blockmap.origin.y), (-8.0, -8.0));
+        assert_eq!((blockmap.columns, blockmap.rows), (1, 1));
+        let linedefs = blockmap.get_linedefs(&Vertex::new(0.0, 0.0), &Vertex::new(1.0, 1.0));
+        assert_eq!(linedefs, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_invalid_blockmap() {
+        // Unknown linedef
+        assert!(matches!(
+            square_room_with_blockmap(&[0, 0, 1, 1, 5, 0, 4, -1]),
+            Err(MapError::InvalidReference { index: 4, .. })
+        ));
+
+        // Missing end of the list
+        assert!(matches!(
+            square_room_with_blockmap(&[0, 0, 1, 1, 5, 0, 1]),
+            Err(MapError::Wad(WadError::InvalidLump { .. }))
+        ));
     }
 }
@@ -1,5 +1,5 @@
 use crate::geometry::BoundingBox;
-use crate::map::SubSector;
+use crate::map::{get_by_index, MapError, SubSector};
 use crate::wad::{MapLumpName, WadFile};
 use std::rc::Rc;
 
@@ -14,14 +14,28 @@ pub enum NodeChild {
 
 impl NodeChild {
     // Create a NodeChild which is either a node or subsector from an index in the WAD file
-    fn from_index(index: i16, nodes: &[Rc<Node>], subsectors: &[Rc<SubSector>]) -> NodeChild {
+    fn from_index(
+        index: i16,
+        nodes: &[Rc<Node>],
+        subsectors: &[Rc<SubSector>],
+        map_name: &str,
+        entry: usize,
+    ) -> Result<NodeChild, MapError> {
         let is_subsector = index & NODE_IS_SUBSECTOR == NODE_IS_SUBSECTOR;
-        let stripped_index = (index & !NODE_IS_SUBSECTOR) as usize;
+        let stripped_index = index & !NODE_IS_SUBSECTOR;
 
         if is_subsector {
-            NodeChild::SubSector(Rc::clone(&subsectors[stripped_index]))
+            let subsector = get_by_index(
+                subsectors,
+                stripped_index,
+                map_name,
+                MapLumpName::Nodes,
+                entry,
+            )?;
+            Ok(NodeChild::SubSector(subsector))
         } else {
-            NodeChild::Node(Rc::clone(&nodes[stripped_index]))
+            let node = get_by_index(nodes, stripped_index, map_name, MapLumpName::Nodes, entry)?;
+            Ok(NodeChild::Node(node))
         }
     }
 }
@@ -46,8 +60,8 @@ pub fn load_nodes(
     wad_file: &WadFile,
     subsectors: &[Rc<SubSector>],
     map_name: &str,
-) -> Vec<Rc<Node>> {
-    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Nodes);
+) -> Result<Vec<Rc<Node>>, MapError> {
+    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Nodes)?;
     let count = dir_entry.size as usize / 28; // A node is 28 bytes long
 
     let mut nodes = Vec::new();
@@ -73,11 +87,23 @@ pub fn load_nodes(
                 right: wad_file.read_f32_from_i16(offset + 22),
             },
 
-            right_child: NodeChild::from_index(wad_file.read_i16(offset + 24), &nodes, subsectors),
-            left_child: NodeChild::from_index(wad_file.read_i16(offset + 26), &nodes, subsectors),
+            right_child: NodeChild::from_index(
+                wad_file.read_i16(offset + 24),
+                &nodes,
+                subsectors,
+                map_name,
+                i,
+            )?,
+            left_child: NodeChild::from_index(
+                wad_file.read_i16(offset + 26),
+                &nodes,
+                subsectors,
+                map_name,
+                i,
+            )?,
         };
         nodes.push(Rc::new(node));
     }
 
-    nodes
+    Ok(nodes)
 }
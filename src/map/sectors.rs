@@ -1,6 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::map::MapError;
 use crate::wad::{MapLumpName, WadFile};
 
 #[allow(dead_code)]
@@ -16,8 +17,11 @@ pub struct Sector {
     pub tag_number: i16,
 }
 
-pub fn load_sectors(wad_file: &WadFile, map_name: &str) -> Vec<Rc<RefCell<Sector>>> {
-    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Sectors);
+pub fn load_sectors(
+    wad_file: &WadFile,
+    map_name: &str,
+) -> Result<Vec<Rc<RefCell<Sector>>>, MapError> {
+    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Sectors)?;
     let count = dir_entry.size as usize / 26; // A sector is 26 bytes long
 
     let mut results = Vec::new();
@@ -40,5 +44,5 @@ pub fn load_sectors(wad_file: &WadFile, map_name: &str) -> Vec<Rc<RefCell<Sector
         results.push(Rc::new(RefCell::new(sector)));
     }
 
-    results
+    Ok(results)
 }
@@ -1,4 +1,4 @@
-use crate::map::{Linedef, Vertex};
+use crate::map::{get_by_index, Linedef, MapError, Vertex};
 use crate::wad::{MapLumpName, WadFile};
 use std::rc::Rc;
 
@@ -19,8 +19,8 @@ pub fn load_segs(
     vertexes: &[Rc<Vertex>],
     linedefs: &[Rc<Linedef>],
     map_name: &str,
-) -> Vec<Rc<Seg>> {
-    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Segs);
+) -> Result<Vec<Rc<Seg>>, MapError> {
+    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Segs)?;
     let count = dir_entry.size as usize / 12; // A seg is 12 bytes long
 
     let mut results = Vec::new();
@@ -28,15 +28,33 @@ pub fn load_segs(
         let offset = dir_entry.offset as usize + i * 12;
         let seg = Seg {
             id: i as i16,
-            start_vertex: Rc::clone(&vertexes[wad_file.read_i16(offset) as usize]),
-            end_vertex: Rc::clone(&vertexes[wad_file.read_i16(offset + 2) as usize]),
+            start_vertex: get_by_index(
+                vertexes,
+                wad_file.read_i16(offset),
+                map_name,
+                MapLumpName::Segs,
+                i,
+            )?,
+            end_vertex: get_by_index(
+                vertexes,
+                wad_file.read_i16(offset + 2),
+                map_name,
+                MapLumpName::Segs,
+                i,
+            )?,
             angle: wad_file.read_i16(offset + 4),
-            linedef: Rc::clone(&linedefs[wad_file.read_i16(offset + 6) as usize]),
+            linedef: get_by_index(
+                linedefs,
+                wad_file.read_i16(offset + 6),
+                map_name,
+                MapLumpName::Segs,
+                i,
+            )?,
             direction: wad_file.read_i16(offset + 8) != 0,
             offset: wad_file.read_i16(offset + 10),
         };
         results.push(Rc::new(seg));
     }
 
-    results
+    Ok(results)
 }
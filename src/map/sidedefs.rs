@@ -1,7 +1,7 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::map::Sector;
+use crate::map::{get_by_index, MapError, Sector};
 use crate::wad::{MapLumpName, WadFile};
 
 #[allow(dead_code)]
@@ -20,8 +20,8 @@ pub fn load_sidedefs(
     wad_file: &WadFile,
     sectors: &[Rc<RefCell<Sector>>],
     map_name: &str,
-) -> Vec<Rc<Sidedef>> {
-    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Sidedefs);
+) -> Result<Vec<Rc<RefCell<Sidedef>>>, MapError> {
+    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Sidedefs)?;
     let count = dir_entry.size as usize / 30; // A sidedef is 30 bytes long
 
     let mut results = Vec::new();
@@ -35,10 +35,16 @@ pub fn load_sidedefs(
             upper_texture: wad_file.read_lump_name(offset + 4),
             lower_texture: wad_file.read_lump_name(offset + 12),
             middle_texture: wad_file.read_lump_name(offset + 20),
-            sector: Rc::clone(&sectors[wad_file.read_i16(offset + 28) as usize]),
+            sector: get_by_index(
+                sectors,
+                wad_file.read_i16(offset + 28),
+                map_name,
+                MapLumpName::Sidedefs,
+                i,
+            )?,
         };
-        results.push(Rc::new(sidedef));
+        results.push(Rc::new(RefCell::new(sidedef)));
     }
 
-    results
+    Ok(results)
 }
@@ -1,4 +1,4 @@
-use crate::map::Seg;
+use crate::map::{get_by_index, MapError, Seg};
 use crate::wad::{MapLumpName, WadFile};
 use std::rc::Rc;
 
@@ -7,8 +7,12 @@ pub struct SubSector {
     pub segs: Vec<Rc<Seg>>,
 }
 
-pub fn load_subsectors(wad_file: &WadFile, segs: &[Rc<Seg>], map_name: &str) -> Vec<Rc<SubSector>> {
-    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Ssectors);
+pub fn load_subsectors(
+    wad_file: &WadFile,
+    segs: &[Rc<Seg>],
+    map_name: &str,
+) -> Result<Vec<Rc<SubSector>>, MapError> {
+    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Ssectors)?;
     let count = dir_entry.size as usize / 4; // A subsector is 4 bytes long
 
     let mut results = Vec::new();
@@ -19,8 +23,14 @@ pub fn load_subsectors(wad_file: &WadFile, segs: &[Rc<Seg>], map_name: &str) ->
         let seg_count = wad_file.read_i16(offset);
         let first_seg_number = wad_file.read_i16(offset + 2);
         let mut subsector_segs = Vec::new();
-        for i in first_seg_number..first_seg_number + seg_count {
-            subsector_segs.push(Rc::clone(&segs[i as usize]));
+        for seg_number in first_seg_number..first_seg_number.saturating_add(seg_count) {
+            subsector_segs.push(get_by_index(
+                segs,
+                seg_number,
+                map_name,
+                MapLumpName::Ssectors,
+                i,
+            )?);
         }
 
         let subsector = SubSector {
@@ -29,5 +39,5 @@ pub fn load_subsectors(wad_file: &WadFile, segs: &[Rc<Seg>], map_name: &str) ->
         results.push(Rc::new(subsector));
     }
 
-    results
+    Ok(results)
 }
@@ -1,3 +1,4 @@
+use crate::map::MapError;
 use crate::wad::{MapLumpName, WadFile};
 use std::rc::Rc;
 
@@ -11,6 +12,7 @@ pub enum ThingTypes {
     Player3Start = 3,
     Player4Start = 4,
     DeathMatchStart = 11,
+    TeleportDestination = 14,
 }
 
 #[derive(Debug)]
@@ -22,8 +24,8 @@ pub struct Thing {
     pub flags: i16,
 }
 
-pub fn load_things(wad_file: &WadFile, map_name: &str) -> Vec<Rc<Thing>> {
-    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Things);
+pub fn load_things(wad_file: &WadFile, map_name: &str) -> Result<Vec<Rc<Thing>>, MapError> {
+    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Things)?;
     let count = dir_entry.size as usize / 10; // A thing is 10 bytes long
 
     let mut results = Vec::new();
@@ -40,16 +42,17 @@ pub fn load_things(wad_file: &WadFile, map_name: &str) -> Vec<Rc<Thing>> {
         results.push(Rc::new(thing));
     }
 
-    results
+    Ok(results)
 }
 
-pub fn get_thing_by_type(things: &Vec<Rc<Thing>>, thing_type: ThingTypes) -> Rc<Thing> {
+// Find the first thing of a type, or None if the map doesn't have one
+pub fn get_thing_by_type(things: &Vec<Rc<Thing>>, thing_type: ThingTypes) -> Option<Rc<Thing>> {
     let i16_thing_type = thing_type as i16;
     for thing in things {
         if thing.thing_type == i16_thing_type {
-            return Rc::clone(thing);
+            return Some(Rc::clone(thing));
         }
     }
 
-    panic!("Could not find thing of type {}", i16_thing_type);
+    None
 }
@@ -4,6 +4,7 @@ use std::ops::{Add, Sub};
 use std::rc::Rc;
 
 use crate::geometry::Line;
+use crate::map::MapError;
 use crate::wad::{MapLumpName, WadFile};
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -44,7 +45,7 @@ impl fmt::Debug for Vertex {
     }
 }
 
-impl<'a, 'b> Add<&'b Vertex> for &'a Vertex {
+impl<'b> Add<&'b Vertex> for &Vertex {
     type Output = Vertex;
 
     fn add(self, other: &'b Vertex) -> Vertex {
@@ -55,7 +56,7 @@ impl<'a, 'b> Add<&'b Vertex> for &'a Vertex {
     }
 }
 
-impl<'a, 'b> Sub<&'b Vertex> for &'a Vertex {
+impl<'b> Sub<&'b Vertex> for &Vertex {
     type Output = Vertex;
 
     fn sub(self, other: &'b Vertex) -> Vertex {
@@ -66,8 +67,8 @@ impl<'a, 'b> Sub<&'b Vertex> for &'a Vertex {
     }
 }
 
-pub fn load_vertexes(wad_file: &WadFile, map_name: &str) -> Vec<Rc<Vertex>> {
-    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Vertexes);
+pub fn load_vertexes(wad_file: &WadFile, map_name: &str) -> Result<Vec<Rc<Vertex>>, MapError> {
+    let dir_entry = wad_file.get_dir_entry_for_map_lump(map_name, MapLumpName::Vertexes)?;
     let count = dir_entry.size as usize / 4; // A vertex is 4 bytes long
 
     let mut results = Vec::new();
@@ -80,5 +81,5 @@ pub fn load_vertexes(wad_file: &WadFile, map_name: &str) -> Vec<Rc<Vertex>> {
         results.push(Rc::new(vertex));
     }
 
-    results
+    Ok(results)
 }
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use std::rc::Rc;
 
 use crate::info::{MapObjectInfo, State, StateId, MAP_OBJECT_INFOS, STATES};
-use crate::map::{Map, ThingTypes, Vertex};
+use crate::map::{Map, MapError, ThingTypes, Vertex};
 use crate::thinkers::Thinker;
 
 #[allow(dead_code)]
@@ -14,6 +14,15 @@ pub struct MapObject {
     pub position: Vertex,
     pub angle: f32, // In radians. 0=East, pi/2=North, pi=West, 3pi/2=South
     pub flags: i16,
+    pub temporary: bool, // Spawned during the game, removed once its states end
+}
+
+impl MapObject {
+    // Has a temporary map object reached the end of its states?
+    // See P_SetMobjState in p_mobj.c.
+    pub fn is_finished(&self) -> bool {
+        self.temporary && self.state.id == StateId::S_NULL
+    }
 }
 
 #[derive(Debug)]
@@ -22,7 +31,7 @@ pub struct MapObjects {
 }
 
 impl MapObjects {
-    pub fn new(map: &Map) -> MapObjects {
+    pub fn new(map: &Map) -> Result<MapObjects, MapError> {
         let object_infos_map = Self::index_map_object_infos();
 
         let mut objects = Vec::new();
@@ -35,7 +44,14 @@ impl MapObjects {
                 continue;
             }
 
-            let map_object_info = object_infos_map.get(&thing.thing_type).unwrap();
+            let map_object_info =
+                object_infos_map
+                    .get(&thing.thing_type)
+                    .ok_or(MapError::UnknownThingType {
+                        thing_type: thing.thing_type,
+                        x: thing.x,
+                        y: thing.y,
+                    })?;
 
             objects.push(Rc::new(RefCell::new(MapObject {
                 info: map_object_info.clone(),
@@ -43,10 +59,37 @@ impl MapObjects {
                 position: Vertex::new(thing.x, thing.y),
                 angle: thing.angle,
                 flags: thing.flags,
+                temporary: false,
             })));
         }
 
-        MapObjects { objects }
+        Ok(MapObjects { objects })
+    }
+
+    // Spawn a temporary map object, e.g. teleport fog. See P_SpawnMobj in p_mobj.c.
+    pub fn spawn(
+        &mut self,
+        info: MapObjectInfo,
+        position: Vertex,
+        angle: f32,
+    ) -> Rc<RefCell<MapObject>> {
+        let map_object = Rc::new(RefCell::new(MapObject {
+            state: STATES[info.spawn_state as usize].clone(),
+            info,
+            position,
+            angle,
+            flags: 0,
+            temporary: true,
+        }));
+        self.objects.push(Rc::clone(&map_object));
+
+        map_object
+    }
+
+    // Remove the temporary map objects that are finished
+    pub fn remove_finished(&mut self) {
+        self.objects
+            .retain(|map_object| !map_object.borrow().is_finished());
     }
 
     fn index_map_object_infos() -> HashMap<i16, MapObjectInfo> {
@@ -118,6 +161,10 @@ impl Thinker for MapObjectThinker {
         let spawn_state = self.map_object.borrow().info.spawn_state;
         self.move_to_state(spawn_state);
     }
+
+    fn is_done(&self) -> bool {
+        self.map_object.borrow().is_finished()
+    }
 }
 
 pub fn kill_everything(thinkers: &mut Vec<Box<dyn Thinker>>) {
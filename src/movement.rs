@@ -0,0 +1,550 @@
+// Player movement with collision detection against the linedefs in the blockmap.
+// The player is a square box that can't overlap blocking linedefs. Blocked moves
+// slide along the wall. See P_TryMove and P_SlideMove in p_map.c.
+// Vertically, the player falls with gravity and the view height is smoothed after
+// step-ups and landings. See P_ZMovement in p_mobj.c and P_CalcHeight in p_user.c.
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use std::rc::Rc;
+
+use crate::game::Player;
+use crate::geometry::Line;
+use crate::map::{Flags, Linedef, Map, Sector, Vertex};
+use crate::renderer::{get_sector_from_vertex, PLAYER_EYE_HEIGHT};
+
+pub const PLAYER_RADIUS: f32 = 16.0; // Half the width of the player's box
+pub const PLAYER_HEIGHT: f32 = 56.0; // Minimum gap between floor and ceiling to fit
+pub const MAX_STEP_HEIGHT: f32 = 24.0; // Highest step up that can be walked onto
+const MAX_STEP_MOVE: f32 = 8.0; // Moves are split so that thin lines can't be skipped
+const FREE_FRACTION_ITERATIONS: usize = 8; // Binary search steps to get close to a wall
+
+const GRAVITY: f32 = 1.0; // Added to the falling speed each tic
+const MAX_BOB: f32 = 16.0; // Maximum view bob
+const BOB_PERIOD: f32 = 20.0; // Tics per up and down bob cycle
+const CEILING_CLEARANCE: f32 = 4.0; // The eyes stay this far below the ceiling
+const VIEW_HEIGHT_RECOVERY: f32 = 0.25; // Added to the view height change each tic
+
+// Does the player's box at position overlap with a linedef? The bounding boxes must
+// overlap and the corners of the player's box must be on both sides of the line.
+// See P_BoxOnLineSide in p_maputl.c.
+fn touches_linedef(position: &Vertex, linedef: &Linedef) -> bool {
+    let start = &linedef.start_vertex;
+    let end = &linedef.end_vertex;
+
+    if position.x + PLAYER_RADIUS <= start.x.min(end.x)
+        || position.x - PLAYER_RADIUS >= start.x.max(end.x)
+        || position.y + PLAYER_RADIUS <= start.y.min(end.y)
+        || position.y - PLAYER_RADIUS >= start.y.max(end.y)
+    {
+        return false;
+    }
+
+    let direction = end.as_ref() - start.as_ref();
+    let sides: Vec<f32> = [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)]
+        .iter()
+        .map(|(dx, dy)| {
+            let corner = Vertex::new(
+                position.x + dx * PLAYER_RADIUS,
+                position.y + dy * PLAYER_RADIUS,
+            );
+            direction.cross_product(&(&corner - start))
+        })
+        .collect();
+
+    sides.iter().any(|&s| s < 0.0) && sides.iter().any(|&s| s > 0.0)
+}
+
+// The bottom and top of the gap between the sectors on both sides of a two-sided
+// linedef. Returns None for one-sided linedefs. See P_LineOpening in p_maputl.c.
+pub fn line_opening(linedef: &Linedef) -> Option<(f32, f32)> {
+    let (Some(front_sidedef), Some(back_sidedef)) = (&linedef.front_sidedef, &linedef.back_sidedef)
+    else {
+        return None;
+    };
+
+    let front_sector = &front_sidedef.borrow().sector;
+    let back_sector = &back_sidedef.borrow().sector;
+    let front_sector = front_sector.borrow();
+    let back_sector = back_sector.borrow();
+
+    let top = front_sector.ceiling_height.min(back_sector.ceiling_height) as f32;
+    let bottom = front_sector.floor_height.max(back_sector.floor_height) as f32;
+
+    Some((bottom, top))
+}
+
+// Can the player standing at height z not pass a linedef? One-sided and blocking
+// lines always block. Two-sided lines block if the opening is too small or the step
+// up is too high. See PIT_CheckLine in p_map.c.
+fn blocks(linedef: &Linedef, z: f32) -> bool {
+    if linedef.flags & Flags::BLOCKING != 0 {
+        return true;
+    }
+
+    let Some((bottom, top)) = line_opening(linedef) else {
+        return true;
+    };
+
+    top - bottom < PLAYER_HEIGHT || top - z < PLAYER_HEIGHT || bottom - z > MAX_STEP_HEIGHT
+}
+
+// Get a linedef that blocks the player at a position, if any.
+// See P_CheckPosition in p_map.c.
+pub fn check_position(map: &Map, position: &Vertex, z: f32) -> Option<Rc<Linedef>> {
+    let min = Vertex::new(position.x - PLAYER_RADIUS, position.y - PLAYER_RADIUS);
+    let max = Vertex::new(position.x + PLAYER_RADIUS, position.y + PLAYER_RADIUS);
+
+    map.blockmap
+        .get_linedefs(&min, &max)
+        .into_iter()
+        .map(|index| &map.linedefs[index])
+        .find(|linedef| touches_linedef(position, linedef) && blocks(linedef, z))
+        .map(Rc::clone)
+}
+
+// The sectors the player's box at a position overlaps with: the sector in the
+// center and the sectors of the two-sided lines it touches. A sector can be in the
+// list more than once. Returns an empty list if there is no sector.
+pub fn touched_sectors(map: &Map, position: &Vertex) -> Vec<Rc<RefCell<Sector>>> {
+    let Some(sector) = get_sector_from_vertex(map, position) else {
+        return Vec::new();
+    };
+    let mut sectors = vec![sector];
+
+    let min = Vertex::new(position.x - PLAYER_RADIUS, position.y - PLAYER_RADIUS);
+    let max = Vertex::new(position.x + PLAYER_RADIUS, position.y + PLAYER_RADIUS);
+    for index in map.blockmap.get_linedefs(&min, &max) {
+        let linedef = &map.linedefs[index];
+        let (Some(front_sidedef), Some(back_sidedef)) =
+            (&linedef.front_sidedef, &linedef.back_sidedef)
+        else {
+            continue;
+        };
+
+        if !touches_linedef(position, linedef) {
+            continue;
+        }
+
+        for sidedef in [front_sidedef, back_sidedef] {
+            sectors.push(Rc::clone(&sidedef.borrow().sector));
+        }
+    }
+
+    sectors
+}
+
+// The floor and ceiling heights at a position. The player's box can overlap other
+// sectors than the one in the center, the highest floor and lowest ceiling of the
+// sectors it touches are used. Returns None if there is no sector.
+pub fn floor_and_ceiling(map: &Map, position: &Vertex) -> Option<(f32, f32)> {
+    let sectors = touched_sectors(map, position);
+    if sectors.is_empty() {
+        return None;
+    }
+
+    let mut floor = f32::MIN;
+    let mut ceiling = f32::MAX;
+    for sector in sectors {
+        let sector = sector.borrow();
+        floor = floor.max(sector.floor_height as f32);
+        ceiling = ceiling.min(sector.ceiling_height as f32);
+    }
+
+    Some((floor, ceiling))
+}
+
+// Does the player at a position fit between the floor and ceiling of a sector it
+// touches? Players that don't touch the sector aren't in its way.
+// See P_ThingHeightClip in p_map.c.
+pub fn fits_in_sector(map: &Map, position: &Vertex, sector: &RefCell<Sector>) -> bool {
+    let sectors = touched_sectors(map, position);
+    if !sectors.iter().any(|s| s.as_ptr() == sector.as_ptr()) {
+        return true;
+    }
+
+    match floor_and_ceiling(map, position) {
+        Some((floor, ceiling)) => ceiling - floor >= PLAYER_HEIGHT,
+        None => true,
+    }
+}
+
+// The linedefs with a special that the player crossed, moving from one position to
+// another, with whether the player came from the back side. A linedef is crossed if
+// the positions are on different sides of it and its vertexes are on different
+// sides of the move. See P_TryMove in p_map.c.
+pub fn crossed_special_linedefs(map: &Map, from: &Vertex, to: &Vertex) -> Vec<(Rc<Linedef>, bool)> {
+    let min = Vertex::new(from.x.min(to.x), from.y.min(to.y));
+    let max = Vertex::new(from.x.max(to.x), from.y.max(to.y));
+    let path = Line::new(from, to);
+
+    map.blockmap
+        .get_linedefs(&min, &max)
+        .into_iter()
+        .map(|index| &map.linedefs[index])
+        .filter_map(|linedef| {
+            let line = Line::new(&linedef.start_vertex, &linedef.end_vertex);
+            let is_back_side = from.is_left_of_line(&line);
+            let crossed = linedef.special_type.get() != 0
+                && is_back_side != to.is_left_of_line(&line)
+                && linedef.start_vertex.is_left_of_line(&path)
+                    != linedef.end_vertex.is_left_of_line(&path);
+            crossed.then(|| (Rc::clone(linedef), is_back_side))
+        })
+        .collect()
+}
+
+// Move in small steps until blocked. When blocked, get as close as possible to the
+// blocking line. Returns the new position, the blocking line and the part of the
+// move that wasn't done.
+fn try_move(
+    map: &Map,
+    position: &Vertex,
+    delta: &Vertex,
+    z: f32,
+) -> (Vertex, Option<Rc<Linedef>>, Vertex) {
+    let length = (delta.x * delta.x + delta.y * delta.y).sqrt();
+    let steps = (length / MAX_STEP_MOVE).ceil().max(1.0) as usize;
+    let step = Vertex::new(delta.x / steps as f32, delta.y / steps as f32);
+
+    let mut position = position.clone();
+    for i in 0..steps {
+        let new_position = &position + &step;
+        let Some(linedef) = check_position(map, &new_position, z) else {
+            position = new_position;
+            continue;
+        };
+
+        // Find the largest fraction of the step that is free
+        let (mut free, mut blocked) = (0.0, 1.0);
+        for _ in 0..FREE_FRACTION_ITERATIONS {
+            let fraction = (free + blocked) / 2.0;
+            let new_position = Vertex::new(
+                position.x + step.x * fraction,
+                position.y + step.y * fraction,
+            );
+            if check_position(map, &new_position, z).is_some() {
+                blocked = fraction;
+            } else {
+                free = fraction;
+            }
+        }
+        position = Vertex::new(position.x + step.x * free, position.y + step.y * free);
+
+        let remaining_steps = (steps - i) as f32 - free;
+        let remaining = Vertex::new(step.x * remaining_steps, step.y * remaining_steps);
+        return (position, Some(linedef), remaining);
+    }
+
+    (position, None, Vertex::new(0.0, 0.0))
+}
+
+// Move the player by delta at height z and return the new position. A blocked move
+// slides along the blocking line. If that doesn't work, only the x or y part of the
+// move is tried.
+pub fn slide_move(map: &Map, position: &Vertex, delta: &Vertex, z: f32) -> Vertex {
+    let (position, linedef, remaining) = try_move(map, position, delta, z);
+    let Some(linedef) = linedef else {
+        return position;
+    };
+
+    // Project the rest of the move onto the direction of the blocking line
+    let direction = linedef.end_vertex.as_ref() - linedef.start_vertex.as_ref();
+    let length_squared = direction.x * direction.x + direction.y * direction.y;
+    let along = (remaining.x * direction.x + remaining.y * direction.y) / length_squared;
+    let slide = Vertex::new(direction.x * along, direction.y * along);
+
+    for attempt in [
+        slide,
+        Vertex::new(remaining.x, 0.0),
+        Vertex::new(0.0, remaining.y),
+    ] {
+        if attempt.x == 0.0 && attempt.y == 0.0 {
+            continue;
+        }
+
+        let (new_position, _, _) = try_move(map, &position, &attempt, z);
+        if new_position.x != position.x || new_position.y != position.y {
+            return new_position;
+        }
+    }
+
+    position
+}
+
+// Fall with gravity, or step up onto a higher floor. Called once per tic, after
+// floor_height and ceiling_height have been updated. See P_ZMovement in p_mobj.c.
+pub fn z_movement(player: &mut Player) {
+    // Step up, the view height catches up with it
+    if player.z < player.floor_height {
+        player.view_height -= player.floor_height - player.z;
+        player.delta_view_height = (PLAYER_EYE_HEIGHT - player.view_height) / 8.0;
+    }
+
+    player.z += player.momentum_z;
+
+    if player.z <= player.floor_height {
+        // Squat down after a hard landing
+        if player.momentum_z < -GRAVITY * 8.0 {
+            player.delta_view_height = player.momentum_z / 8.0;
+        }
+        player.momentum_z = 0.0;
+        player.z = player.floor_height;
+    } else if player.momentum_z == 0.0 {
+        player.momentum_z = -GRAVITY * 2.0;
+    } else {
+        player.momentum_z -= GRAVITY;
+    }
+
+    if player.z + PLAYER_HEIGHT > player.ceiling_height {
+        player.momentum_z = player.momentum_z.min(0.0);
+        player.z = player.ceiling_height - PLAYER_HEIGHT;
+    }
+}
+
+// Move the view height back to the eye height after a step-up or landing. Called
+// once per tic. See P_CalcHeight in p_user.c.
+pub fn smooth_view_height(player: &mut Player) {
+    player.view_height += player.delta_view_height;
+
+    if player.view_height > PLAYER_EYE_HEIGHT {
+        player.view_height = PLAYER_EYE_HEIGHT;
+        player.delta_view_height = 0.0;
+    }
+
+    if player.view_height < PLAYER_EYE_HEIGHT / 2.0 {
+        player.view_height = PLAYER_EYE_HEIGHT / 2.0;
+        if player.delta_view_height <= 0.0 {
+            player.delta_view_height = f32::EPSILON;
+        }
+    }
+
+    if player.delta_view_height != 0.0 {
+        player.delta_view_height += VIEW_HEIGHT_RECOVERY;
+    }
+}
+
+// The view bob for a move of delta in one tic. Faster moves bob more.
+pub fn bob(delta: &Vertex) -> f32 {
+    ((delta.x * delta.x + delta.y * delta.y) / 4.0).min(MAX_BOB)
+}
+
+// Height of the eyes, with the view bob at a tic. See P_CalcHeight in p_user.c.
+pub fn view_z(player: &Player, tic: u32) -> f32 {
+    let bob = player.bob / 2.0 * (2.0 * PI * tic as f32 / BOB_PERIOD).sin();
+    let view_z = player.z + player.view_height + bob;
+
+    view_z.min(player.ceiling_height - CEILING_CLEARANCE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::WadFile;
+    use crate::test_maps::{square_room, two_rooms, TEST_MAP_NAME};
+
+    fn load_map(wad_file: &WadFile) -> Map {
+        Map::new(wad_file, TEST_MAP_NAME).unwrap()
+    }
+
+    #[test]
+    fn test_blocked_by_wall() {
+        let map = load_map(&square_room().build());
+
+        // Walking east stops at the player's radius from the east wall
+        let position = slide_move(
+            &map,
+            &Vertex::new(64.0, 64.0),
+            &Vertex::new(100.0, 0.0),
+            0.0,
+        );
+        assert!(position.x <= 128.0 - PLAYER_RADIUS);
+        assert!(position.x > 128.0 - PLAYER_RADIUS - 0.5);
+        assert_eq!(position.y, 64.0);
+
+        assert!(check_position(&map, &Vertex::new(64.0, 64.0), 0.0).is_none());
+        assert_eq!(
+            check_position(&map, &Vertex::new(120.0, 64.0), 0.0)
+                .unwrap()
+                .id,
+            2
+        );
+    }
+
+    #[test]
+    fn test_crossed_special_linedefs() {
+        let mut builder = two_rooms();
+        builder.linedef_mut(3).special_type = 88;
+        let map = load_map(&builder.build());
+        let crossed = |from: (f32, f32), to: (f32, f32)| -> Vec<(i16, bool)> {
+            let from = Vertex::new(from.0, from.1);
+            let to = Vertex::new(to.0, to.1);
+            crossed_special_linedefs(&map, &from, &to)
+                .iter()
+                .map(|(linedef, is_back_side)| (linedef.id, *is_back_side))
+                .collect()
+        };
+
+        // Crossing the portal between the rooms, from the front and the back
+        assert_eq!(crossed((120.0, 64.0), (136.0, 64.0)), vec![(3, false)]);
+        assert_eq!(crossed((136.0, 64.0), (120.0, 80.0)), vec![(3, true)]);
+
+        // Moving up to it and along it
+        assert!(crossed((100.0, 64.0), (127.0, 64.0)).is_empty());
+        assert!(crossed((120.0, 10.0), (120.0, 100.0)).is_empty());
+
+        // Lines without a special don't count
+        assert!(crossed((100.0, 64.0), (100.0, 140.0)).is_empty());
+    }
+
+    #[test]
+    fn test_slide_along_wall() {
+        let map = load_map(&square_room().build());
+
+        // Walking north-east into the east wall slides north
+        let position = slide_move(
+            &map,
+            &Vertex::new(100.0, 40.0),
+            &Vertex::new(20.0, 20.0),
+            0.0,
+        );
+        assert!(position.x <= 128.0 - PLAYER_RADIUS);
+        assert!(position.y > 55.0);
+        assert!(position.y <= 60.0);
+
+        // Into the north-east corner, the player stays out of both walls
+        let position = slide_move(
+            &map,
+            &Vertex::new(100.0, 100.0),
+            &Vertex::new(50.0, 50.0),
+            0.0,
+        );
+        assert!(position.x <= 128.0 - PLAYER_RADIUS);
+        assert!(position.y <= 128.0 - PLAYER_RADIUS);
+    }
+
+    #[test]
+    fn test_step_up() {
+        // A step of 16 into the east room can be walked onto
+        let map = load_map(&two_rooms().build());
+        let position = slide_move(
+            &map,
+            &Vertex::new(64.0, 64.0),
+            &Vertex::new(128.0, 0.0),
+            0.0,
+        );
+        assert_eq!(position.x, 192.0);
+
+        // A step of 32 is too high
+        let mut builder = two_rooms();
+        builder.sector_mut(1).floor_height = 32;
+        let map = load_map(&builder.build());
+        let position = slide_move(
+            &map,
+            &Vertex::new(64.0, 64.0),
+            &Vertex::new(128.0, 0.0),
+            0.0,
+        );
+        assert!(position.x <= 128.0 - PLAYER_RADIUS);
+
+        // Once up on the step, it's possible to walk back down
+        let position = slide_move(
+            &map,
+            &Vertex::new(192.0, 64.0),
+            &Vertex::new(-128.0, 0.0),
+            32.0,
+        );
+        assert_eq!(position.x, 64.0);
+    }
+
+    // A player in a sector with the floor at 0 and the ceiling at 128
+    fn player_at(z: f32) -> Player {
+        Player {
+            floor_height: 0.0,
+            ceiling_height: 128.0,
+            z,
+            ..Player::new(Vertex::new(64.0, 64.0), 0.0)
+        }
+    }
+
+    #[test]
+    fn test_floor_and_ceiling() {
+        let map = load_map(&two_rooms().build());
+        assert_eq!(
+            floor_and_ceiling(&map, &Vertex::new(64.0, 64.0)),
+            Some((0.0, 128.0))
+        );
+
+        // Overlapping the two-sided line, the east room's floor and ceiling are used
+        assert_eq!(
+            floor_and_ceiling(&map, &Vertex::new(120.0, 64.0)),
+            Some((16.0, 112.0))
+        );
+    }
+
+    #[test]
+    fn test_falling() {
+        let mut player = player_at(64.0);
+
+        // Gravity speeds up the fall
+        z_movement(&mut player);
+        assert_eq!((player.z, player.momentum_z), (64.0, -2.0));
+        z_movement(&mut player);
+        assert_eq!((player.z, player.momentum_z), (62.0, -3.0));
+
+        let mut tics = 2;
+        while player.z > 0.0 {
+            z_movement(&mut player);
+            smooth_view_height(&mut player);
+            tics += 1;
+        }
+        assert_eq!(tics, 11);
+        assert_eq!(player.momentum_z, 0.0);
+
+        // A hard landing squats the view, which then recovers
+        assert!(player.view_height < PLAYER_EYE_HEIGHT);
+        for _ in 0..35 {
+            z_movement(&mut player);
+            smooth_view_height(&mut player);
+        }
+        assert_eq!(player.z, 0.0);
+        assert_eq!(player.view_height, PLAYER_EYE_HEIGHT);
+        assert_eq!(player.delta_view_height, 0.0);
+    }
+
+    #[test]
+    fn test_step_up_is_smoothed() {
+        let mut player = player_at(0.0);
+        player.floor_height = 16.0;
+
+        // The feet go up at once, the eyes stay where they were
+        z_movement(&mut player);
+        assert_eq!(player.z, 16.0);
+        assert_eq!(player.view_height, PLAYER_EYE_HEIGHT - 16.0);
+        assert_eq!(view_z(&player, 0), PLAYER_EYE_HEIGHT);
+
+        let mut previous_view_height = player.view_height;
+        for _ in 0..7 {
+            smooth_view_height(&mut player);
+            assert!(player.view_height > previous_view_height);
+            previous_view_height = player.view_height;
+        }
+        assert_eq!(player.view_height, PLAYER_EYE_HEIGHT);
+    }
+
+    #[test]
+    fn test_view_bob() {
+        let mut player = player_at(0.0);
+        assert_eq!(view_z(&player, 5), PLAYER_EYE_HEIGHT);
+
+        // Walking slowly bobs a little, running bobs at most MAX_BOB / 2
+        player.bob = bob(&Vertex::new(4.0, 0.0));
+        assert_eq!(view_z(&player, 5), PLAYER_EYE_HEIGHT + 2.0);
+        player.bob = bob(&Vertex::new(16.0, 0.0));
+        assert_eq!(view_z(&player, 5), PLAYER_EYE_HEIGHT + 8.0);
+        assert_eq!(view_z(&player, 15), PLAYER_EYE_HEIGHT - 8.0);
+
+        // The eyes stay below the ceiling
+        player.ceiling_height = 48.0;
+        assert_eq!(view_z(&player, 5), 44.0);
+    }
+}
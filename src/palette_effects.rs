@@ -0,0 +1,150 @@
+// Full screen palette effects: red when taking damage, yellow when picking up
+// bonuses and green while wearing a radiation suit. See ST_doPaletteStuff in
+// st_stuff.c.
+use clap::ValueEnum;
+
+const START_RED_PALETTES: usize = 1;
+const NUM_RED_PALETTES: usize = 8;
+const START_BONUS_PALETTES: usize = 9;
+const NUM_BONUS_PALETTES: usize = 4;
+const RADIATION_PALETTE: usize = 13;
+
+const MAX_DAMAGE_COUNT: i32 = 100; // See P_DamageMobj in p_inter.c
+const BONUS_ADD: i32 = 6; // Added to the bonus count for each pickup
+const RADIATION_SUIT_TICS: i32 = 60 * 35; // One minute, see IRONTICS in p_inter.c
+const RADIATION_SUIT_BLINK_TICS: i32 = 4 * 32; // Start blinking when running out
+
+// Effects that can be started from the command line, to preview them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PaletteEffect {
+    Damage,        // Maximum damage
+    Bonus,         // A single bonus pickup
+    RadiationSuit, // A freshly picked up radiation suit
+}
+
+#[derive(Debug, Default)]
+pub struct PaletteEffects {
+    damage_count: i32,        // Counts down after taking damage
+    bonus_count: i32,         // Counts down after a bonus pickup
+    radiation_suit_tics: i32, // Counts down while wearing a radiation suit
+}
+
+impl PaletteEffects {
+    pub fn new() -> PaletteEffects {
+        PaletteEffects::default()
+    }
+
+    // Flash red, the amount of red depends on the damage
+    pub fn add_damage(&mut self, damage: i32) {
+        self.damage_count = (self.damage_count + damage).min(MAX_DAMAGE_COUNT);
+    }
+
+    // Flash yellow
+    pub fn add_bonus(&mut self) {
+        self.bonus_count += BONUS_ADD;
+    }
+
+    // Turn green for a number of tics
+    pub fn set_radiation_suit(&mut self, tics: i32) {
+        self.radiation_suit_tics = tics;
+    }
+
+    pub fn start(&mut self, effect: PaletteEffect) {
+        match effect {
+            PaletteEffect::Damage => self.add_damage(MAX_DAMAGE_COUNT),
+            PaletteEffect::Bonus => self.add_bonus(),
+            PaletteEffect::RadiationSuit => self.set_radiation_suit(RADIATION_SUIT_TICS),
+        }
+    }
+
+    // Fade the effects, called once per tic. See P_PlayerThink in p_user.c.
+    pub fn tick(&mut self) {
+        self.damage_count = (self.damage_count - 1).max(0);
+        self.bonus_count = (self.bonus_count - 1).max(0);
+        self.radiation_suit_tics = (self.radiation_suit_tics - 1).max(0);
+    }
+
+    // The PLAYPAL palette to use. Damage has priority over bonuses, which have
+    // priority over the radiation suit.
+    pub fn palette_index(&self) -> usize {
+        if self.damage_count > 0 {
+            let palette = ((self.damage_count + 7) >> 3) as usize;
+            START_RED_PALETTES + palette.min(NUM_RED_PALETTES - 1)
+        } else if self.bonus_count > 0 {
+            let palette = ((self.bonus_count + 7) >> 3) as usize;
+            START_BONUS_PALETTES + palette.min(NUM_BONUS_PALETTES - 1)
+        } else if self.radiation_suit_tics > RADIATION_SUIT_BLINK_TICS
+            || self.radiation_suit_tics & 8 != 0
+        {
+            RADIATION_PALETTE
+        } else {
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_damage_fades() {
+        let mut palette_effects = PaletteEffects::new();
+        assert_eq!(palette_effects.palette_index(), 0);
+
+        palette_effects.add_damage(200);
+        assert_eq!(palette_effects.palette_index(), 8);
+
+        palette_effects.add_damage(0);
+        for _ in 0..90 {
+            palette_effects.tick();
+        }
+        assert_eq!(palette_effects.palette_index(), 3);
+
+        for _ in 0..10 {
+            palette_effects.tick();
+        }
+        assert_eq!(palette_effects.palette_index(), 0);
+    }
+
+    #[test]
+    fn test_bonus_fades() {
+        let mut palette_effects = PaletteEffects::new();
+        palette_effects.add_bonus();
+        assert_eq!(palette_effects.palette_index(), 10);
+
+        for _ in 0..5 {
+            palette_effects.tick();
+        }
+        assert_eq!(palette_effects.palette_index(), 10);
+
+        palette_effects.tick();
+        assert_eq!(palette_effects.palette_index(), 0);
+    }
+
+    #[test]
+    fn test_priorities() {
+        let mut palette_effects = PaletteEffects::new();
+        palette_effects.set_radiation_suit(RADIATION_SUIT_TICS);
+        assert_eq!(palette_effects.palette_index(), 13);
+
+        palette_effects.add_bonus();
+        assert_eq!(palette_effects.palette_index(), 10);
+
+        palette_effects.add_damage(10);
+        assert_eq!(palette_effects.palette_index(), 3);
+    }
+
+    #[test]
+    fn test_radiation_suit_blinks() {
+        let mut palette_effects = PaletteEffects::new();
+        palette_effects.set_radiation_suit(RADIATION_SUIT_BLINK_TICS + 1);
+        assert_eq!(palette_effects.palette_index(), 13);
+
+        // 4*32 tics left has bit 3 clear, 4*32 - 1 has it set
+        palette_effects.tick();
+        assert_eq!(palette_effects.palette_index(), 0);
+        palette_effects.tick();
+        assert_eq!(palette_effects.palette_index(), 13);
+    }
+}
@@ -0,0 +1,143 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::map::{Map, Sector};
+
+// Floors and ceilings of sectors move with a speed in units per tic towards a
+// destination height. Doors, lifts, floors and ceilings are thinkers that move
+// them. See T_MovePlane in p_floor.c.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Plane {
+    Floor,
+    Ceiling,
+}
+
+impl Plane {
+    pub fn height(&self, sector: &Sector) -> i16 {
+        match self {
+            Plane::Floor => sector.floor_height,
+            Plane::Ceiling => sector.ceiling_height,
+        }
+    }
+
+    pub fn set_height(&self, sector: &mut Sector, height: i16) {
+        match self {
+            Plane::Floor => sector.floor_height = height,
+            Plane::Ceiling => sector.ceiling_height = height,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoveResult {
+    Moving,          // The destination hasn't been reached yet
+    PastDestination, // The plane is at the destination
+}
+
+// Move a floor or ceiling by speed towards the destination. The plane stops at the
+// destination, which is reached one move after the plane gets there exactly. A
+// plane that blocks the player is moved back by the thinker, see Thinker::crushed.
+pub fn move_plane(
+    sector: &RefCell<Sector>,
+    plane: Plane,
+    speed: i16,
+    destination: i16,
+    direction: Direction,
+) -> MoveResult {
+    let mut sector = sector.borrow_mut();
+    let height = plane.height(&sector);
+
+    let (new_height, past_destination) = match direction {
+        Direction::Down => (height - speed, height - speed < destination),
+        Direction::Up => (height + speed, height + speed > destination),
+    };
+
+    if past_destination {
+        plane.set_height(&mut sector, destination);
+        MoveResult::PastDestination
+    } else {
+        plane.set_height(&mut sector, new_height);
+        MoveResult::Moving
+    }
+}
+
+// The sectors on the other side of the two-sided lines of a sector. A sector can be
+// in the list more than once. See getNextSector in p_spec.c.
+pub fn neighbor_sectors(map: &Map, sector: &Sector) -> Vec<Rc<RefCell<Sector>>> {
+    let mut neighbors = Vec::new();
+
+    for linedef in &map.linedefs {
+        let (Some(front_sidedef), Some(back_sidedef)) =
+            (&linedef.front_sidedef, &linedef.back_sidedef)
+        else {
+            continue;
+        };
+
+        let front_sector = &front_sidedef.borrow().sector;
+        let back_sector = &back_sidedef.borrow().sector;
+        if front_sector.borrow().id == sector.id {
+            neighbors.push(Rc::clone(back_sector));
+        } else if back_sector.borrow().id == sector.id {
+            neighbors.push(Rc::clone(front_sector));
+        }
+    }
+
+    neighbors
+}
+
+// The highest floor of the neighboring sectors, or the sector's own floor if it has
+// no neighbors. See P_FindHighestFloorSurrounding in p_spec.c.
+pub fn find_highest_floor_surrounding(map: &Map, sector: &Sector) -> i16 {
+    neighbor_sectors(map, sector)
+        .iter()
+        .map(|neighbor| neighbor.borrow().floor_height)
+        .max()
+        .unwrap_or(sector.floor_height)
+}
+
+// The lowest floor of the sector and its neighbors.
+// See P_FindLowestFloorSurrounding in p_spec.c.
+pub fn find_lowest_floor_surrounding(map: &Map, sector: &Sector) -> i16 {
+    neighbor_sectors(map, sector)
+        .iter()
+        .map(|neighbor| neighbor.borrow().floor_height)
+        .fold(sector.floor_height, i16::min)
+}
+
+// The lowest floor of the neighboring sectors that is higher than a height, or the
+// height if there is none. See P_FindNextHighestFloor in p_spec.c.
+pub fn find_next_highest_floor(map: &Map, sector: &Sector, height: i16) -> i16 {
+    neighbor_sectors(map, sector)
+        .iter()
+        .map(|neighbor| neighbor.borrow().floor_height)
+        .filter(|&floor_height| floor_height > height)
+        .min()
+        .unwrap_or(height)
+}
+
+// The highest ceiling of the neighboring sectors, or the sector's own ceiling if it
+// has no neighbors. See P_FindHighestCeilingSurrounding in p_spec.c.
+pub fn find_highest_ceiling_surrounding(map: &Map, sector: &Sector) -> i16 {
+    neighbor_sectors(map, sector)
+        .iter()
+        .map(|neighbor| neighbor.borrow().ceiling_height)
+        .max()
+        .unwrap_or(sector.ceiling_height)
+}
+
+// The lowest ceiling of the neighboring sectors, or the sector's own ceiling if it
+// has no neighbors. See P_FindLowestCeilingSurrounding in p_spec.c.
+pub fn find_lowest_ceiling_surrounding(map: &Map, sector: &Sector) -> i16 {
+    neighbor_sectors(map, sector)
+        .iter()
+        .map(|neighbor| neighbor.borrow().ceiling_height)
+        .min()
+        .unwrap_or(sector.ceiling_height)
+}
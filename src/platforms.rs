@@ -0,0 +1,323 @@
+use rand::Rng;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::map::{Linedef, Map, Sector};
+use crate::planes::{
+    find_highest_floor_surrounding, find_lowest_floor_surrounding, move_plane, Direction,
+    MoveResult, Plane,
+};
+use crate::thinkers::{get_thinkers_mut, is_sector_moving, Thinker};
+
+// Platforms, or lifts, are floors that go down, wait and come back up. Perpetual
+// platforms keep going up and down until they are stopped.
+// https://doomwiki.org/wiki/Lift
+
+const PLATFORM_SPEED: i16 = 1; // Units per tic
+const PLATFORM_WAIT: i16 = 3 * 35; // Tics a platform waits at the bottom or top
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlatformType {
+    PerpetualRaise,      // Move between the lowest and highest surrounding floor
+    DownWaitUpStay,      // Lower to the lowest surrounding floor, wait and come back up
+    BlazeDownWaitUpStay, // Fast down wait up stay
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlatformStatus {
+    Up,
+    Down,
+    Waiting,
+    InStasis, // A stopped perpetual platform
+}
+
+// See T_PlatRaise in p_plats.c
+#[derive(Debug)]
+pub struct Platform {
+    sector: Rc<RefCell<Sector>>, // The sector to move the floor of
+    platform_type: PlatformType, // What the platform does
+    tag: i16,                    // Tag of the linedef that started it
+    speed: i16,                  // Units per tic
+    low: i16,                    // Floor height at the bottom
+    high: i16,                   // Floor height at the top
+    wait: i16,                   // Tics to wait at the bottom and top
+    count: i16,                  // Counts down while waiting
+    status: PlatformStatus,      // Moving, waiting or stopped
+    old_status: PlatformStatus,  // Status before it was stopped
+    last_height: i16,            // Floor height before the last move
+    done: bool,                  // The platform is back up
+}
+
+impl Platform {
+    // See EV_DoPlat in p_plats.c
+    pub fn new(
+        map: &Map,
+        sector: Rc<RefCell<Sector>>,
+        platform_type: PlatformType,
+        tag: i16,
+    ) -> Platform {
+        let (floor_height, low, highest) = {
+            let sector = sector.borrow();
+            (
+                sector.floor_height,
+                find_lowest_floor_surrounding(map, &sector),
+                find_highest_floor_surrounding(map, &sector),
+            )
+        };
+
+        let (speed, high, status) = match platform_type {
+            PlatformType::PerpetualRaise => {
+                let status = if rand::thread_rng().gen_bool(0.5) {
+                    PlatformStatus::Up
+                } else {
+                    PlatformStatus::Down
+                };
+                (PLATFORM_SPEED, highest.max(floor_height), status)
+            }
+            PlatformType::DownWaitUpStay => {
+                (PLATFORM_SPEED * 4, floor_height, PlatformStatus::Down)
+            }
+            PlatformType::BlazeDownWaitUpStay => {
+                (PLATFORM_SPEED * 8, floor_height, PlatformStatus::Down)
+            }
+        };
+
+        Platform {
+            sector,
+            platform_type,
+            tag,
+            speed,
+            low: low.min(floor_height),
+            high,
+            wait: PLATFORM_WAIT,
+            count: 0,
+            status,
+            old_status: status,
+            last_height: floor_height,
+            done: false,
+        }
+    }
+
+    fn move_floor(&mut self, destination: i16, direction: Direction) -> MoveResult {
+        move_plane(
+            &self.sector,
+            Plane::Floor,
+            self.speed,
+            destination,
+            direction,
+        )
+    }
+}
+
+impl Thinker for Platform {
+    fn mutate(&mut self) {
+        self.last_height = self.sector.borrow().floor_height;
+
+        match self.status {
+            PlatformStatus::Up => {
+                if self.move_floor(self.high, Direction::Up) == MoveResult::PastDestination {
+                    self.count = self.wait;
+                    self.status = PlatformStatus::Waiting;
+                    if self.platform_type != PlatformType::PerpetualRaise {
+                        self.done = true;
+                    }
+                }
+            }
+
+            PlatformStatus::Down => {
+                if self.move_floor(self.low, Direction::Down) == MoveResult::PastDestination {
+                    self.count = self.wait;
+                    self.status = PlatformStatus::Waiting;
+                }
+            }
+
+            PlatformStatus::Waiting => {
+                self.count -= 1;
+                if self.count == 0 {
+                    self.status = if self.sector.borrow().floor_height == self.low {
+                        PlatformStatus::Up
+                    } else {
+                        PlatformStatus::Down
+                    };
+                }
+            }
+
+            PlatformStatus::InStasis => {}
+        }
+    }
+
+    fn moved_sector(&self) -> Option<&Rc<RefCell<Sector>>> {
+        Some(&self.sector)
+    }
+
+    // A platform that pushes the player into the ceiling goes back down
+    fn crushed(&mut self) {
+        if self.status != PlatformStatus::Up {
+            return;
+        }
+
+        self.sector.borrow_mut().floor_height = self.last_height;
+        self.count = self.wait;
+        self.status = PlatformStatus::Down;
+        self.done = false;
+    }
+
+    fn is_done(&self) -> bool {
+        self.done
+    }
+}
+
+// Start a platform in each sector with the linedef's tag that isn't moving yet.
+// Stopped perpetual platforms with the tag start again. Returns true if a platform
+// was started. See EV_DoPlat in p_plats.c.
+pub fn do_platform(
+    map: &Map,
+    linedef: &Linedef,
+    platform_type: PlatformType,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+) -> bool {
+    if platform_type == PlatformType::PerpetualRaise {
+        // See P_ActivateInStasis in p_plats.c
+        for platform in get_thinkers_mut::<Platform>(thinkers) {
+            if platform.tag == linedef.sector_tag && platform.status == PlatformStatus::InStasis {
+                platform.status = platform.old_status;
+            }
+        }
+    }
+
+    let mut started = false;
+
+    for sector in map.get_tagged_sectors(linedef.sector_tag) {
+        if is_sector_moving(thinkers, &sector.borrow()) {
+            continue;
+        }
+
+        let platform = Platform::new(map, sector, platform_type, linedef.sector_tag);
+        thinkers.push(Box::new(platform));
+        started = true;
+    }
+
+    started
+}
+
+// Stop the moving platforms with the linedef's tag, until a perpetual platform
+// special with the same tag starts them again. See EV_StopPlat in p_plats.c.
+pub fn stop_platforms(linedef: &Linedef, thinkers: &mut [Box<dyn Thinker>]) {
+    for platform in get_thinkers_mut::<Platform>(thinkers) {
+        if platform.tag == linedef.sector_tag && platform.status != PlatformStatus::InStasis {
+            platform.old_status = platform.status;
+            platform.status = PlatformStatus::InStasis;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_maps::{run_thinkers, tagged_two_rooms};
+
+    // The east room has a floor at 16, the west room at 0. Linedef 1 has the tag of
+    // the east room.
+    fn lift_height(map: &Map) -> i16 {
+        map.sectors[1].borrow().floor_height
+    }
+
+    // The lift height after each tic
+    fn run_heights(map: &Map, thinkers: &mut Vec<Box<dyn Thinker>>, tics: usize) -> Vec<i16> {
+        (0..tics)
+            .map(|_| {
+                run_thinkers(thinkers, 1);
+                lift_height(map)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_down_wait_up_stay() {
+        let map = tagged_two_rooms();
+        let linedef = &map.linedefs[1];
+        let mut thinkers = Vec::new();
+
+        assert!(do_platform(
+            &map,
+            linedef,
+            PlatformType::DownWaitUpStay,
+            &mut thinkers
+        ));
+        assert!(!do_platform(
+            &map,
+            linedef,
+            PlatformType::DownWaitUpStay,
+            &mut thinkers
+        ));
+
+        // Down 4 units per tic, wait 3 seconds and back up
+        run_thinkers(&mut thinkers, 2);
+        assert_eq!(lift_height(&map), 8);
+        run_thinkers(&mut thinkers, 3 + PLATFORM_WAIT as usize);
+        assert_eq!(lift_height(&map), 0);
+        run_thinkers(&mut thinkers, 2);
+        assert_eq!(lift_height(&map), 8);
+        run_thinkers(&mut thinkers, 3);
+        assert_eq!(lift_height(&map), 16);
+        assert!(thinkers.is_empty());
+    }
+
+    #[test]
+    fn test_platform_crushed() {
+        let map = tagged_two_rooms();
+        let mut thinkers: Vec<Box<dyn Thinker>> = Vec::new();
+        assert!(do_platform(
+            &map,
+            &map.linedefs[1],
+            PlatformType::BlazeDownWaitUpStay,
+            &mut thinkers
+        ));
+
+        // Something on the way up sends it back down after it waited
+        run_thinkers(&mut thinkers, 4 + PLATFORM_WAIT as usize);
+        assert_eq!(lift_height(&map), 8);
+        thinkers[0].crushed();
+        assert_eq!(lift_height(&map), 0);
+        run_thinkers(&mut thinkers, 1 + PLATFORM_WAIT as usize);
+        assert_eq!(lift_height(&map), 0);
+        run_thinkers(&mut thinkers, 3);
+        assert_eq!(lift_height(&map), 16);
+        assert!(thinkers.is_empty());
+    }
+
+    #[test]
+    fn test_perpetual_raise() {
+        let map = tagged_two_rooms();
+        let linedef = &map.linedefs[1];
+        let mut thinkers = Vec::new();
+
+        // It starts going either way and keeps moving between the lowest and highest
+        // floor
+        assert!(do_platform(
+            &map,
+            linedef,
+            PlatformType::PerpetualRaise,
+            &mut thinkers
+        ));
+        let cycle = 2 * (17 + PLATFORM_WAIT as usize);
+        let heights = run_heights(&map, &mut thinkers, cycle);
+        assert_eq!(heights.iter().min(), Some(&0));
+        assert_eq!(heights.iter().max(), Some(&16));
+
+        // Stopping freezes it until it's started again
+        stop_platforms(linedef, &mut thinkers);
+        let height = lift_height(&map);
+        let heights = run_heights(&map, &mut thinkers, cycle);
+        assert!(heights.iter().all(|&h| h == height));
+        assert!(!do_platform(
+            &map,
+            linedef,
+            PlatformType::PerpetualRaise,
+            &mut thinkers
+        ));
+        assert_eq!(thinkers.len(), 1);
+        let heights = run_heights(&map, &mut thinkers, cycle);
+        assert!(heights.iter().any(|&h| h != height));
+    }
+}
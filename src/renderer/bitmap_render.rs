@@ -3,6 +3,7 @@ use sdl2::pixels::Color;
 use std::rc::Rc;
 
 use super::clipped_line::ClippedLine;
+use super::lighting::Lighting;
 use super::pixels::Pixels;
 
 use crate::graphics::{Bitmap, Palette};
@@ -98,7 +99,7 @@ impl BitmapRender {
         });
     }
 
-    pub fn render(&mut self, pixels: &mut Pixels, palette: &Palette) {
+    pub fn render(&mut self, pixels: &mut Pixels, palette: &Palette, lighting: &Lighting) {
         // Bail if already rendered
         if self.state == BitmapRenderState::SolidSeg || self.state == BitmapRenderState::DrawnSeg {
             return;
@@ -109,6 +110,7 @@ impl BitmapRender {
                 render_vertical_bitmap_line(
                     pixels,
                     palette,
+                    lighting,
                     bitmap,
                     self.light_level,
                     &self.clipped_line,
@@ -187,32 +189,13 @@ impl PartialEq for BitmapRender {
 
 impl Eq for BitmapRender {}
 
-pub fn diminish_color(color: &Color, light_level: i16, distance: i16) -> Color {
-    let mut factor = light_level as f32 / 255.0; // Start with the sector light level
-
-    // Reduce the light based on the distance
-    // See r_plane.c
-    // The factor below is based on a visual feel of how things look rather
-    // then a calculation of what the actual doom code does.
-    let dimishing_factor: f32 = 1.0 / (16.0 * 256.0);
-    factor -= distance as f32 * dimishing_factor;
-    if factor < 0.0 {
-        factor = 0.0
-    };
-
-    Color::RGB(
-        (color.r as f32 * factor) as u8,
-        (color.g as f32 * factor) as u8,
-        (color.b as f32 * factor) as u8,
-    )
-}
-
 // Draw a vertical line of a texture
 // See 5.12.5 Perspective-Correct Texture Mapping in the game engine black book
 #[allow(clippy::too_many_arguments)]
 pub fn render_vertical_bitmap_line(
     pixels: &mut Pixels,
     palette: &Palette,
+    lighting: &Lighting,
     bitmap: &Bitmap,            // The texture or picture's bitmap
     light_level: i16,           // Sector light level
     clipped_line: &ClippedLine, // The clipped line in viewport coordinates
@@ -248,7 +231,7 @@ pub fn render_vertical_bitmap_line(
     tx %= bitmap.width;
 
     // z coordinate of column in world coordinates
-    let z = (((1.0 - ax) + ax) / ((1.0 - ax) * (1.0 / uz0) + ax * (1.0 / uz1))) as i16;
+    let z = ((1.0 - ax) + ax) / ((1.0 - ax) * (1.0 / uz0) + ax * (1.0 / uz1));
 
     for y in clipped_top_y..clipped_bottom_y + 1 {
         // Calculate texture y
@@ -263,8 +246,7 @@ pub fn render_vertical_bitmap_line(
         ty %= bitmap.height;
 
         if let Some(color_value) = bitmap.pixels[ty as usize][tx as usize] {
-            let color = palette.colors[color_value as usize];
-            let mut diminished_color = diminish_color(&color, light_level, z);
+            let mut diminished_color = lighting.wall_color(palette, color_value, light_level, z);
 
             if is_edge || (debug_draw_outline && (y == clipped_top_y || y == clipped_bottom_y)) {
                 diminished_color = Color::RGB(255, 255, 255);
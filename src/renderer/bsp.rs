@@ -34,7 +34,7 @@ pub fn get_sector_from_vertex(map: &Map, vertex: &Vertex) -> Option<Rc<RefCell<S
                     };
 
                     if let Some(sidedef) = opt_sidedef {
-                        return Some(sidedef.sector.clone());
+                        return Some(sidedef.borrow().sector.clone());
                     };
                 }
                 return None;
@@ -42,3 +42,33 @@ pub fn get_sector_from_vertex(map: &Map, vertex: &Vertex) -> Option<Rc<RefCell<S
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_maps::{square_room, two_rooms, TEST_MAP_NAME};
+
+    fn get_sector_id(map: &Map, x: f32, y: f32) -> Option<i16> {
+        get_sector_from_vertex(map, &Vertex::new(x, y)).map(|sector| sector.borrow().id)
+    }
+
+    #[test]
+    fn test_get_sector_from_vertex_in_square_room() {
+        let map = Map::new(&square_room().build(), TEST_MAP_NAME).unwrap();
+
+        // Both sides of the diagonal partition line
+        assert_eq!(get_sector_id(&map, 96.0, 32.0), Some(0));
+        assert_eq!(get_sector_id(&map, 32.0, 96.0), Some(0));
+    }
+
+    #[test]
+    fn test_get_sector_from_vertex_in_two_rooms() {
+        let map = Map::new(&two_rooms().build(), TEST_MAP_NAME).unwrap();
+
+        assert_eq!(get_sector_id(&map, 64.0, 64.0), Some(0));
+        assert_eq!(get_sector_id(&map, 192.0, 64.0), Some(1));
+
+        // On the partition line is the left side
+        assert_eq!(get_sector_id(&map, 128.0, 64.0), Some(1));
+    }
+}
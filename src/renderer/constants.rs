@@ -1,6 +1,6 @@
 use crate::game::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
-pub const PLAYER_EYE_HEIGHT: f32 = 41.0;
+pub const PLAYER_EYE_HEIGHT: f32 = 41.0; // View height that step-ups and landings return to
 
 // The game ran on 320x200 but ended up on monitors with squarepixels and  320x240
 // https://doomwiki.org/wiki/Aspect_ratio#:~:text=it%20was%20wide.-,Design%20of%20graphics,to%20this%20hardware%20video%20mode.
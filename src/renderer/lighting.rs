@@ -0,0 +1,250 @@
+use clap::ValueEnum;
+use sdl2::pixels::Color;
+
+use crate::graphics::{Colormaps, Palette, LIGHT_COLORMAPS};
+use crate::map::Vertex;
+
+// See r_main.h
+const LIGHT_LEVELS: usize = 16; // Sector light levels are reduced to 16 levels
+const LIGHT_SEG_SHIFT: i16 = 4; // Shift from a 0-255 light level to a light table row
+const MAX_LIGHT_SCALE: usize = 48; // Amount of wall & sprite scale steps
+const MAX_LIGHT_Z: usize = 128; // Amount of floor & ceiling distance steps
+const LIGHT_Z_STEP: f32 = 16.0; // Distance covered by one plane step
+const DIST_MAP: i32 = 2;
+
+// Doom's projection is the center of its 320 wide screen. The wall scale is
+// projection / distance and a scale step is 1/16th.
+const PROJECTION: f32 = 160.0;
+const LIGHT_SCALE_STEPS: f32 = 16.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LightingMode {
+    Colormap, // Colormap rows selected by light level and distance, like doom
+    Smooth,   // Palette colors scaled by light level and distance
+}
+
+// Light diminishing. In colormap mode, every palette index is mapped through a
+// COLORMAP row. The rows are looked up in the zlight table for floors and
+// ceilings and in the scalelight table for walls and sprites, see r_main.c.
+pub struct Lighting {
+    pub mode: LightingMode,
+    pub fixed_colormap: Option<usize>, // Colormap used for everything, e.g. invulnerability
+    colormaps: Colormaps,
+    zlight: [[u8; MAX_LIGHT_Z]; LIGHT_LEVELS], // Colormap rows for planes by distance
+    scalelight: [[u8; MAX_LIGHT_SCALE]; LIGHT_LEVELS], // Colormap rows for walls by scale
+}
+
+impl Lighting {
+    pub fn new(colormaps: Colormaps, mode: LightingMode) -> Lighting {
+        Lighting {
+            mode,
+            fixed_colormap: None,
+            colormaps,
+            zlight: make_zlight(),
+            scalelight: make_scalelight(),
+        }
+    }
+
+    // Color of a wall or sprite pixel at distance z
+    pub fn wall_color(&self, palette: &Palette, index: u8, light_level: i16, z: f32) -> Color {
+        let scale = (PROJECTION * LIGHT_SCALE_STEPS / z.max(1.0)) as usize;
+        let row = self.scalelight[light_table_row(light_level)][scale.min(MAX_LIGHT_SCALE - 1)];
+        self.color(palette, index, light_level, z, row)
+    }
+
+    // Color of a floor or ceiling pixel at distance z
+    pub fn plane_color(&self, palette: &Palette, index: u8, light_level: i16, z: f32) -> Color {
+        let distance = (z.max(0.0) / LIGHT_Z_STEP) as usize;
+        let row = self.zlight[light_table_row(light_level)][distance.min(MAX_LIGHT_Z - 1)];
+        self.color(palette, index, light_level, z, row)
+    }
+
+    // Light level of a wall. In colormap mode, walls along the x axis are made
+    // darker and walls along the y axis lighter, like doom's fake contrast.
+    pub fn wall_light_level(&self, light_level: i16, start: &Vertex, end: &Vertex) -> i16 {
+        if self.mode != LightingMode::Colormap {
+            return light_level;
+        }
+
+        if start.y == end.y {
+            light_level - (1 << LIGHT_SEG_SHIFT)
+        } else if start.x == end.x {
+            light_level + (1 << LIGHT_SEG_SHIFT)
+        } else {
+            light_level
+        }
+    }
+
+    fn color(&self, palette: &Palette, index: u8, light_level: i16, z: f32, row: u8) -> Color {
+        if let Some(fixed_colormap) = self.fixed_colormap {
+            return self.colormap_color(palette, fixed_colormap, index);
+        }
+
+        match self.mode {
+            LightingMode::Colormap => self.colormap_color(palette, row as usize, index),
+            LightingMode::Smooth => {
+                diminish_color(&palette.colors[index as usize], light_level, z as i16)
+            }
+        }
+    }
+
+    fn colormap_color(&self, palette: &Palette, colormap: usize, index: u8) -> Color {
+        palette.colors[self.colormaps.maps[colormap][index as usize] as usize]
+    }
+}
+
+// Row in the light tables for a 0-255 sector light level
+fn light_table_row(light_level: i16) -> usize {
+    (light_level >> LIGHT_SEG_SHIFT).clamp(0, LIGHT_LEVELS as i16 - 1) as usize
+}
+
+// Colormap row for a light table row, starting with full bright for the brightest row
+fn start_map(i: usize) -> i32 {
+    ((LIGHT_LEVELS - 1 - i) * 2 * LIGHT_COLORMAPS / LIGHT_LEVELS) as i32
+}
+
+fn clamp_colormap(level: i32) -> u8 {
+    level.clamp(0, LIGHT_COLORMAPS as i32 - 1) as u8
+}
+
+// See R_InitLightTables in r_main.c
+fn make_zlight() -> [[u8; MAX_LIGHT_Z]; LIGHT_LEVELS] {
+    let mut zlight = [[0; MAX_LIGHT_Z]; LIGHT_LEVELS];
+
+    for (i, row) in zlight.iter_mut().enumerate() {
+        for (j, level) in row.iter_mut().enumerate() {
+            let scale = PROJECTION as i32 / (j as i32 + 1);
+            *level = clamp_colormap(start_map(i) - scale / DIST_MAP);
+        }
+    }
+
+    zlight
+}
+
+// See R_ExecuteSetViewSize in r_main.c
+fn make_scalelight() -> [[u8; MAX_LIGHT_SCALE]; LIGHT_LEVELS] {
+    let mut scalelight = [[0; MAX_LIGHT_SCALE]; LIGHT_LEVELS];
+
+    for (i, row) in scalelight.iter_mut().enumerate() {
+        for (j, level) in row.iter_mut().enumerate() {
+            *level = clamp_colormap(start_map(i) - j as i32 / DIST_MAP);
+        }
+    }
+
+    scalelight
+}
+
+pub fn diminish_color(color: &Color, light_level: i16, distance: i16) -> Color {
+    let mut factor = light_level as f32 / 255.0; // Start with the sector light level
+
+    // Reduce the light based on the distance
+    // See r_plane.c
+    // The factor below is based on a visual feel of how things look rather
+    // then a calculation of what the actual doom code does.
+    let dimishing_factor: f32 = 1.0 / (16.0 * 256.0);
+    factor -= distance as f32 * dimishing_factor;
+    if factor < 0.0 {
+        factor = 0.0
+    };
+
+    Color::RGB(
+        (color.r as f32 * factor) as u8,
+        (color.g as f32 * factor) as u8,
+        (color.b as f32 * factor) as u8,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::INVULNERABILITY_COLORMAP;
+    use crate::test_maps::square_room;
+
+    fn lighting(mode: LightingMode) -> (Palette, Lighting) {
+        let wad_file = square_room().build();
+        let palette = Palette::new(&wad_file).unwrap();
+        let colormaps = Colormaps::new(&wad_file).unwrap();
+
+        (palette, Lighting::new(colormaps, mode))
+    }
+
+    #[test]
+    fn test_light_tables() {
+        let zlight = make_zlight();
+        let scalelight = make_scalelight();
+
+        // Close by planes are full bright
+        assert_eq!(zlight[15][0], 0);
+        assert_eq!(zlight[8][0], 0);
+        assert_eq!(zlight[0][0], 0);
+
+        // Far away planes fade to the sector's starting colormap
+        assert_eq!(zlight[15][127], 0);
+        assert_eq!(zlight[8][127], 28);
+        assert_eq!(zlight[8][20], 25);
+        assert_eq!(zlight[0][127], 31);
+
+        // Walls fade one colormap per two scale steps
+        assert_eq!(scalelight[8][0], 28);
+        assert_eq!(scalelight[8][10], 23);
+        assert_eq!(scalelight[8][47], 5);
+        assert_eq!(scalelight[15][47], 0);
+    }
+
+    #[test]
+    fn test_colormap_banding() {
+        let (palette, lighting) = lighting(LightingMode::Colormap);
+
+        // Pixels at nearly the same distance map to the same colormap row
+        assert_eq!(
+            lighting.plane_color(&palette, 100, 128, 320.0),
+            lighting.plane_color(&palette, 100, 128, 330.0)
+        );
+        assert_eq!(
+            lighting.plane_color(&palette, 100, 255, 1000.0),
+            palette.colors[100]
+        );
+        assert_ne!(
+            lighting.wall_color(&palette, 100, 128, 1000.0),
+            lighting.wall_color(&palette, 100, 128, 50.0)
+        );
+    }
+
+    #[test]
+    fn test_fixed_colormap() {
+        let (palette, mut lighting) = lighting(LightingMode::Smooth);
+        lighting.fixed_colormap = Some(INVULNERABILITY_COLORMAP);
+
+        let expected =
+            palette.colors[lighting.colormaps.maps[INVULNERABILITY_COLORMAP][100] as usize];
+        assert_eq!(lighting.wall_color(&palette, 100, 0, 5000.0), expected);
+        assert_eq!(lighting.plane_color(&palette, 100, 255, 0.0), expected);
+    }
+
+    #[test]
+    fn test_fake_contrast() {
+        let (_, colormap_lighting) = lighting(LightingMode::Colormap);
+        let (_, smooth_lighting) = lighting(LightingMode::Smooth);
+        let origin = Vertex::new(0.0, 0.0);
+
+        let horizontal = Vertex::new(64.0, 0.0);
+        let vertical = Vertex::new(0.0, 64.0);
+        let diagonal = Vertex::new(64.0, 64.0);
+        assert_eq!(
+            colormap_lighting.wall_light_level(160, &origin, &horizontal),
+            144
+        );
+        assert_eq!(
+            colormap_lighting.wall_light_level(160, &origin, &vertical),
+            176
+        );
+        assert_eq!(
+            colormap_lighting.wall_light_level(160, &origin, &diagonal),
+            160
+        );
+        assert_eq!(
+            smooth_lighting.wall_light_level(160, &origin, &vertical),
+            160
+        );
+    }
+}
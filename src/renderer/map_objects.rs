@@ -4,8 +4,8 @@ use std::rc::Rc;
 
 use super::bitmap_render::{BitmapRender, BitmapRenderState};
 use super::bsp::get_sector_from_vertex;
-use super::constants::PLAYER_EYE_HEIGHT;
-use super::misc::{clip_to_viewport, make_sidedef_non_vertical_line};
+use super::lighting::Lighting;
+use super::misc::{camera_focus_y, clip_to_viewport, make_sidedef_non_vertical_line};
 use super::pixels::Pixels;
 
 use crate::game::{Player, SCREEN_HEIGHT, SCREEN_WIDTH};
@@ -16,6 +16,7 @@ use crate::map::{Map, Vertex};
 use crate::map_objects::MapObjects;
 
 // Draw map objects (aka things)
+#[allow(clippy::too_many_arguments)]
 pub fn draw_map_objects(
     segs: &mut Vec<BitmapRender>,
     pixels: &mut Pixels,
@@ -24,6 +25,7 @@ pub fn draw_map_objects(
     sprites: &Sprites,
     map: &Map,
     palette: &Palette,
+    lighting: &Lighting,
 ) {
     const DEBUG_DRAW_OUTLINE: bool = false;
 
@@ -111,7 +113,7 @@ pub fn draw_map_objects(
             sector.borrow().light_level
         };
 
-        let player_height = player.floor_height + PLAYER_EYE_HEIGHT;
+        let player_height = player.view_z;
         let z = sector.borrow().floor_height;
         let mut bottom_height = z as f32 - player_height;
         let mut top_height = z as f32 + picture.bitmap.height as f32 - 1.0 - player_height;
@@ -121,8 +123,9 @@ pub fn draw_map_objects(
         top_height += picture.top_offset as f32 - picture.bitmap.height as f32;
 
         // Make bottom and top lines
-        let bottom = make_sidedef_non_vertical_line(&clipped_line.line, bottom_height);
-        let top = make_sidedef_non_vertical_line(&clipped_line.line, top_height);
+        let focus_y = camera_focus_y(player);
+        let bottom = make_sidedef_non_vertical_line(&clipped_line.line, bottom_height, focus_y);
+        let top = make_sidedef_non_vertical_line(&clipped_line.line, top_height, focus_y);
 
         // top_seg_clip and bottom_seg_clip is the area not obscured.
         // It starts off all of the screen and gets reduced by the segs in front
@@ -231,11 +234,11 @@ pub fn draw_map_objects(
         // Render any two sided textures behind the map object
         for seg in &mut *segs {
             if seg.is_behind_vertex(&view_port_vertex) {
-                seg.render(pixels, palette);
+                seg.render(pixels, palette, lighting);
             }
         }
 
         // Render the map object
-        map_object_bitmap_render.render(pixels, palette);
+        map_object_bitmap_render.render(pixels, palette, lighting);
     }
 }
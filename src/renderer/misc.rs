@@ -1,6 +1,6 @@
 use sdl2::rect::Point;
 
-use crate::game::SCREEN_WIDTH;
+use crate::game::{Player, SCREEN_WIDTH};
 use crate::geometry::Line;
 use crate::map::Vertex;
 
@@ -134,8 +134,15 @@ fn perspective_transform(v: &Vertex, y: f32) -> Vertex {
     Vertex::new(GAME_CAMERA_FOCUS_X * x / z, GAME_CAMERA_FOCUS_X * y / z)
 }
 
-// Make the slanted non-vertical line for a sidedef.
-pub fn make_sidedef_non_vertical_line(line: &Line, height: f32) -> SdlLine {
+// Screen y of the horizon. Looking up or down shears the view vertically, instead
+// of rotating it, like Heretic and most source ports do.
+pub fn camera_focus_y(player: &Player) -> f32 {
+    CAMERA_FOCUS_Y + GAME_CAMERA_FOCUS_X * player.pitch.tan()
+}
+
+// Make the slanted non-vertical line for a sidedef. focus_y is the screen y of
+// the horizon.
+pub fn make_sidedef_non_vertical_line(line: &Line, height: f32, focus_y: f32) -> SdlLine {
     let mut transformed_start = perspective_transform(&line.start, height);
     let mut transformed_end = perspective_transform(&line.end, height);
 
@@ -146,12 +153,12 @@ pub fn make_sidedef_non_vertical_line(line: &Line, height: f32) -> SdlLine {
 
     let mut screen_start = Point::new(
         (CAMERA_FOCUS_X - transformed_start.x) as i32,
-        (CAMERA_FOCUS_Y - transformed_start.y) as i32,
+        (focus_y - transformed_start.y) as i32,
     );
 
     let mut screen_end = Point::new(
         (CAMERA_FOCUS_X - transformed_end.x) as i32,
-        (CAMERA_FOCUS_Y - transformed_end.y) as i32,
+        (focus_y - transformed_end.y) as i32,
     );
 
     screen_start.x = screen_start.x.min(SCREEN_WIDTH as i32 - 1);
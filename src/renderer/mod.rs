@@ -2,6 +2,7 @@ mod bitmap_render;
 mod bsp;
 mod clipped_line;
 mod constants;
+mod lighting;
 mod map_objects;
 mod misc;
 mod pixels;
@@ -19,6 +20,8 @@ use crate::map::{Map, Node, NodeChild, SubSector, Vertex};
 use crate::map_objects::MapObjects;
 
 pub use bsp::get_sector_from_vertex;
+pub use constants::PLAYER_EYE_HEIGHT;
+pub use lighting::{Lighting, LightingMode};
 use map_objects::draw_map_objects;
 pub use pixels::Pixels;
 use segs::Segs;
@@ -43,10 +46,11 @@ impl Renderer<'_> {
         sky_texture: Rc<Texture>,
         flats: &'a mut Flats,
         palette: &'a Palette,
+        lighting: &'a Lighting,
         player: &'a Player,
-        timestamp: f32,
+        tic: u32,
     ) -> Renderer<'a> {
-        let segs = Segs::new(pixels, textures, flats, palette, player, timestamp);
+        let segs = Segs::new(pixels, textures, flats, palette, lighting, player, tic);
 
         Renderer {
             segs,
@@ -108,6 +112,7 @@ impl Renderer<'_> {
             draw_visplane(
                 self.segs.pixels,
                 self.segs.palette,
+                self.segs.lighting,
                 self.segs.player,
                 Rc::clone(&self.sky_texture),
                 visplane,
@@ -130,6 +135,7 @@ impl Renderer<'_> {
             self.sprites,
             self.map,
             self.segs.palette,
+            self.segs.lighting,
         );
 
         self.segs.draw_remaining_segs();
@@ -1,4 +1,5 @@
 use sdl2::pixels::Color;
+use sdl2::rect::Point;
 
 use crate::game::{SCREEN_HEIGHT, SCREEN_WIDTH};
 
@@ -13,14 +14,13 @@ impl Pixels {
         }
     }
 
-    #[allow(dead_code)]
     pub fn clear(&mut self) {
         self.pixels.iter_mut().for_each(|x| *x = 0);
     }
 
     // Set a single pixel
     pub fn set(&mut self, x: usize, y: usize, color: &Color) {
-        if x >= SCREEN_WIDTH as usize || y > SCREEN_HEIGHT as usize {
+        if x >= SCREEN_WIDTH as usize || y >= SCREEN_HEIGHT as usize {
             return;
         }
 
@@ -45,4 +45,46 @@ impl Pixels {
             self.pixels[3 * (y as usize * SCREEN_WIDTH as usize + x as usize) + 2] = color.b;
         }
     }
+
+    // Draw a line using Bresenham's algorithm. Pixels outside of the screen are skipped.
+    pub fn draw_line(&mut self, start: &Point, end: &Point, color: &Color) {
+        let dx = (end.x - start.x).abs();
+        let dy = -(end.y - start.y).abs();
+        let step_x = if start.x < end.x { 1 } else { -1 };
+        let step_y = if start.y < end.y { 1 } else { -1 };
+
+        let mut x = start.x;
+        let mut y = start.y;
+        let mut error = dx + dy;
+
+        loop {
+            if x >= 0 && y >= 0 {
+                self.set(x as usize, y as usize, color);
+            }
+
+            if x == end.x && y == end.y {
+                break;
+            }
+
+            let error2 = 2 * error;
+            if error2 >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if error2 <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    // Fill a rectangle. Pixels outside of the screen are skipped.
+    #[allow(dead_code)]
+    pub fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: &Color) {
+        for y in y.max(0)..y + height as i32 {
+            for x in x.max(0)..x + width as i32 {
+                self.set(x as usize, y as usize, color);
+            }
+        }
+    }
 }
@@ -8,8 +8,8 @@ use crate::map::{Flags as LinedefFlags, Seg, Sidedef};
 
 use super::bitmap_render::{render_vertical_bitmap_line, BitmapRender, BitmapRenderState};
 use super::clipped_line::ClippedLine;
-use super::constants::PLAYER_EYE_HEIGHT;
-use super::misc::{clip_to_viewport, make_sidedef_non_vertical_line};
+use super::lighting::Lighting;
+use super::misc::{camera_focus_y, clip_to_viewport, make_sidedef_non_vertical_line};
 use super::pixels::Pixels;
 use super::sdl_line::SdlLine;
 use super::sidedef_visplanes::SidedefVisPlanes;
@@ -24,10 +24,11 @@ pub struct Segs<'a> {
     // Game state
     pub pixels: &'a mut Pixels,
     pub palette: &'a Palette,
+    pub lighting: &'a Lighting,
     pub player: &'a Player,
     textures: &'a mut Textures,
     flats: &'a mut Flats,
-    timestamp: f32,
+    tic: u32, // Game tic, used for animated textures and flats
 
     // Outputs
     pub segs: Vec<BitmapRender>,  // Segs, rendered and unrendered
@@ -41,13 +42,14 @@ pub struct Segs<'a> {
 
 struct SideDefDetails<'a> {
     clipped_line: &'a ClippedLine, // The clipped line in viewport coords
-    sidedef: &'a Rc<Sidedef>,      // The sidedef
+    sidedef: &'a Sidedef,          // The sidedef
     offset_x: i16,                 // Distance along linedef to start of seg
     floor_height: i16,             // Height of the floor
     ceiling_height: i16,           // Height of the ceiling
     floor_flat: &'a Rc<Flat>,      // Floor texture
     ceiling_flat: &'a Rc<Flat>,    // Ceiling texture
     light_level: i16,              // Sector light level
+    wall_light_level: i16,         // Light level of the wall, with fake contrast
 }
 
 struct Flags {
@@ -82,16 +84,18 @@ impl Segs<'_> {
         textures: &'a mut Textures,
         flats: &'a mut Flats,
         palette: &'a Palette,
+        lighting: &'a Lighting,
         player: &'a Player,
-        timestamp: f32,
+        tic: u32,
     ) -> Segs<'a> {
         Segs {
             pixels,
             palette,
+            lighting,
             player,
             textures,
             flats,
-            timestamp,
+            tic,
             segs: Vec::new(),
             visplanes: Vec::new(),
             hor_ocl: [false; SCREEN_WIDTH as usize],
@@ -127,15 +131,29 @@ impl Segs<'_> {
         texture_name: &str,   // Optional texture
         flags: Flags,         // Specific details
     ) {
-        let bottom = make_sidedef_non_vertical_line(&sds.clipped_line.line, bottom_height);
-        let top = make_sidedef_non_vertical_line(&sds.clipped_line.line, top_height);
+        let focus_y = camera_focus_y(self.player);
+        let bottom = make_sidedef_non_vertical_line(&sds.clipped_line.line, bottom_height, focus_y);
+        let top = make_sidedef_non_vertical_line(&sds.clipped_line.line, top_height, focus_y);
 
+        // Unknown textures aren't drawn
         let texture = if texture_name != "-" {
-            Some(self.textures.get(texture_name))
+            self.textures.get_animated(texture_name, self.tic).ok()
         } else {
             None
         };
 
+        // Scrolling walls keep changing the sidedef offsets, wrap them by the size of
+        // the texture
+        let (x_offset, y_offset) = match &texture {
+            Some(texture) => (
+                sds.sidedef.x_offset.rem_euclid(texture.bitmap.width as f32) as i16,
+                sds.sidedef
+                    .y_offset
+                    .rem_euclid(texture.bitmap.height as f32) as i16,
+            ),
+            None => (0, 0),
+        };
+
         // Do some sanity checks
         if bottom.start.x != top.start.x || bottom.end.x != top.end.x {
             panic!(
@@ -185,14 +203,14 @@ impl Segs<'_> {
         let mut bitmap_render = BitmapRender::new(
             bitmap_render_state,
             bitmap,
-            sds.light_level,
+            sds.wall_light_level,
             sds.clipped_line.clone(),
             bottom.start.x,
             bottom.end.x,
             bottom_height,
             top_height,
-            sds.sidedef.x_offset as i16 + sds.offset_x,
-            sds.sidedef.y_offset as i16 + offset_y as i16,
+            x_offset + sds.offset_x,
+            y_offset + offset_y as i16,
             flags.is_lower_wall || (!flags.is_two_sided_middle_wall && is_full_height_wall),
             flags.is_upper_wall || (!flags.is_two_sided_middle_wall && is_full_height_wall),
             flags.draw_ceiling,
@@ -235,15 +253,16 @@ impl Segs<'_> {
                                 // Wall/portal details
                                 self.pixels,
                                 self.palette,
+                                self.lighting,
                                 &texture.bitmap,
-                                sds.light_level,
+                                sds.wall_light_level,
                                 sds.clipped_line,
                                 bottom.start.x,
                                 bottom.end.x,
                                 bottom_height,
                                 top_height,
-                                sds.sidedef.x_offset as i16 + sds.offset_x,
-                                sds.sidedef.y_offset as i16 + offset_y as i16,
+                                x_offset + sds.offset_x,
+                                y_offset + offset_y as i16,
                                 // Column details
                                 x.into(),
                                 clipped_bottom_y.into(),
@@ -362,8 +381,8 @@ impl Segs<'_> {
         };
 
         // Get the front sector (the one we're facing)
-        let front_sidedef = match opt_front_sidedef {
-            Some(s) => s,
+        let front_sidedef = &match opt_front_sidedef {
+            Some(s) => s.borrow(),
             None => {
                 // If there is no sidedef, then there is no wall
                 return;
@@ -380,7 +399,7 @@ impl Segs<'_> {
         // sector.
         let (opt_portal_bottom_height, mut opt_portal_top_height) = match opt_back_sidedef {
             Some(back_sidedef) => {
-                let back_sector = &back_sidedef.sector;
+                let back_sector = &back_sidedef.borrow().sector;
 
                 let opt_portal_bottom_height =
                     if back_sector.borrow().floor_height > front_sector.floor_height {
@@ -436,23 +455,33 @@ impl Segs<'_> {
         }
 
         // Draw the non-vertial lines for all parts of the wall
-        let player_height = self.player.floor_height + PLAYER_EYE_HEIGHT;
+        let player_height = self.player.view_z;
 
         // Check one line to ensure we're not facing the back of it
-        let floor =
-            make_sidedef_non_vertical_line(&clipped_line.line, floor_height - player_height);
+        let floor = make_sidedef_non_vertical_line(
+            &clipped_line.line,
+            floor_height - player_height,
+            camera_focus_y(self.player),
+        );
 
         // We are facing the non-rendered side of the segment.
         if floor.start.x > floor.end.x {
             return;
         }
 
-        let floor_flat = self
+        // Segs in sectors with unknown flats aren't drawn
+        let Ok(floor_flat) = self
             .flats
-            .get_animated(front_sector.floor_texture.as_str(), self.timestamp);
-        let ceiling_flat = self
+            .get_animated(front_sector.floor_texture.as_str(), self.tic)
+        else {
+            return;
+        };
+        let Ok(ceiling_flat) = self
             .flats
-            .get_animated(front_sector.ceiling_texture.as_str(), self.timestamp);
+            .get_animated(front_sector.ceiling_texture.as_str(), self.tic)
+        else {
+            return;
+        };
 
         let mut draw_ceiling = true;
 
@@ -461,6 +490,7 @@ impl Segs<'_> {
         // https://doomwiki.org/wiki/Sky_hack
         // This follows the gory details in r_segs.c
         if let Some(back_sidedef) = opt_back_sidedef {
+            let back_sidedef = back_sidedef.borrow();
             if front_sidedef
                 .sector
                 .borrow()
@@ -485,6 +515,11 @@ impl Segs<'_> {
             floor_flat: &floor_flat,
             ceiling_flat: &ceiling_flat,
             light_level: front_sector.light_level,
+            wall_light_level: self.lighting.wall_light_level(
+                front_sector.light_level,
+                &seg.start_vertex,
+                &seg.end_vertex,
+            ),
         };
 
         // All the transformations are done and the wall/portal is facing us.
@@ -592,7 +627,7 @@ impl Segs<'_> {
     // Draw remaining two sided segs
     pub fn draw_remaining_segs(&mut self) {
         for seg in &mut self.segs {
-            seg.render(self.pixels, self.palette);
+            seg.render(self.pixels, self.palette, self.lighting);
         }
     }
 }
@@ -2,10 +2,11 @@ use sdl2::pixels::Color;
 use std::f32::consts::PI;
 use std::rc::Rc;
 
-use super::bitmap_render::diminish_color;
 use super::constants::{
-    ASPECT_RATIO_CORRECTION, CAMERA_FOCUS_X, CAMERA_FOCUS_Y, GAME_CAMERA_FOCUS_X, PLAYER_EYE_HEIGHT,
+    ASPECT_RATIO_CORRECTION, CAMERA_FOCUS_X, CAMERA_FOCUS_Y, GAME_CAMERA_FOCUS_X,
 };
+use super::lighting::Lighting;
+use super::misc::camera_focus_y;
 use super::pixels::Pixels;
 use crate::game::{Player, SCREEN_HEIGHT, SCREEN_WIDTH};
 use crate::graphics::{Flat, Palette, Texture, FLAT_SIZE};
@@ -57,6 +58,9 @@ fn draw_sky(
         tx_offset += SKY_TEXTURE_WIDTH * (1 - tx_offset / SKY_TEXTURE_WIDTH);
     }
 
+    // The bottom of the sky texture is on the horizon, which moves down when looking up
+    let shear = camera_focus_y(player) - CAMERA_FOCUS_Y;
+
     for x in visplane.left..visplane.right + 1 {
         let top = visplane.top[x as usize].max(0);
         let bottom = visplane.bottom[x as usize].min(SCREEN_HEIGHT as i16 - 1);
@@ -65,11 +69,12 @@ fn draw_sky(
             let mut tx = (x as f32 * SKY_TEXTURE_WIDTH as f32 / SCREEN_WIDTH as f32) as i16;
             tx = (tx + tx_offset) % SKY_TEXTURE_WIDTH;
 
-            let mut ty = (y as f32 * SKY_TEXTURE_HEIGHT as f32 * 2.0 / SCREEN_HEIGHT as f32) as i16;
-            if ty < 0 {
-                ty += SKY_TEXTURE_HEIGHT;
-            }
-            ty %= SKY_TEXTURE_HEIGHT;
+            // Above the top of the texture, the top row is stretched so that there
+            // are no gaps when looking up
+            let sky_y = y as f32 - shear;
+            let ty = (sky_y * SKY_TEXTURE_HEIGHT as f32 * 2.0 / SCREEN_HEIGHT as f32).max(0.0)
+                as i16
+                % SKY_TEXTURE_HEIGHT;
 
             if let Some(color_value) = sky_texture.bitmap.pixels[ty as usize][tx as usize] {
                 let color = palette.colors[color_value as usize];
@@ -82,6 +87,7 @@ fn draw_sky(
 pub fn draw_visplane(
     pixels: &mut Pixels,
     palette: &Palette,
+    lighting: &Lighting,
     player: &Player,
     sky_texture: Rc<Texture>,
     visplane: &Visplane,
@@ -91,6 +97,8 @@ pub fn draw_visplane(
         return;
     }
 
+    let focus_y = camera_focus_y(player);
+
     for x in visplane.left..visplane.right + 1 {
         let top = visplane.top[x as usize].max(0);
         let bottom = visplane.bottom[x as usize].min(SCREEN_HEIGHT as i16 - 1);
@@ -106,24 +114,24 @@ pub fn draw_visplane(
 
             // Transform to viewport coordinates (v prefix) (the reverse of make_sidedef_non_vertical_line)
             let vx = (CAMERA_FOCUS_X - x as f32) / ASPECT_RATIO_CORRECTION;
-            let vy = CAMERA_FOCUS_Y - y as f32;
+            let vy = focus_y - y as f32;
 
             // Inverse perspective transform to world coordinates (w prefix)
-            let wz = visplane.height as f32 - player.floor_height - PLAYER_EYE_HEIGHT;
+            let wz = visplane.height as f32 - player.view_z;
             let wx = GAME_CAMERA_FOCUS_X * wz / vy;
             let wy = wz * vx / vy;
 
             // Translate and rotate to player view
             let rotated = Vertex::new(wx, wy).rotate(player.angle);
 
-            let mut tx: i16 = rotated.x as i16 + player.position.x as i16;
-            let mut ty: i16 = rotated.y as i16 + player.position.y as i16;
-
-            tx &= FLAT_SIZE - 1;
-            ty &= FLAT_SIZE - 1;
+            // Use i32, the fly camera can see planes from far above or below
+            let mask = FLAT_SIZE as i32 - 1;
+            let tx = (rotated.x as i32).wrapping_add(player.position.x as i32) & mask;
+            let ty = (rotated.y as i32).wrapping_add(player.position.y as i32) & mask;
 
-            let color = palette.colors[visplane.flat.pixels[ty as usize][tx as usize] as usize];
-            let diminished_color = diminish_color(&color, visplane.light_level, wx as i16);
+            let color_value = visplane.flat.pixels[ty as usize][tx as usize];
+            let diminished_color =
+                lighting.plane_color(palette, color_value, visplane.light_level, wx);
 
             pixels.set(x as usize, y as usize, &diminished_color);
         }
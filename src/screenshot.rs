@@ -0,0 +1,156 @@
+use clap::ValueEnum;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::game::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::renderer::Pixels;
+
+// Highest number in an auto-incremented screenshot file name
+const MAX_SCREENSHOT_NUMBER: u32 = 9999;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScreenshotFormat {
+    Png, // Compressed
+    Ppm, // Binary PPM, uncompressed RGB
+}
+
+impl ScreenshotFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            ScreenshotFormat::Png => "png",
+            ScreenshotFormat::Ppm => "ppm",
+        }
+    }
+}
+
+// Write a frame buffer to a file
+pub fn write_screenshot(
+    pixels: &Pixels,
+    path: &Path,
+    format: ScreenshotFormat,
+) -> Result<(), io::Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    match format {
+        ScreenshotFormat::Png => {
+            let mut encoder = png::Encoder::new(&mut writer, SCREEN_WIDTH, SCREEN_HEIGHT);
+            encoder.set_color(png::ColorType::Rgb);
+            encoder.set_depth(png::BitDepth::Eight);
+            encoder.write_header()?.write_image_data(&pixels.pixels)?;
+        }
+        ScreenshotFormat::Ppm => {
+            write!(writer, "P6\n{} {}\n255\n", SCREEN_WIDTH, SCREEN_HEIGHT)?;
+            writer.write_all(&pixels.pixels)?;
+        }
+    }
+
+    writer.flush()
+}
+
+// Find the first unused file name like doom0000.png, doom0001.png, etc in a directory
+pub fn next_screenshot_path(
+    directory: &Path,
+    prefix: &str,
+    format: ScreenshotFormat,
+) -> Result<PathBuf, io::Error> {
+    for i in 0..=MAX_SCREENSHOT_NUMBER {
+        let path = directory.join(format!("{}{:04}.{}", prefix, i, format.extension()));
+        if !path.exists() {
+            return Ok(path);
+        }
+    }
+
+    Err(io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("Too many {} screenshots in {}", prefix, directory.display()),
+    ))
+}
+
+// Write a frame buffer to the next unused file name. Returns the file name.
+pub fn save_screenshot(
+    pixels: &Pixels,
+    directory: &Path,
+    prefix: &str,
+    format: ScreenshotFormat,
+) -> Result<PathBuf, io::Error> {
+    let path = next_screenshot_path(directory, prefix, format)?;
+    write_screenshot(pixels, &path, format)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sdl2::pixels::Color;
+    use std::fs;
+
+    // Make an empty directory for the test
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!(
+            "doom-rust-renderer-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+
+        directory
+    }
+
+    #[test]
+    fn test_auto_increment() {
+        let directory = test_directory("auto-increment");
+        let pixels = Pixels::new();
+
+        let path1 = save_screenshot(&pixels, &directory, "doom", ScreenshotFormat::Ppm).unwrap();
+        let path2 = save_screenshot(&pixels, &directory, "doom", ScreenshotFormat::Ppm).unwrap();
+        let path3 = save_screenshot(&pixels, &directory, "map", ScreenshotFormat::Png).unwrap();
+
+        assert_eq!(path1, directory.join("doom0000.ppm"));
+        assert_eq!(path2, directory.join("doom0001.ppm"));
+        assert_eq!(path3, directory.join("map0000.png"));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_write_ppm() {
+        let directory = test_directory("ppm");
+        let mut pixels = Pixels::new();
+        pixels.set(1, 0, &Color::RGB(1, 2, 3));
+
+        let path = directory.join("frame.ppm");
+        write_screenshot(&pixels, &path, ScreenshotFormat::Ppm).unwrap();
+
+        let data = fs::read(&path).unwrap();
+        let header = format!("P6\n{} {}\n255\n", SCREEN_WIDTH, SCREEN_HEIGHT);
+        assert_eq!(&data[..header.len()], header.as_bytes());
+        assert_eq!(&data[header.len()..], &pixels.pixels[..]);
+        assert_eq!(&data[header.len() + 3..header.len() + 6], &[1, 2, 3]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_write_png() {
+        let directory = test_directory("png");
+        let mut pixels = Pixels::new();
+        pixels.set(0, 1, &Color::RGB(4, 5, 6));
+
+        let path = directory.join("frame.png");
+        write_screenshot(&pixels, &path, ScreenshotFormat::Png).unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut data).unwrap();
+
+        assert_eq!((info.width, info.height), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(&data[..], &pixels.pixels[..]);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,93 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::map::Sidedef;
+use crate::thinkers::Thinker;
+
+// A wall texture that moves a number of units per tic. The offsets keep growing,
+// the renderer wraps them by the size of the texture. See T_Scroll in Boom's
+// p_spec.c.
+#[derive(Debug)]
+pub struct ScrollingWall {
+    sidedef: Rc<RefCell<Sidedef>>, // The sidedef to change the offsets of
+    dx: f32,                       // Horizontal units per tic
+    dy: f32,                       // Vertical units per tic
+}
+
+impl ScrollingWall {
+    pub fn new(sidedef: Rc<RefCell<Sidedef>>, dx: f32, dy: f32) -> ScrollingWall {
+        ScrollingWall { sidedef, dx, dy }
+    }
+
+    // Scroll by the offsets of the sidedef. The x offset is reversed, so that the
+    // texture moves in the direction of the offset.
+    pub fn from_offsets(sidedef: Rc<RefCell<Sidedef>>) -> ScrollingWall {
+        let (dx, dy) = {
+            let sidedef = sidedef.borrow();
+            (-sidedef.x_offset, sidedef.y_offset)
+        };
+
+        ScrollingWall::new(sidedef, dx, dy)
+    }
+}
+
+impl Thinker for ScrollingWall {
+    fn mutate(&mut self) {
+        let mut sidedef = self.sidedef.borrow_mut();
+        sidedef.x_offset += self.dx;
+        sidedef.y_offset += self.dy;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::Map;
+    use crate::map_objects::MapObjects;
+    use crate::test_maps::{square_room, TEST_MAP_NAME};
+    use crate::thinkers::init_thinkers;
+
+    // Make the square room with a special on the west wall and run the thinkers
+    fn scroll(special_type: i16, offsets: (i16, i16), tics: usize) -> (f32, f32) {
+        let mut builder = square_room();
+        builder.linedef_mut(0).special_type = special_type;
+        let sidedef = builder.linedef_mut(0).front_sidedef;
+        builder.sidedef_mut(sidedef).x_offset = offsets.0;
+        builder.sidedef_mut(sidedef).y_offset = offsets.1;
+
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let map_objects = MapObjects::new(&map).unwrap();
+        let mut thinkers = Vec::new();
+        init_thinkers(&mut thinkers, &map, &map_objects);
+
+        for _ in 0..tics {
+            for thinker in &mut thinkers {
+                thinker.mutate();
+            }
+        }
+
+        let sidedef = map.linedefs[0].front_sidedef.as_ref().unwrap().borrow();
+        (sidedef.x_offset, sidedef.y_offset)
+    }
+
+    #[test]
+    fn test_scroll_left() {
+        assert_eq!(scroll(48, (0, 0), 3), (3.0, 0.0));
+        assert_eq!(scroll(48, (1022, 5), 3), (1025.0, 5.0));
+    }
+
+    #[test]
+    fn test_scroll_right() {
+        assert_eq!(scroll(85, (2, 0), 3), (-1.0, 0.0));
+    }
+
+    #[test]
+    fn test_scroll_by_offsets() {
+        assert_eq!(scroll(255, (-8, 4), 1), (0.0, 8.0));
+        assert_eq!(scroll(255, (-8, 4), 2), (8.0, 12.0));
+    }
+
+    #[test]
+    fn test_no_special() {
+        assert_eq!(scroll(0, (8, 4), 10), (8.0, 4.0));
+    }
+}
@@ -0,0 +1,683 @@
+use crate::ceilings::{do_ceiling, stop_crushers, CeilingType};
+use crate::doors::{do_door, use_door, DoorType};
+use crate::floors::{do_floor, FloorType};
+use crate::geometry::Line;
+use crate::lights::{light_turn_on, start_light_strobing, turn_tag_lights_off};
+use crate::map::{Linedef, Map, Vertex};
+use crate::movement::line_opening;
+use crate::platforms::{do_platform, stop_platforms, PlatformType};
+use crate::switches::{change_switch_texture, Switches};
+use crate::teleports::find_teleport_destination;
+use crate::thinkers::Thinker;
+
+// Linedef specials are activated by walking over the linedef or by using it. The
+// player uses linedefs to open doors and flip switches. A use reaches the first
+// linedef with a special within range, as long as there is an opening between the
+// player and the linedef. See P_UseLines in p_map.c.
+// https://doomwiki.org/wiki/Linedef_type
+
+pub const USE_RANGE: f32 = 64.0; // How far the player can reach to use a linedef
+
+// How a linedef special is activated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Walk,   // Crossing the linedef
+    Switch, // Using the front side of the linedef
+    Manual, // Using a door linedef, which moves the sector on its back side
+}
+
+// The doom wiki's names for the triggers, with whether the special can be activated
+// again
+const W1: (Trigger, bool) = (Trigger::Walk, false);
+const WR: (Trigger, bool) = (Trigger::Walk, true);
+const S1: (Trigger, bool) = (Trigger::Switch, false);
+const SR: (Trigger, bool) = (Trigger::Switch, true);
+const D1: (Trigger, bool) = (Trigger::Manual, false);
+const DR: (Trigger, bool) = (Trigger::Manual, true);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelExit {
+    Normal,
+    Secret,
+}
+
+// What a linedef special does. Everything but manual doors acts on the sectors with
+// the linedef's tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinedefAction {
+    ManualDoor(DoorType),
+    Door(DoorType),
+    Floor(FloorType),
+    Platform(PlatformType),
+    StopPlatforms,
+    Ceiling(CeilingType),
+    CeilingAndFloor(CeilingType, FloorType), // Both planes move at the same time
+    StopCrushers,
+    LightTurnOn(Option<i16>), // A light level, or that of the brightest neighbor
+    LightsOff,                // The light level of the darkest neighbor
+    StartStrobing,
+    Teleport, // To the teleport destination in a tagged sector
+    Exit(LevelExit),
+}
+
+// The specials of doom. Specials that are activated by shooting them, that change
+// textures or that build stairs are missing. Locked doors don't check keys, there is
+// no inventory. See P_CrossSpecialLine in p_spec.c and P_UseSpecialLine in
+// p_switch.c.
+const LINEDEF_SPECIALS: &[(i16, (Trigger, bool), LinedefAction)] = &[
+    // Manual doors
+    (1, DR, LinedefAction::ManualDoor(DoorType::Normal)),
+    (26, DR, LinedefAction::ManualDoor(DoorType::Normal)), // Blue key
+    (27, DR, LinedefAction::ManualDoor(DoorType::Normal)), // Yellow key
+    (28, DR, LinedefAction::ManualDoor(DoorType::Normal)), // Red key
+    (31, D1, LinedefAction::ManualDoor(DoorType::Open)),
+    (32, D1, LinedefAction::ManualDoor(DoorType::Open)), // Blue key
+    (33, D1, LinedefAction::ManualDoor(DoorType::Open)), // Red key
+    (34, D1, LinedefAction::ManualDoor(DoorType::Open)), // Yellow key
+    (117, DR, LinedefAction::ManualDoor(DoorType::BlazeRaise)),
+    (118, D1, LinedefAction::ManualDoor(DoorType::BlazeOpen)),
+    // Doors
+    (2, W1, LinedefAction::Door(DoorType::Open)),
+    (3, W1, LinedefAction::Door(DoorType::Close)),
+    (4, W1, LinedefAction::Door(DoorType::Normal)),
+    (16, W1, LinedefAction::Door(DoorType::Close30ThenOpen)),
+    (108, W1, LinedefAction::Door(DoorType::BlazeRaise)),
+    (109, W1, LinedefAction::Door(DoorType::BlazeOpen)),
+    (110, W1, LinedefAction::Door(DoorType::BlazeClose)),
+    (75, WR, LinedefAction::Door(DoorType::Close)),
+    (76, WR, LinedefAction::Door(DoorType::Close30ThenOpen)),
+    (86, WR, LinedefAction::Door(DoorType::Open)),
+    (90, WR, LinedefAction::Door(DoorType::Normal)),
+    (105, WR, LinedefAction::Door(DoorType::BlazeRaise)),
+    (106, WR, LinedefAction::Door(DoorType::BlazeOpen)),
+    (107, WR, LinedefAction::Door(DoorType::BlazeClose)),
+    (29, S1, LinedefAction::Door(DoorType::Normal)),
+    (50, S1, LinedefAction::Door(DoorType::Close)),
+    (103, S1, LinedefAction::Door(DoorType::Open)),
+    (111, S1, LinedefAction::Door(DoorType::BlazeRaise)),
+    (112, S1, LinedefAction::Door(DoorType::BlazeOpen)),
+    (113, S1, LinedefAction::Door(DoorType::BlazeClose)),
+    (133, S1, LinedefAction::Door(DoorType::BlazeOpen)), // Blue key
+    (135, S1, LinedefAction::Door(DoorType::BlazeOpen)), // Red key
+    (137, S1, LinedefAction::Door(DoorType::BlazeOpen)), // Yellow key
+    (42, SR, LinedefAction::Door(DoorType::Close)),
+    (61, SR, LinedefAction::Door(DoorType::Open)),
+    (63, SR, LinedefAction::Door(DoorType::Normal)),
+    (114, SR, LinedefAction::Door(DoorType::BlazeRaise)),
+    (115, SR, LinedefAction::Door(DoorType::BlazeOpen)),
+    (116, SR, LinedefAction::Door(DoorType::BlazeClose)),
+    (99, SR, LinedefAction::Door(DoorType::BlazeOpen)), // Blue key
+    (134, SR, LinedefAction::Door(DoorType::BlazeOpen)), // Red key
+    (136, SR, LinedefAction::Door(DoorType::BlazeOpen)), // Yellow key
+    // Floors
+    (5, W1, LinedefAction::Floor(FloorType::Raise)),
+    (19, W1, LinedefAction::Floor(FloorType::Lower)),
+    (36, W1, LinedefAction::Floor(FloorType::TurboLower)),
+    (38, W1, LinedefAction::Floor(FloorType::LowerToLowest)),
+    (56, W1, LinedefAction::Floor(FloorType::RaiseCrush)),
+    (58, W1, LinedefAction::Floor(FloorType::Raise24)),
+    (119, W1, LinedefAction::Floor(FloorType::RaiseToNearest)),
+    (130, W1, LinedefAction::Floor(FloorType::RaiseTurbo)),
+    (82, WR, LinedefAction::Floor(FloorType::LowerToLowest)),
+    (83, WR, LinedefAction::Floor(FloorType::Lower)),
+    (91, WR, LinedefAction::Floor(FloorType::Raise)),
+    (92, WR, LinedefAction::Floor(FloorType::Raise24)),
+    (94, WR, LinedefAction::Floor(FloorType::RaiseCrush)),
+    (98, WR, LinedefAction::Floor(FloorType::TurboLower)),
+    (128, WR, LinedefAction::Floor(FloorType::RaiseToNearest)),
+    (129, WR, LinedefAction::Floor(FloorType::RaiseTurbo)),
+    (18, S1, LinedefAction::Floor(FloorType::RaiseToNearest)),
+    (23, S1, LinedefAction::Floor(FloorType::LowerToLowest)),
+    (55, S1, LinedefAction::Floor(FloorType::RaiseCrush)),
+    (71, S1, LinedefAction::Floor(FloorType::TurboLower)),
+    (101, S1, LinedefAction::Floor(FloorType::Raise)),
+    (102, S1, LinedefAction::Floor(FloorType::Lower)),
+    (131, S1, LinedefAction::Floor(FloorType::RaiseTurbo)),
+    (140, S1, LinedefAction::Floor(FloorType::Raise512)),
+    (45, SR, LinedefAction::Floor(FloorType::Lower)),
+    (60, SR, LinedefAction::Floor(FloorType::LowerToLowest)),
+    (64, SR, LinedefAction::Floor(FloorType::Raise)),
+    (65, SR, LinedefAction::Floor(FloorType::RaiseCrush)),
+    (69, SR, LinedefAction::Floor(FloorType::RaiseToNearest)),
+    (70, SR, LinedefAction::Floor(FloorType::TurboLower)),
+    (132, SR, LinedefAction::Floor(FloorType::RaiseTurbo)),
+    // Lifts
+    (
+        10,
+        W1,
+        LinedefAction::Platform(PlatformType::DownWaitUpStay),
+    ),
+    (
+        53,
+        W1,
+        LinedefAction::Platform(PlatformType::PerpetualRaise),
+    ),
+    (54, W1, LinedefAction::StopPlatforms),
+    (
+        121,
+        W1,
+        LinedefAction::Platform(PlatformType::BlazeDownWaitUpStay),
+    ),
+    (
+        87,
+        WR,
+        LinedefAction::Platform(PlatformType::PerpetualRaise),
+    ),
+    (
+        88,
+        WR,
+        LinedefAction::Platform(PlatformType::DownWaitUpStay),
+    ),
+    (89, WR, LinedefAction::StopPlatforms),
+    (
+        120,
+        WR,
+        LinedefAction::Platform(PlatformType::BlazeDownWaitUpStay),
+    ),
+    (
+        21,
+        S1,
+        LinedefAction::Platform(PlatformType::DownWaitUpStay),
+    ),
+    (
+        122,
+        S1,
+        LinedefAction::Platform(PlatformType::BlazeDownWaitUpStay),
+    ),
+    (
+        62,
+        SR,
+        LinedefAction::Platform(PlatformType::DownWaitUpStay),
+    ),
+    (
+        123,
+        SR,
+        LinedefAction::Platform(PlatformType::BlazeDownWaitUpStay),
+    ),
+    // Ceilings and crushers
+    (
+        6,
+        W1,
+        LinedefAction::Ceiling(CeilingType::FastCrushAndRaise),
+    ),
+    (25, W1, LinedefAction::Ceiling(CeilingType::CrushAndRaise)),
+    (
+        40,
+        W1,
+        LinedefAction::CeilingAndFloor(CeilingType::RaiseToHighest, FloorType::LowerToLowest),
+    ),
+    (44, W1, LinedefAction::Ceiling(CeilingType::LowerAndCrush)),
+    (57, W1, LinedefAction::StopCrushers),
+    (
+        141,
+        W1,
+        LinedefAction::Ceiling(CeilingType::SilentCrushAndRaise),
+    ),
+    (72, WR, LinedefAction::Ceiling(CeilingType::LowerAndCrush)),
+    (73, WR, LinedefAction::Ceiling(CeilingType::CrushAndRaise)),
+    (74, WR, LinedefAction::StopCrushers),
+    (
+        77,
+        WR,
+        LinedefAction::Ceiling(CeilingType::FastCrushAndRaise),
+    ),
+    (41, S1, LinedefAction::Ceiling(CeilingType::LowerToFloor)),
+    (49, S1, LinedefAction::Ceiling(CeilingType::CrushAndRaise)),
+    (43, SR, LinedefAction::Ceiling(CeilingType::LowerToFloor)),
+    // Lights
+    (12, W1, LinedefAction::LightTurnOn(None)),
+    (13, W1, LinedefAction::LightTurnOn(Some(255))),
+    (17, W1, LinedefAction::StartStrobing),
+    (35, W1, LinedefAction::LightTurnOn(Some(35))),
+    (104, W1, LinedefAction::LightsOff),
+    (79, WR, LinedefAction::LightTurnOn(Some(35))),
+    (80, WR, LinedefAction::LightTurnOn(None)),
+    (81, WR, LinedefAction::LightTurnOn(Some(255))),
+    (138, SR, LinedefAction::LightTurnOn(Some(255))),
+    (139, SR, LinedefAction::LightTurnOn(Some(35))),
+    // Teleporters
+    (39, W1, LinedefAction::Teleport),
+    (97, WR, LinedefAction::Teleport),
+    // Exits
+    (52, W1, LinedefAction::Exit(LevelExit::Normal)),
+    (124, W1, LinedefAction::Exit(LevelExit::Secret)),
+    (11, S1, LinedefAction::Exit(LevelExit::Normal)),
+    (51, S1, LinedefAction::Exit(LevelExit::Secret)),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinedefSpecial {
+    pub trigger: Trigger,
+    pub repeatable: bool, // Can be activated more than once
+    pub action: LinedefAction,
+}
+
+// Look up a linedef special type in the table
+pub fn linedef_special(special_type: i16) -> Option<LinedefSpecial> {
+    LINEDEF_SPECIALS
+        .iter()
+        .find(|(t, ..)| *t == special_type)
+        .map(|&(_, (trigger, repeatable), action)| LinedefSpecial {
+            trigger,
+            repeatable,
+            action,
+        })
+}
+
+// What happened when a linedef special was activated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activation {
+    Nothing,         // There was no special, or its sectors were already moving
+    Done,            // The special did something
+    Teleport(usize), // The player teleports to the thing with this index
+    Exit(LevelExit), // The player exits the level
+}
+
+// Do the action of a linedef special. Teleporters only work from the front side.
+fn do_linedef_action(
+    map: &Map,
+    linedef: &Linedef,
+    is_back_side: bool,
+    action: LinedefAction,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+) -> Activation {
+    let done = match action {
+        LinedefAction::ManualDoor(door_type) => use_door(map, linedef, door_type, thinkers),
+        LinedefAction::Door(door_type) => do_door(map, linedef, door_type, thinkers),
+        LinedefAction::Floor(floor_type) => do_floor(map, linedef, floor_type, thinkers),
+        LinedefAction::Platform(platform_type) => {
+            do_platform(map, linedef, platform_type, thinkers)
+        }
+        LinedefAction::StopPlatforms => {
+            stop_platforms(linedef, thinkers);
+            true
+        }
+        LinedefAction::Ceiling(ceiling_type) => do_ceiling(map, linedef, ceiling_type, thinkers),
+        LinedefAction::CeilingAndFloor(ceiling_type, floor_type) => {
+            // The floors only wait for thinkers that were already moving the
+            // sectors, not for the ceilings started here
+            let first_started = thinkers.len();
+            let ceiling_done = do_ceiling(map, linedef, ceiling_type, thinkers);
+            let ceilings = thinkers.split_off(first_started);
+            let floor_done = do_floor(map, linedef, floor_type, thinkers);
+            thinkers.extend(ceilings);
+            ceiling_done || floor_done
+        }
+        LinedefAction::StopCrushers => stop_crushers(linedef, thinkers),
+        LinedefAction::LightTurnOn(light_level) => {
+            light_turn_on(map, linedef, light_level);
+            true
+        }
+        LinedefAction::LightsOff => {
+            turn_tag_lights_off(map, linedef);
+            true
+        }
+        LinedefAction::StartStrobing => {
+            start_light_strobing(map, linedef, thinkers);
+            true
+        }
+        LinedefAction::Teleport if is_back_side => false,
+        LinedefAction::Teleport => {
+            return match find_teleport_destination(map, linedef) {
+                Some(thing) => Activation::Teleport(thing),
+                None => Activation::Nothing,
+            }
+        }
+        LinedefAction::Exit(level_exit) => return Activation::Exit(level_exit),
+    };
+
+    if done {
+        Activation::Done
+    } else {
+        Activation::Nothing
+    }
+}
+
+// Use the first linedef with a special in front of the player. Lines without a special
+// are passed if there is a gap between the floor and ceiling behind them.
+// See PTR_UseTraverse in p_map.c.
+pub fn use_lines(
+    map: &Map,
+    position: &Vertex,
+    angle: f32,
+    switches: &Switches,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+) -> Activation {
+    let end = position + &Vertex::new(USE_RANGE, 0.0).rotate(angle);
+
+    for intercept in map.trace_line(position, &end) {
+        let linedef = &intercept.linedef;
+
+        if linedef.special_type.get() == 0 {
+            match line_opening(linedef) {
+                Some((bottom, top)) if top > bottom => continue,
+                _ => return Activation::Nothing, // Can't use through a wall
+            }
+        }
+
+        let line = Line::new(&linedef.start_vertex, &linedef.end_vertex);
+        let is_back_side = position.is_left_of_line(&line);
+
+        // Only one special line can be used at a time
+        return use_special_line(map, linedef, is_back_side, switches, thinkers);
+    }
+
+    Activation::Nothing
+}
+
+// Do the special of a used linedef. Only the front side can be used. One-time
+// switches and doors clear the special once they did something. A switch that did
+// something flips its texture.
+// See P_UseSpecialLine in p_switch.c.
+pub fn use_special_line(
+    map: &Map,
+    linedef: &Linedef,
+    is_back_side: bool,
+    switches: &Switches,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+) -> Activation {
+    if is_back_side {
+        return Activation::Nothing;
+    }
+
+    let Some(special) = linedef_special(linedef.special_type.get()) else {
+        return Activation::Nothing;
+    };
+    if special.trigger == Trigger::Walk {
+        return Activation::Nothing;
+    }
+
+    let activation = do_linedef_action(map, linedef, is_back_side, special.action, thinkers);
+    if activation == Activation::Nothing {
+        return activation;
+    }
+
+    if !special.repeatable {
+        linedef.special_type.set(0);
+    }
+    if special.trigger == Trigger::Switch {
+        change_switch_texture(switches, linedef, special.repeatable, thinkers);
+    }
+
+    activation
+}
+
+// Do the special of a linedef the player walked over, coming from the front or back
+// side. One-time specials are cleared, even if they didn't do anything.
+// See P_CrossSpecialLine in p_spec.c.
+pub fn cross_special_line(
+    map: &Map,
+    linedef: &Linedef,
+    is_back_side: bool,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+) -> Activation {
+    let Some(special) = linedef_special(linedef.special_type.get()) else {
+        return Activation::Nothing;
+    };
+    if special.trigger != Trigger::Walk {
+        return Activation::Nothing;
+    }
+
+    let activation = do_linedef_action(map, linedef, is_back_side, special.action, thinkers);
+    if !special.repeatable {
+        linedef.special_type.set(0);
+    }
+
+    activation
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_maps::{door_rooms, run_thinkers, two_rooms, TEST_MAP_NAME};
+    use std::f32::consts::PI;
+
+    fn door_height(map: &Map) -> i16 {
+        map.sectors[1].borrow().ceiling_height
+    }
+
+    #[test]
+    fn test_use_door() {
+        let mut builder = door_rooms();
+        builder.linedef_mut(3).special_type = 1;
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let switches = Switches::default();
+        let mut thinkers = Vec::new();
+
+        // Out of range
+        let far = Vertex::new(60.0, 64.0);
+        let activation = use_lines(&map, &far, 0.0, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Nothing);
+
+        // Facing away from the door
+        let near = Vertex::new(100.0, 64.0);
+        let activation = use_lines(&map, &near, 3.0, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Nothing);
+
+        // In range and facing the door
+        let activation = use_lines(&map, &near, 0.3, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Done);
+        assert_eq!(thinkers.len(), 1);
+
+        // The back side can't be used
+        let linedef = &map.linedefs[3];
+        let mut thinkers = Vec::new();
+        let activation = use_special_line(&map, linedef, true, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Nothing);
+        assert!(thinkers.is_empty());
+    }
+
+    #[test]
+    fn test_one_time_door() {
+        let mut builder = door_rooms();
+        builder.linedef_mut(3).special_type = 118;
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let linedef = &map.linedefs[3];
+        let switches = Switches::default();
+        let mut thinkers = Vec::new();
+
+        let activation = use_special_line(&map, linedef, false, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Done);
+        assert_eq!(linedef.special_type.get(), 0);
+        let activation = use_special_line(&map, linedef, false, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Nothing);
+    }
+
+    #[test]
+    fn test_use_through_openings() {
+        // Flip the east side of the door, so that its front faces the west room, and
+        // make it a switch that closes the door
+        let mut builder = door_rooms();
+        builder.sector_mut(1).tag_number = 3;
+        let linedef = builder.linedef_mut(9);
+        (linedef.start_vertex, linedef.end_vertex) = (linedef.end_vertex, linedef.start_vertex);
+        (linedef.front_sidedef, linedef.back_sidedef) =
+            (linedef.back_sidedef, linedef.front_sidedef);
+        linedef.special_type = 42;
+        linedef.sector_tag = 3;
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let switches = Switches::default();
+        let mut thinkers = Vec::new();
+
+        // The closed door is in the way
+        let position = Vertex::new(100.0, 64.0);
+        let activation = use_lines(&map, &position, 0.0, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Nothing);
+
+        // The switch can be reached through the open door
+        map.sectors[1].borrow_mut().ceiling_height = 124;
+        let activation = use_lines(&map, &position, 0.0, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Done);
+        assert_eq!(thinkers.len(), 1);
+    }
+
+    #[test]
+    fn test_switch() {
+        let mut builder = door_rooms();
+        builder.sector_mut(1).tag_number = 3;
+        builder.linedef_mut(0).special_type = 103; // S1 open door
+        builder.linedef_mut(0).sector_tag = 3;
+        builder.linedef_mut(2).special_type = 42; // SR close door
+        builder.linedef_mut(2).sector_tag = 3;
+        let sidedef = builder.linedef_mut(0).front_sidedef;
+        builder.sidedef_mut(sidedef).middle_texture = "SW1STRTN";
+        let wad_file = builder.build();
+        let map = Map::new(&wad_file, TEST_MAP_NAME).unwrap();
+        let switches = Switches::load(&wad_file).unwrap();
+        let mut thinkers = Vec::new();
+
+        // Use the west wall to open the door, after which the switch is used up
+        let west = Vertex::new(40.0, 64.0);
+        let activation = use_lines(&map, &west, PI, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Done);
+        assert_eq!(map.linedefs[0].special_type.get(), 0);
+        let texture = &map.sidedefs[sidedef as usize].borrow().middle_texture;
+        assert_eq!(texture, "SW2STRTN");
+        for _ in 0..63 {
+            thinkers[0].mutate();
+        }
+        assert_eq!(door_height(&map), 124);
+        assert!(thinkers[0].is_done());
+        thinkers.clear();
+        let activation = use_lines(&map, &west, PI, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Nothing);
+
+        // The south wall closes it and can be used again
+        let south = Vertex::new(64.0, 40.0);
+        let activation = use_lines(&map, &south, -PI / 2.0, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Done);
+        assert_eq!(map.linedefs[2].special_type.get(), 42);
+    }
+
+    #[test]
+    fn test_linedef_specials() {
+        // Each special is in the table once
+        for (i, (special_type, ..)) in LINEDEF_SPECIALS.iter().enumerate() {
+            assert!(!LINEDEF_SPECIALS[i + 1..]
+                .iter()
+                .any(|(t, ..)| t == special_type));
+        }
+
+        assert_eq!(
+            linedef_special(88),
+            Some(LinedefSpecial {
+                trigger: Trigger::Walk,
+                repeatable: true,
+                action: LinedefAction::Platform(PlatformType::DownWaitUpStay),
+            })
+        );
+        assert_eq!(linedef_special(48), None); // Scrolling walls aren't activated
+    }
+
+    #[test]
+    fn test_walk_over() {
+        // The portal between the rooms lowers the east floor once, the north wall of
+        // the west room raises it every time
+        let mut builder = two_rooms();
+        builder.sector_mut(1).tag_number = 1;
+        builder.linedef_mut(3).special_type = 38; // W1 lower floor to lowest
+        builder.linedef_mut(3).sector_tag = 1;
+        builder.linedef_mut(1).special_type = 92; // WR raise floor by 24
+        builder.linedef_mut(1).sector_tag = 1;
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let switches = Switches::default();
+        let mut thinkers = Vec::new();
+
+        // Walk-over specials can't be used
+        let portal = &map.linedefs[3];
+        let activation = use_special_line(&map, portal, false, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Nothing);
+
+        let activation = cross_special_line(&map, portal, false, &mut thinkers);
+        assert_eq!(activation, Activation::Done);
+        assert_eq!(portal.special_type.get(), 0);
+        assert_eq!(thinkers.len(), 1);
+        let activation = cross_special_line(&map, portal, false, &mut thinkers);
+        assert_eq!(activation, Activation::Nothing);
+
+        // The east floor is still moving
+        let wall = &map.linedefs[1];
+        let activation = cross_special_line(&map, wall, false, &mut thinkers);
+        assert_eq!(activation, Activation::Nothing);
+        assert_eq!(wall.special_type.get(), 92);
+        thinkers.clear();
+        let activation = cross_special_line(&map, wall, false, &mut thinkers);
+        assert_eq!(activation, Activation::Done);
+        assert_eq!(wall.special_type.get(), 92);
+    }
+
+    #[test]
+    fn test_ceiling_and_floor() {
+        // The portal between the rooms opens up the east room
+        let mut builder = two_rooms();
+        builder.sector_mut(1).tag_number = 1;
+        builder.linedef_mut(3).special_type = 40;
+        builder.linedef_mut(3).sector_tag = 1;
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let mut thinkers = Vec::new();
+
+        let activation = cross_special_line(&map, &map.linedefs[3], false, &mut thinkers);
+        assert_eq!(activation, Activation::Done);
+        assert_eq!(thinkers.len(), 2);
+
+        // The ceiling raises to the highest and the floor lowers to the lowest
+        // neighbor, the west room
+        run_thinkers(&mut thinkers, 100);
+        let sector = map.sectors[1].borrow();
+        assert_eq!(sector.ceiling_height, 128);
+        assert_eq!(sector.floor_height, 0);
+        assert!(thinkers.is_empty());
+    }
+
+    #[test]
+    fn test_teleport() {
+        // The portal between the rooms teleports to the east room every time
+        let mut builder = two_rooms();
+        builder.sector_mut(1).tag_number = 1;
+        builder.linedef_mut(3).special_type = 97;
+        builder.linedef_mut(3).sector_tag = 1;
+        let destination = builder.add_thing(192, 64, 180, 14);
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let portal = &map.linedefs[3];
+        let mut thinkers = Vec::new();
+
+        let activation = cross_special_line(&map, portal, false, &mut thinkers);
+        assert_eq!(activation, Activation::Teleport(destination as usize));
+        let activation = cross_special_line(&map, portal, false, &mut thinkers);
+        assert_eq!(activation, Activation::Teleport(destination as usize));
+
+        // Not from the back side
+        let activation = cross_special_line(&map, portal, true, &mut thinkers);
+        assert_eq!(activation, Activation::Nothing);
+    }
+
+    #[test]
+    fn test_lights_and_exits() {
+        // The west room gets light level 100, the east room has 255
+        let mut builder = two_rooms();
+        builder.sector_mut(0).tag_number = 1;
+        builder.sector_mut(0).light_level = 100;
+        for (linedef, special_type) in [(0, 12), (1, 104), (2, 11)] {
+            builder.linedef_mut(linedef).special_type = special_type;
+            builder.linedef_mut(linedef).sector_tag = 1;
+        }
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+        let switches = Switches::default();
+        let mut thinkers = Vec::new();
+        let light_level = || map.sectors[0].borrow().light_level;
+
+        // Up to the brightest neighbor
+        cross_special_line(&map, &map.linedefs[0], false, &mut thinkers);
+        assert_eq!(light_level(), 255);
+
+        // Down to the darkest neighbor
+        map.sectors[1].borrow_mut().light_level = 80;
+        cross_special_line(&map, &map.linedefs[1], false, &mut thinkers);
+        assert_eq!(light_level(), 80);
+
+        // An exit switch
+        let activation = use_special_line(&map, &map.linedefs[2], false, &switches, &mut thinkers);
+        assert_eq!(activation, Activation::Exit(LevelExit::Normal));
+        assert_eq!(map.linedefs[2].special_type.get(), 0);
+    }
+}
@@ -0,0 +1,289 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::map::{Linedef, Sidedef};
+use crate::thinkers::{get_thinkers_mut, Thinker};
+use crate::wad::{WadError, WadFile};
+
+// Switches change the texture of the linedef's front sidedef when they are used,
+// from the SW1 to the SW2 texture or back. Repeatable switches flip back after a
+// second. https://doomwiki.org/wiki/Switch
+
+const BUTTON_TIME: u32 = 35; // Tics until a repeatable switch flips back
+const SWITCHES_RECORD_SIZE: usize = 20; // Size of an entry in a SWITCHES lump
+
+// The built-in switches, see alphSwitchList in p_switch.c. The episode is that of
+// the first game that has the textures: 1 for shareware, 2 for registered and 3
+// for Doom II. All switches are used, a map only has the switches of its game.
+const SWITCH_LIST: [(&str, &str, i16); 40] = [
+    // Doom shareware episode 1 switches
+    ("SW1BRCOM", "SW2BRCOM", 1),
+    ("SW1BRN1", "SW2BRN1", 1),
+    ("SW1BRN2", "SW2BRN2", 1),
+    ("SW1BRNGN", "SW2BRNGN", 1),
+    ("SW1BROWN", "SW2BROWN", 1),
+    ("SW1COMM", "SW2COMM", 1),
+    ("SW1COMP", "SW2COMP", 1),
+    ("SW1DIRT", "SW2DIRT", 1),
+    ("SW1EXIT", "SW2EXIT", 1),
+    ("SW1GRAY", "SW2GRAY", 1),
+    ("SW1GRAY1", "SW2GRAY1", 1),
+    ("SW1METAL", "SW2METAL", 1),
+    ("SW1PIPE", "SW2PIPE", 1),
+    ("SW1SLAD", "SW2SLAD", 1),
+    ("SW1STARG", "SW2STARG", 1),
+    ("SW1STON1", "SW2STON1", 1),
+    ("SW1STON2", "SW2STON2", 1),
+    ("SW1STONE", "SW2STONE", 1),
+    ("SW1STRTN", "SW2STRTN", 1),
+    // Doom registered episodes 2 and 3 switches
+    ("SW1BLUE", "SW2BLUE", 2),
+    ("SW1CMT", "SW2CMT", 2),
+    ("SW1GARG", "SW2GARG", 2),
+    ("SW1GSTON", "SW2GSTON", 2),
+    ("SW1HOT", "SW2HOT", 2),
+    ("SW1LION", "SW2LION", 2),
+    ("SW1SATYR", "SW2SATYR", 2),
+    ("SW1SKIN", "SW2SKIN", 2),
+    ("SW1VINE", "SW2VINE", 2),
+    ("SW1WOOD", "SW2WOOD", 2),
+    // Doom II switches
+    ("SW1PANEL", "SW2PANEL", 3),
+    ("SW1ROCK", "SW2ROCK", 3),
+    ("SW1MET2", "SW2MET2", 3),
+    ("SW1WDMET", "SW2WDMET", 3),
+    ("SW1BRIK", "SW2BRIK", 3),
+    ("SW1MOD1", "SW2MOD1", 3),
+    ("SW1ZIM", "SW2ZIM", 3),
+    ("SW1STON6", "SW2STON6", 3),
+    ("SW1TEK", "SW2TEK", 3),
+    ("SW1MARB", "SW2MARB", 3),
+    ("SW1SKULL", "SW2SKULL", 3),
+];
+
+// A map of switch texture names to the other texture of the switch, both ways
+#[derive(Debug, Default)]
+pub struct Switches {
+    map: HashMap<String, String>,
+}
+
+impl Switches {
+    // Load the switches from a Boom SWITCHES lump, which replaces the built-in
+    // switches. Without the lump, the built-in switches are used.
+    pub fn load(wad_file: &WadFile) -> Result<Switches, WadError> {
+        let Ok(dir_entry) = wad_file.get_dir_entry("SWITCHES") else {
+            return Ok(Switches::new(
+                SWITCH_LIST.iter().map(|&(off, on, _)| (off, on)),
+            ));
+        };
+
+        // An entry is two nul terminated 9 byte names and a 16 bit episode. An
+        // episode of 0 ends the list.
+        let mut names = Vec::new();
+        for index in 0.. {
+            let record_offset = index * SWITCHES_RECORD_SIZE;
+            dir_entry.check_range(record_offset, SWITCHES_RECORD_SIZE)?;
+            let offset = dir_entry.offset as usize + record_offset;
+
+            if wad_file.read_i16(offset + 18) == 0 {
+                break;
+            }
+
+            names.push((
+                wad_file.read_lump_name(offset),
+                wad_file.read_lump_name(offset + 9),
+            ));
+        }
+
+        Ok(Switches::new(
+            names.iter().map(|(off, on)| (off.as_str(), on.as_str())),
+        ))
+    }
+
+    fn new<'a>(names: impl Iterator<Item = (&'a str, &'a str)>) -> Switches {
+        let mut map = HashMap::new();
+
+        for (off, on) in names {
+            map.insert(off.to_ascii_uppercase(), on.to_ascii_uppercase());
+            map.insert(on.to_ascii_uppercase(), off.to_ascii_uppercase());
+        }
+
+        Switches { map }
+    }
+
+    // The other texture of a switch, if the texture is a switch
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.map.get(&name.to_ascii_uppercase()).map(String::as_str)
+    }
+}
+
+// The texture of a sidedef that has the switch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SwitchPart {
+    Upper,
+    Middle,
+    Lower,
+}
+
+impl SwitchPart {
+    fn texture_mut(self, sidedef: &mut Sidedef) -> &mut String {
+        match self {
+            SwitchPart::Upper => &mut sidedef.upper_texture,
+            SwitchPart::Middle => &mut sidedef.middle_texture,
+            SwitchPart::Lower => &mut sidedef.lower_texture,
+        }
+    }
+}
+
+// A repeatable switch that flips back to its texture after a second
+// See P_StartButton in p_switch.c
+#[derive(Debug)]
+pub struct Button {
+    sidedef: Rc<RefCell<Sidedef>>, // The sidedef with the switch
+    part: SwitchPart,              // Which texture has the switch
+    texture: String,               // The texture to flip back to
+    timer: u32,                    // Tics until it flips back
+}
+
+impl Thinker for Button {
+    fn mutate(&mut self) {
+        if self.timer == 0 {
+            return;
+        }
+
+        self.timer -= 1;
+        if self.timer == 0 {
+            let mut sidedef = self.sidedef.borrow_mut();
+            *self.part.texture_mut(&mut sidedef) = self.texture.clone();
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.timer == 0
+    }
+}
+
+// Flip the switch texture on the front sidedef of a used linedef. The upper texture
+// is checked first, then the middle and lower texture. Repeatable switches flip
+// back, unless the switch is already flipping back. See P_ChangeSwitchTexture in
+// p_switch.c.
+pub fn change_switch_texture(
+    switches: &Switches,
+    linedef: &Linedef,
+    repeatable: bool,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+) {
+    let Some(front_sidedef) = &linedef.front_sidedef else {
+        return;
+    };
+
+    for part in [SwitchPart::Upper, SwitchPart::Middle, SwitchPart::Lower] {
+        let texture = {
+            let mut sidedef = front_sidedef.borrow_mut();
+            let texture = part.texture_mut(&mut sidedef);
+            let Some(other) = switches.get(texture) else {
+                continue;
+            };
+
+            std::mem::replace(texture, other.to_string())
+        };
+
+        let is_flipping_back = get_thinkers_mut::<Button>(thinkers)
+            .any(|button| Rc::ptr_eq(&button.sidedef, front_sidedef));
+        if repeatable && !is_flipping_back {
+            thinkers.push(Box::new(Button {
+                sidedef: Rc::clone(front_sidedef),
+                part,
+                texture,
+                timer: BUTTON_TIME,
+            }));
+        }
+
+        return;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::Map;
+    use crate::test_maps::{run_thinkers, square_room, TEST_MAP_NAME};
+    use crate::wad_builder::{WadBuilder, WadType};
+
+    #[test]
+    fn test_builtin_switches() {
+        let switches = Switches::load(&square_room().build()).unwrap();
+
+        assert_eq!(switches.get("SW1BRCOM"), Some("SW2BRCOM"));
+        assert_eq!(switches.get("sw2skull"), Some("SW1SKULL"));
+        assert_eq!(switches.get("STARTAN3"), None);
+    }
+
+    #[test]
+    fn test_switches_lump() {
+        let mut switches = Vec::new();
+        switches.extend(b"MYSWOFF\0\0");
+        switches.extend(b"MYSWON\0\0\0");
+        switches.extend(1i16.to_le_bytes());
+        switches.extend([0; SWITCHES_RECORD_SIZE]);
+
+        let mut wad_builder = WadBuilder::new(WadType::Pwad);
+        wad_builder.add_lump("SWITCHES", switches).unwrap();
+        let wad_file = WadFile::new(wad_builder.write().unwrap()).unwrap();
+
+        let switches = Switches::load(&wad_file).unwrap();
+        assert_eq!(switches.get("MYSWOFF"), Some("MYSWON"));
+        assert_eq!(switches.get("MYSWON"), Some("MYSWOFF"));
+        assert_eq!(switches.get("SW1BRCOM"), None);
+
+        // A missing terminator is an error
+        let mut wad_builder = WadBuilder::new(WadType::Pwad);
+        wad_builder
+            .add_lump("SWITCHES", vec![1; SWITCHES_RECORD_SIZE])
+            .unwrap();
+        let wad_file = WadFile::new(wad_builder.write().unwrap()).unwrap();
+        assert!(Switches::load(&wad_file).is_err());
+    }
+
+    #[test]
+    fn test_change_switch_texture() {
+        let mut builder = square_room();
+        let sidedef = builder.linedef_mut(0).front_sidedef;
+        builder.sidedef_mut(sidedef).middle_texture = "SW1COMP";
+        let wad_file = builder.build();
+        let map = Map::new(&wad_file, TEST_MAP_NAME).unwrap();
+        let switches = Switches::load(&wad_file).unwrap();
+        let linedef = &map.linedefs[0];
+        let texture = || {
+            map.sidedefs[sidedef as usize]
+                .borrow()
+                .middle_texture
+                .clone()
+        };
+        let mut thinkers = Vec::new();
+
+        // A one-time switch stays on
+        change_switch_texture(&switches, linedef, false, &mut thinkers);
+        assert_eq!(texture(), "SW2COMP");
+        assert!(thinkers.is_empty());
+
+        // A repeatable switch flips back after a second. Using it again while it's
+        // flipping back doesn't flip it back twice.
+        change_switch_texture(&switches, linedef, true, &mut thinkers);
+        assert_eq!(texture(), "SW1COMP");
+        change_switch_texture(&switches, linedef, true, &mut thinkers);
+        assert_eq!(texture(), "SW2COMP");
+        change_switch_texture(&switches, linedef, true, &mut thinkers);
+        assert_eq!(thinkers.len(), 1);
+        run_thinkers(&mut thinkers, BUTTON_TIME as usize - 1);
+        assert_eq!(texture(), "SW1COMP");
+        run_thinkers(&mut thinkers, 1);
+        assert_eq!(texture(), "SW2COMP");
+        assert!(thinkers.is_empty());
+
+        // Walls without a switch texture don't change
+        change_switch_texture(&switches, &map.linedefs[1], true, &mut thinkers);
+        assert!(thinkers.is_empty());
+    }
+}
@@ -0,0 +1,87 @@
+use std::rc::Rc;
+
+use crate::info::{StateId, MAP_OBJECT_INFOS};
+use crate::map::{Linedef, Map, ThingTypes, Vertex};
+use crate::map_objects::{MapObjectThinker, MapObjects};
+use crate::renderer::get_sector_from_vertex;
+use crate::thinkers::Thinker;
+
+// Teleporters move the player to the teleport destination thing in a sector with
+// the linedef's tag, facing the direction of the thing. Teleport fog appears where
+// the player left and in front of where the player arrives.
+// https://doomwiki.org/wiki/Teleport
+
+pub const FOG_DISTANCE: f32 = 20.0; // The fog appears this far in front of the player
+
+// The index in the map's things of the teleport destination in the first sector with
+// the linedef's tag that has one. See EV_Teleport in p_telept.c.
+pub fn find_teleport_destination(map: &Map, linedef: &Linedef) -> Option<usize> {
+    map.get_tagged_sectors(linedef.sector_tag)
+        .iter()
+        .find_map(|sector| {
+            map.things.iter().position(|thing| {
+                thing.thing_type == ThingTypes::TeleportDestination as i16
+                    && get_sector_from_vertex(map, &Vertex::new(thing.x, thing.y))
+                        .is_some_and(|s| Rc::ptr_eq(&s, sector))
+            })
+        })
+}
+
+// Spawn teleport fog, which goes away once its animation is done
+pub fn spawn_teleport_fog(
+    map_objects: &mut MapObjects,
+    thinkers: &mut Vec<Box<dyn Thinker>>,
+    position: Vertex,
+) {
+    let Some(info) = MAP_OBJECT_INFOS
+        .into_iter()
+        .find(|info| info.spawn_state == StateId::S_TFOG)
+    else {
+        return;
+    };
+
+    let fog = map_objects.spawn(info, position, 0.0);
+    thinkers.push(Box::new(MapObjectThinker::new(fog)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_maps::{two_rooms, TEST_MAP_NAME};
+
+    #[test]
+    fn test_find_teleport_destination() {
+        // Destinations in both rooms, only the east room has the tag
+        let mut builder = two_rooms();
+        builder.sector_mut(1).tag_number = 1;
+        builder.linedef_mut(0).sector_tag = 1;
+        builder.linedef_mut(1).sector_tag = 2;
+        builder.add_thing(32, 32, 90, 14);
+        let destination = builder.add_thing(192, 64, 180, 14);
+        let map = Map::new(&builder.build(), TEST_MAP_NAME).unwrap();
+
+        assert_eq!(
+            find_teleport_destination(&map, &map.linedefs[0]),
+            Some(destination as usize)
+        );
+        assert_eq!(find_teleport_destination(&map, &map.linedefs[1]), None);
+    }
+
+    #[test]
+    fn test_teleport_fog() {
+        let map = Map::new(&two_rooms().build(), TEST_MAP_NAME).unwrap();
+        let mut map_objects = MapObjects::new(&map).unwrap();
+        let mut thinkers = Vec::new();
+
+        // The fog is removed after its 12 frames of 6 tics
+        spawn_teleport_fog(&mut map_objects, &mut thinkers, Vertex::new(64.0, 64.0));
+        assert_eq!(map_objects.objects.len(), 1);
+        for _ in 0..12 * 6 {
+            assert!(!thinkers[0].is_done());
+            thinkers[0].mutate();
+        }
+        assert!(thinkers[0].is_done());
+        map_objects.remove_finished();
+        assert!(map_objects.objects.is_empty());
+    }
+}
@@ -0,0 +1,762 @@
+// Build small WAD files in memory with hand-crafted map lumps, so that the map
+// loaders, BSP traversal and game logic can be tested without an IWAD.
+use std::f32::consts::PI;
+
+use crate::map::{Flags, Map};
+use crate::thinkers::Thinker;
+use crate::wad::WadFile;
+use crate::wad_builder::{WadBuilder, WadType};
+
+pub const TEST_MAP_NAME: &str = "E1M1";
+
+// Color index of a pixel at x, y in a generated picture or flat
+type Pattern = fn(usize, usize) -> u8;
+
+// A child of a BSP node
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
+pub enum TestNodeChild {
+    Node(i16),
+    SubSector(i16),
+}
+
+#[derive(Debug, Clone)]
+pub struct TestSector {
+    pub floor_height: i16,
+    pub ceiling_height: i16,
+    pub floor_texture: &'static str,
+    pub ceiling_texture: &'static str,
+    pub light_level: i16,
+    pub special_type: i16,
+    pub tag_number: i16,
+}
+
+impl Default for TestSector {
+    fn default() -> TestSector {
+        TestSector {
+            floor_height: 0,
+            ceiling_height: 128,
+            floor_texture: "FLOOR4_8",
+            ceiling_texture: "CEIL3_5",
+            light_level: 160,
+            special_type: 0,
+            tag_number: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TestSidedef {
+    pub x_offset: i16,
+    pub y_offset: i16,
+    pub upper_texture: &'static str,
+    pub lower_texture: &'static str,
+    pub middle_texture: &'static str,
+    pub sector: i16,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestLinedef {
+    pub start_vertex: i16,
+    pub end_vertex: i16,
+    pub flags: i16,
+    pub special_type: i16,
+    pub sector_tag: i16,
+    pub front_sidedef: i16, // -1 if there is none
+    pub back_sidedef: i16,  // -1 if there is none
+}
+
+#[derive(Debug, Clone)]
+struct TestSeg {
+    start_vertex: i16,
+    end_vertex: i16,
+    linedef: i16,
+    direction: bool,
+}
+
+#[derive(Debug, Clone)]
+struct TestNode {
+    x: i16,
+    y: i16,
+    dx: i16,
+    dy: i16,
+    right_child: TestNodeChild,
+    left_child: TestNodeChild,
+}
+
+// Builds the lumps of a single map. Segs, subsectors and nodes are added by hand,
+// there is no node builder. Seg angles and offsets and the node bounding boxes are
+// calculated.
+#[derive(Debug, Default)]
+pub struct TestMapBuilder {
+    things: Vec<(i16, i16, i16, i16, i16)>, // x, y, angle, type, flags
+    vertexes: Vec<(i16, i16)>,
+    sectors: Vec<TestSector>,
+    sidedefs: Vec<TestSidedef>,
+    linedefs: Vec<TestLinedef>,
+    segs: Vec<TestSeg>,
+    subsectors: Vec<(i16, i16)>, // seg count, first seg
+    nodes: Vec<TestNode>,
+}
+
+impl TestMapBuilder {
+    pub fn new() -> TestMapBuilder {
+        TestMapBuilder::default()
+    }
+
+    pub fn add_thing(&mut self, x: i16, y: i16, angle: i16, thing_type: i16) -> i16 {
+        // Appear in all skill levels
+        self.things.push((x, y, angle, thing_type, 7));
+        self.things.len() as i16 - 1
+    }
+
+    pub fn add_vertex(&mut self, x: i16, y: i16) -> i16 {
+        self.vertexes.push((x, y));
+        self.vertexes.len() as i16 - 1
+    }
+
+    pub fn add_sector(&mut self, sector: TestSector) -> i16 {
+        self.sectors.push(sector);
+        self.sectors.len() as i16 - 1
+    }
+
+    pub fn add_sidedef(&mut self, sidedef: TestSidedef) -> i16 {
+        self.sidedefs.push(sidedef);
+        self.sidedefs.len() as i16 - 1
+    }
+
+    pub fn add_linedef(&mut self, linedef: TestLinedef) -> i16 {
+        self.linedefs.push(linedef);
+        self.linedefs.len() as i16 - 1
+    }
+
+    // Change a sector after it's been added, e.g. to set heights
+    pub fn sector_mut(&mut self, sector: i16) -> &mut TestSector {
+        &mut self.sectors[sector as usize]
+    }
+
+    // Change a sidedef after it's been added, e.g. to set offsets
+    pub fn sidedef_mut(&mut self, sidedef: i16) -> &mut TestSidedef {
+        &mut self.sidedefs[sidedef as usize]
+    }
+
+    // Change a linedef after it's been added, e.g. to set a special
+    pub fn linedef_mut(&mut self, linedef: i16) -> &mut TestLinedef {
+        &mut self.linedefs[linedef as usize]
+    }
+
+    // Add a solid wall with a middle texture facing the sector
+    pub fn add_wall(&mut self, start_vertex: i16, end_vertex: i16, sector: i16) -> i16 {
+        let front_sidedef = self.add_sidedef(TestSidedef {
+            x_offset: 0,
+            y_offset: 0,
+            upper_texture: "-",
+            lower_texture: "-",
+            middle_texture: "STARTAN3",
+            sector,
+        });
+
+        self.add_linedef(TestLinedef {
+            start_vertex,
+            end_vertex,
+            flags: Flags::BLOCKING,
+            special_type: 0,
+            sector_tag: 0,
+            front_sidedef,
+            back_sidedef: -1,
+        })
+    }
+
+    // Add a two-sided line between the front and back sectors
+    pub fn add_portal(
+        &mut self,
+        start_vertex: i16,
+        end_vertex: i16,
+        front_sector: i16,
+        back_sector: i16,
+    ) -> i16 {
+        let mut add_sidedef = |sector| {
+            self.add_sidedef(TestSidedef {
+                x_offset: 0,
+                y_offset: 0,
+                upper_texture: "STARTAN3",
+                lower_texture: "STARTAN3",
+                middle_texture: "-",
+                sector,
+            })
+        };
+        let front_sidedef = add_sidedef(front_sector);
+        let back_sidedef = add_sidedef(back_sector);
+
+        self.add_linedef(TestLinedef {
+            start_vertex,
+            end_vertex,
+            flags: Flags::TWOSIDED,
+            special_type: 0,
+            sector_tag: 0,
+            front_sidedef,
+            back_sidedef,
+        })
+    }
+
+    // Add a seg covering a whole linedef, on the front or back side
+    pub fn add_seg(&mut self, linedef: i16, direction: bool) -> i16 {
+        let line = &self.linedefs[linedef as usize];
+        let (start_vertex, end_vertex) = if direction {
+            (line.end_vertex, line.start_vertex)
+        } else {
+            (line.start_vertex, line.end_vertex)
+        };
+
+        self.segs.push(TestSeg {
+            start_vertex,
+            end_vertex,
+            linedef,
+            direction,
+        });
+        self.segs.len() as i16 - 1
+    }
+
+    // Add a subsector made up of the segs added since the previous subsector
+    pub fn add_subsector(&mut self) -> i16 {
+        let first_seg = self
+            .subsectors
+            .last()
+            .map(|(count, first)| count + first)
+            .unwrap_or(0);
+        let seg_count = self.segs.len() as i16 - first_seg;
+        self.subsectors.push((seg_count, first_seg));
+        self.subsectors.len() as i16 - 1
+    }
+
+    // Add a node with a partition line starting at x, y. The right child is on
+    // the right side of the partition line. The last node is the root node.
+    pub fn add_node(
+        &mut self,
+        (x, y): (i16, i16),
+        (dx, dy): (i16, i16),
+        right_child: TestNodeChild,
+        left_child: TestNodeChild,
+    ) -> i16 {
+        self.nodes.push(TestNode {
+            x,
+            y,
+            dx,
+            dy,
+            right_child,
+            left_child,
+        });
+        self.nodes.len() as i16 - 1
+    }
+
+    // Add the map lumps to a WAD
+    pub fn add_to_wad(&self, wad_builder: &mut WadBuilder, map_name: &str) {
+        let lumps = [
+            ("THINGS", self.things_lump()),
+            ("LINEDEFS", self.linedefs_lump()),
+            ("SIDEDEFS", self.sidedefs_lump()),
+            ("VERTEXES", self.vertexes_lump()),
+            ("SEGS", self.segs_lump()),
+            ("SSECTORS", self.subsectors_lump()),
+            ("NODES", self.nodes_lump()),
+            ("SECTORS", self.sectors_lump()),
+            ("REJECT", self.reject_lump()),
+            ("BLOCKMAP", Vec::new()),
+        ];
+
+        wad_builder.add_marker(map_name).unwrap();
+        for (name, data) in lumps {
+            wad_builder.add_lump(name, data).unwrap();
+        }
+    }
+
+    // Build a WAD file with this map and the graphics it uses
+    pub fn build(&self) -> WadFile {
+        let mut wad_builder = WadBuilder::new(WadType::Iwad);
+        add_graphics_to_wad(&mut wad_builder);
+        self.add_to_wad(&mut wad_builder, TEST_MAP_NAME);
+
+        WadFile::new(wad_builder.write().unwrap()).unwrap()
+    }
+
+    fn things_lump(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (x, y, angle, thing_type, flags) in &self.things {
+            push_i16s(&mut data, &[*x, *y, *angle, *thing_type, *flags]);
+        }
+        data
+    }
+
+    fn linedefs_lump(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for linedef in &self.linedefs {
+            push_i16s(
+                &mut data,
+                &[
+                    linedef.start_vertex,
+                    linedef.end_vertex,
+                    linedef.flags,
+                    linedef.special_type,
+                    linedef.sector_tag,
+                    linedef.front_sidedef,
+                    linedef.back_sidedef,
+                ],
+            );
+        }
+        data
+    }
+
+    fn sidedefs_lump(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for sidedef in &self.sidedefs {
+            push_i16s(&mut data, &[sidedef.x_offset, sidedef.y_offset]);
+            push_name(&mut data, sidedef.upper_texture);
+            push_name(&mut data, sidedef.lower_texture);
+            push_name(&mut data, sidedef.middle_texture);
+            push_i16s(&mut data, &[sidedef.sector]);
+        }
+        data
+    }
+
+    fn vertexes_lump(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (x, y) in &self.vertexes {
+            push_i16s(&mut data, &[*x, *y]);
+        }
+        data
+    }
+
+    fn segs_lump(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for seg in &self.segs {
+            let (x1, y1) = self.vertexes[seg.start_vertex as usize];
+            let (x2, y2) = self.vertexes[seg.end_vertex as usize];
+
+            // Full circle is 65536
+            let radians = ((y2 - y1) as f32).atan2((x2 - x1) as f32);
+            let angle = (radians / (2.0 * PI) * 65536.0).round() as i32 as i16;
+
+            // Distance along the linedef to the start of the seg
+            let linedef = &self.linedefs[seg.linedef as usize];
+            let linedef_start = if seg.direction {
+                linedef.end_vertex
+            } else {
+                linedef.start_vertex
+            };
+            let (lx, ly) = self.vertexes[linedef_start as usize];
+            let offset = (((x1 - lx) as f32).powi(2) + ((y1 - ly) as f32).powi(2)).sqrt() as i16;
+
+            push_i16s(
+                &mut data,
+                &[
+                    seg.start_vertex,
+                    seg.end_vertex,
+                    angle,
+                    seg.linedef,
+                    seg.direction as i16,
+                    offset,
+                ],
+            );
+        }
+        data
+    }
+
+    fn subsectors_lump(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for (seg_count, first_seg) in &self.subsectors {
+            push_i16s(&mut data, &[*seg_count, *first_seg]);
+        }
+        data
+    }
+
+    fn nodes_lump(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for node in &self.nodes {
+            push_i16s(&mut data, &[node.x, node.y, node.dx, node.dy]);
+            push_i16s(&mut data, &self.child_bounding_box(node.right_child));
+            push_i16s(&mut data, &self.child_bounding_box(node.left_child));
+            push_i16s(
+                &mut data,
+                &[
+                    Self::child_index(node.right_child),
+                    Self::child_index(node.left_child),
+                ],
+            );
+        }
+        data
+    }
+
+    fn sectors_lump(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        for sector in &self.sectors {
+            push_i16s(&mut data, &[sector.floor_height, sector.ceiling_height]);
+            push_name(&mut data, sector.floor_texture);
+            push_name(&mut data, sector.ceiling_texture);
+            push_i16s(
+                &mut data,
+                &[sector.light_level, sector.special_type, sector.tag_number],
+            );
+        }
+        data
+    }
+
+    // One bit per pair of sectors, all sectors can see each other
+    fn reject_lump(&self) -> Vec<u8> {
+        vec![0; (self.sectors.len().pow(2)).div_ceil(8)]
+    }
+
+    fn child_index(child: TestNodeChild) -> i16 {
+        match child {
+            TestNodeChild::Node(index) => index,
+            TestNodeChild::SubSector(index) => index | i16::MIN,
+        }
+    }
+
+    // Bounding box of the segs in a child as top, bottom, left, right
+    fn child_bounding_box(&self, child: TestNodeChild) -> [i16; 4] {
+        let mut vertexes = Vec::new();
+        self.collect_child_vertexes(child, &mut vertexes);
+
+        [
+            vertexes.iter().map(|v| v.1).max().unwrap_or(0),
+            vertexes.iter().map(|v| v.1).min().unwrap_or(0),
+            vertexes.iter().map(|v| v.0).min().unwrap_or(0),
+            vertexes.iter().map(|v| v.0).max().unwrap_or(0),
+        ]
+    }
+
+    fn collect_child_vertexes(&self, child: TestNodeChild, vertexes: &mut Vec<(i16, i16)>) {
+        match child {
+            TestNodeChild::Node(index) => {
+                let node = &self.nodes[index as usize];
+                self.collect_child_vertexes(node.right_child, vertexes);
+                self.collect_child_vertexes(node.left_child, vertexes);
+            }
+            TestNodeChild::SubSector(index) => {
+                let (seg_count, first_seg) = self.subsectors[index as usize];
+                for seg in &self.segs[first_seg as usize..(first_seg + seg_count) as usize] {
+                    vertexes.push(self.vertexes[seg.start_vertex as usize]);
+                    vertexes.push(self.vertexes[seg.end_vertex as usize]);
+                }
+            }
+        }
+    }
+}
+
+// Add a palette and the textures and flats used by the test maps. The pixels are
+// simple patterns, so that rendered frames are easy to reason about.
+pub fn add_graphics_to_wad(wad_builder: &mut WadBuilder) {
+    // Color i is (i, 3i, 255 - i) in palette 0. The red of the damage, bonus
+    // and radiation suit palettes is raised by 8 for every palette.
+    let mut playpal = Vec::new();
+    for p in 0..14 {
+        for i in 0..256 {
+            playpal.extend([(i + p * 8).min(255) as u8, (i * 3) as u8, 255 - i as u8]);
+        }
+    }
+    wad_builder.add_lump("PLAYPAL", playpal).unwrap();
+
+    // Light colormap r maps color i to i * (32 - r) / 32, followed by an inverse
+    // invulnerability colormap and an all-black colormap
+    let mut colormap = Vec::new();
+    for r in 0..32 {
+        colormap.extend((0..256).map(|i| (i * (32 - r) / 32) as u8));
+    }
+    colormap.extend((0..256).map(|i| 255 - i as u8));
+    colormap.extend([0; 256]);
+    wad_builder.add_lump("COLORMAP", colormap).unwrap();
+
+    // Textures are made up of a single patch with the same name
+    let textures: [(&str, i16, i16, Pattern); 2] = [
+        ("STARTAN3", 64, 128, |x, y| {
+            32 + ((x / 8 + y / 8) % 2) as u8 * 16
+        }),
+        ("SKY1", 256, 128, |_, y| 192 + (y / 16) as u8),
+    ];
+
+    let mut pnames = Vec::new();
+    pnames.extend((textures.len() as u32).to_le_bytes());
+    for (name, ..) in &textures {
+        push_name(&mut pnames, name);
+    }
+    wad_builder.add_lump("PNAMES", pnames).unwrap();
+
+    // A texture entry is 22 bytes, plus 10 bytes for the patch
+    let mut texture1 = Vec::new();
+    texture1.extend((textures.len() as u32).to_le_bytes());
+    for i in 0..textures.len() {
+        let offset = 4 + textures.len() * 4 + i * 32;
+        texture1.extend((offset as u32).to_le_bytes());
+    }
+    for (i, (name, width, height, _)) in textures.iter().enumerate() {
+        push_name(&mut texture1, name);
+        push_i16s(&mut texture1, &[0, 0, *width, *height, 0, 0, 1]);
+        push_i16s(&mut texture1, &[0, 0, i as i16, 1, 0]);
+    }
+    wad_builder.add_lump("TEXTURE1", texture1).unwrap();
+
+    wad_builder.add_marker("P_START").unwrap();
+    for (name, width, height, pattern) in &textures {
+        wad_builder
+            .add_lump(name, picture_lump(*width, *height, *pattern))
+            .unwrap();
+    }
+    wad_builder.add_marker("P_END").unwrap();
+
+    let flats: [(&str, Pattern); 3] = [
+        ("FLOOR4_8", |x, y| 64 + ((x / 16 + y / 16) % 2) as u8 * 16),
+        ("CEIL3_5", |x, _| 128 + (x / 16) as u8),
+        ("F_SKY1", |_, _| 0),
+    ];
+
+    wad_builder.add_marker("F_START").unwrap();
+    for (name, pattern) in &flats {
+        let mut data = Vec::with_capacity(64 * 64);
+        for y in 0..64 {
+            for x in 0..64 {
+                data.push(pattern(x, y));
+            }
+        }
+        wad_builder.add_lump(name, data).unwrap();
+    }
+    wad_builder.add_marker("F_END").unwrap();
+
+    wad_builder.add_marker("S_START").unwrap();
+    wad_builder.add_marker("S_END").unwrap();
+}
+
+// Encode a picture without transparent pixels, with one post per column
+// https://doomwiki.org/wiki/Picture_format
+pub fn picture_lump(width: i16, height: i16, pattern: Pattern) -> Vec<u8> {
+    let mut data = Vec::new();
+    push_i16s(&mut data, &[width, height, 0, 0]);
+
+    // A post is the y offset, length, a padding byte, the pixels and another
+    // padding byte. A column is a post and an end of column marker.
+    let column_size = height as usize + 5;
+    let columns_offset = 8 + width as usize * 4;
+    for x in 0..width as usize {
+        data.extend(((columns_offset + x * column_size) as u32).to_le_bytes());
+    }
+
+    for x in 0..width as usize {
+        data.extend([0, height as u8, 0]);
+        for y in 0..height as usize {
+            data.push(pattern(x, y));
+        }
+        data.extend([0, 0xff]);
+    }
+
+    data
+}
+
+fn push_i16s(data: &mut Vec<u8>, values: &[i16]) {
+    for value in values {
+        data.extend(value.to_le_bytes());
+    }
+}
+
+// Lump names are 8 bytes, padded with nul bytes
+fn push_name(data: &mut Vec<u8>, name: &str) {
+    let mut bytes = [0u8; 8];
+    bytes[..name.len()].copy_from_slice(name.as_bytes());
+    data.extend(bytes);
+}
+
+// A 128x128 room with the player in the middle, facing east. The walls go
+// clockwise, so the front sides face into the room. The BSP tree has a single node
+// splitting the room along the diagonal from (0, 0) to (128, 128).
+pub fn square_room() -> TestMapBuilder {
+    let mut builder = TestMapBuilder::new();
+
+    let v0 = builder.add_vertex(0, 0);
+    let v1 = builder.add_vertex(0, 128);
+    let v2 = builder.add_vertex(128, 128);
+    let v3 = builder.add_vertex(128, 0);
+
+    let sector = builder.add_sector(TestSector::default());
+
+    let west = builder.add_wall(v0, v1, sector);
+    let north = builder.add_wall(v1, v2, sector);
+    let east = builder.add_wall(v2, v3, sector);
+    let south = builder.add_wall(v3, v0, sector);
+
+    // Right of the diagonal
+    builder.add_seg(east, false);
+    builder.add_seg(south, false);
+    let right = builder.add_subsector();
+
+    // Left of the diagonal
+    builder.add_seg(west, false);
+    builder.add_seg(north, false);
+    let left = builder.add_subsector();
+
+    builder.add_node(
+        (0, 0),
+        (128, 128),
+        TestNodeChild::SubSector(right),
+        TestNodeChild::SubSector(left),
+    );
+
+    builder.add_thing(64, 64, 0, 1); // Player 1 start
+
+    builder
+}
+
+// Two 128x128 rooms with a 16 units wide closed door between them, at x=128 to 144.
+// The door lines face away from the door, so that the door sector is on the back.
+// Linedef 3 is the west side of the door and linedef 9 the east side. The root node
+// splits off the west room at x=128, its left child splits the door from the east
+// room at x=144.
+pub fn door_rooms() -> TestMapBuilder {
+    let mut builder = TestMapBuilder::new();
+
+    let v0 = builder.add_vertex(0, 0);
+    let v1 = builder.add_vertex(0, 128);
+    let v2 = builder.add_vertex(128, 128);
+    let v3 = builder.add_vertex(128, 0);
+    let v4 = builder.add_vertex(144, 128);
+    let v5 = builder.add_vertex(144, 0);
+    let v6 = builder.add_vertex(272, 128);
+    let v7 = builder.add_vertex(272, 0);
+
+    let west_sector = builder.add_sector(TestSector::default());
+    let door_sector = builder.add_sector(TestSector {
+        ceiling_height: 0,
+        ..TestSector::default()
+    });
+    let east_sector = builder.add_sector(TestSector::default());
+
+    let west_room_walls = [
+        builder.add_wall(v0, v1, west_sector),
+        builder.add_wall(v1, v2, west_sector),
+        builder.add_wall(v3, v0, west_sector),
+    ];
+    let west_door = builder.add_portal(v2, v3, west_sector, door_sector);
+    let door_walls = [
+        builder.add_wall(v2, v4, door_sector),
+        builder.add_wall(v5, v3, door_sector),
+    ];
+    let east_room_walls = [
+        builder.add_wall(v4, v6, east_sector),
+        builder.add_wall(v6, v7, east_sector),
+        builder.add_wall(v7, v5, east_sector),
+    ];
+    let east_door = builder.add_portal(v5, v4, east_sector, door_sector);
+
+    for wall in west_room_walls {
+        builder.add_seg(wall, false);
+    }
+    builder.add_seg(west_door, false);
+    let west = builder.add_subsector();
+
+    builder.add_seg(west_door, true);
+    for wall in door_walls {
+        builder.add_seg(wall, false);
+    }
+    builder.add_seg(east_door, true);
+    let door = builder.add_subsector();
+
+    builder.add_seg(east_door, false);
+    for wall in east_room_walls {
+        builder.add_seg(wall, false);
+    }
+    let east = builder.add_subsector();
+
+    let east_node = builder.add_node(
+        (144, 128),
+        (0, -128),
+        TestNodeChild::SubSector(door),
+        TestNodeChild::SubSector(east),
+    );
+    builder.add_node(
+        (128, 128),
+        (0, -128),
+        TestNodeChild::SubSector(west),
+        TestNodeChild::Node(east_node),
+    );
+
+    builder.add_thing(64, 64, 0, 1); // Player 1 start
+
+    builder
+}
+
+// Two 128x128 rooms next to each other, joined by a two-sided line at x=128. The
+// east room has a floor at 16 and a ceiling at 112. The BSP tree has a single node
+// with the partition line on the two-sided line, going south, so that the west room
+// is on the right.
+pub fn two_rooms() -> TestMapBuilder {
+    let mut builder = TestMapBuilder::new();
+
+    let v0 = builder.add_vertex(0, 0);
+    let v1 = builder.add_vertex(0, 128);
+    let v2 = builder.add_vertex(128, 128);
+    let v3 = builder.add_vertex(128, 0);
+    let v4 = builder.add_vertex(256, 128);
+    let v5 = builder.add_vertex(256, 0);
+
+    let west_sector = builder.add_sector(TestSector::default());
+    let east_sector = builder.add_sector(TestSector {
+        floor_height: 16,
+        ceiling_height: 112,
+        light_level: 255,
+        ..TestSector::default()
+    });
+
+    let west_room_walls = [
+        builder.add_wall(v0, v1, west_sector),
+        builder.add_wall(v1, v2, west_sector),
+        builder.add_wall(v3, v0, west_sector),
+    ];
+    let portal = builder.add_portal(v2, v3, west_sector, east_sector);
+    let east_room_walls = [
+        builder.add_wall(v2, v4, east_sector),
+        builder.add_wall(v4, v5, east_sector),
+        builder.add_wall(v5, v3, east_sector),
+    ];
+
+    for wall in west_room_walls {
+        builder.add_seg(wall, false);
+    }
+    builder.add_seg(portal, false);
+    let west = builder.add_subsector();
+
+    builder.add_seg(portal, true);
+    for wall in east_room_walls {
+        builder.add_seg(wall, false);
+    }
+    let east = builder.add_subsector();
+
+    builder.add_node(
+        (128, 128),
+        (0, -128),
+        TestNodeChild::SubSector(west),
+        TestNodeChild::SubSector(east),
+    );
+
+    builder.add_thing(64, 64, 0, 1); // Player 1 start
+
+    builder
+}
+
+// The two rooms, loaded with tags. Linedef 0 has the tag of the west room and
+// linedef 1 that of the east room.
+pub fn tagged_two_rooms() -> Map {
+    let mut builder = two_rooms();
+    builder.sector_mut(0).tag_number = 1;
+    builder.sector_mut(1).tag_number = 2;
+    builder.linedef_mut(0).sector_tag = 1;
+    builder.linedef_mut(1).sector_tag = 2;
+    Map::new(&builder.build(), TEST_MAP_NAME).unwrap()
+}
+
+// Run the thinkers for a number of tics and remove the finished ones, like the game
+// does
+pub fn run_thinkers(thinkers: &mut Vec<Box<dyn Thinker>>, tics: usize) {
+    for _ in 0..tics {
+        for thinker in thinkers.iter_mut() {
+            thinker.mutate();
+        }
+        thinkers.retain(|thinker| !thinker.is_done());
+    }
+}
@@ -1,14 +1,75 @@
+use std::any::Any;
+use std::cell::RefCell;
 use std::rc::Rc;
 
+use crate::doors::VerticalDoor;
 use crate::lights::{FireFlicker, GlowingLight, LightFlash, StrobeFlash, FAST_DARK, SLOW_DARK};
-use crate::map::Map;
+use crate::map::{Map, Sector};
 use crate::map_objects::{MapObjectThinker, MapObjects};
+use crate::scrollers::ScrollingWall;
 
-pub trait Thinker {
+// Lets a thinker be downcast to its type, e.g. to reverse a moving door
+pub trait AsAny {
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+pub trait Thinker: AsAny {
     fn mutate(&mut self);
     fn kill(&mut self) {}
     fn explode(&mut self) {}
     fn respawn(&mut self) {}
+
+    // The sector of which a door, lift, floor or ceiling thinker moves the floor or
+    // ceiling. A sector is moved by one thinker at a time, like specialdata in doom.
+    fn moved_sector(&self) -> Option<&Rc<RefCell<Sector>>> {
+        None
+    }
+
+    // Called after mutate when the player doesn't fit in the moved sector anymore
+    fn crushed(&mut self) {}
+
+    // Finished thinkers are removed at the end of the tic
+    fn is_done(&self) -> bool {
+        false
+    }
+}
+
+// Is a thinker moving the floor or ceiling of a sector?
+pub fn is_sector_moving(thinkers: &[Box<dyn Thinker>], sector: &Sector) -> bool {
+    thinkers.iter().any(|thinker| {
+        thinker
+            .moved_sector()
+            .is_some_and(|moved_sector| moved_sector.borrow().id == sector.id)
+    })
+}
+
+// The thinkers of type T
+pub fn get_thinkers_mut<T: Thinker + 'static>(
+    thinkers: &mut [Box<dyn Thinker>],
+) -> impl Iterator<Item = &mut T> {
+    thinkers
+        .iter_mut()
+        .filter_map(|thinker| thinker.as_mut().as_any_mut().downcast_mut::<T>())
+}
+
+// Get the thinker that moves a sector if it's of type T
+pub fn get_sector_mover<'a, T: Thinker + 'static>(
+    thinkers: &'a mut [Box<dyn Thinker>],
+    sector: &Sector,
+) -> Option<&'a mut T> {
+    let thinker = thinkers.iter_mut().find(|thinker| {
+        thinker
+            .moved_sector()
+            .is_some_and(|moved_sector| moved_sector.borrow().id == sector.id)
+    })?;
+
+    thinker.as_mut().as_any_mut().downcast_mut::<T>()
 }
 
 fn init_sector_thinkers(thinkers: &mut Vec<Box<dyn Thinker>>, map: &Map) {
@@ -69,6 +130,19 @@ fn init_sector_thinkers(thinkers: &mut Vec<Box<dyn Thinker>>, map: &Map) {
                     true,
                 )));
             }
+            10 => {
+                // door close in 30 seconds
+                thinkers.push(Box::new(VerticalDoor::close_in_30(Rc::clone(sector))));
+                sector.borrow_mut().special_type = 0;
+            }
+            14 => {
+                // door raise in 5 minutes
+                thinkers.push(Box::new(VerticalDoor::raise_in_5_mins(
+                    map,
+                    Rc::clone(sector),
+                )));
+                sector.borrow_mut().special_type = 0;
+            }
             17 => {
                 // firelight flicker
                 thinkers.push(Box::new(FireFlicker::new(map, Rc::clone(sector))))
@@ -79,6 +153,41 @@ fn init_sector_thinkers(thinkers: &mut Vec<Box<dyn Thinker>>, map: &Map) {
     }
 }
 
+fn init_linedef_thinkers(thinkers: &mut Vec<Box<dyn Thinker>>, map: &Map) {
+    for linedef in &map.linedefs {
+        let Some(front_sidedef) = &linedef.front_sidedef else {
+            continue;
+        };
+
+        match linedef.special_type.get() {
+            48 => {
+                // scroll texture left
+                thinkers.push(Box::new(ScrollingWall::new(
+                    Rc::clone(front_sidedef),
+                    1.0,
+                    0.0,
+                )));
+            }
+            85 => {
+                // scroll texture right (Boom)
+                thinkers.push(Box::new(ScrollingWall::new(
+                    Rc::clone(front_sidedef),
+                    -1.0,
+                    0.0,
+                )));
+            }
+            255 => {
+                // scroll texture by the sidedef offsets (Boom)
+                thinkers.push(Box::new(ScrollingWall::from_offsets(Rc::clone(
+                    front_sidedef,
+                ))));
+            }
+
+            _ => {}
+        }
+    }
+}
+
 fn init_map_obj_thinkers(thinkers: &mut Vec<Box<dyn Thinker>>, map_objects: &MapObjects) {
     for map_object in &map_objects.objects {
         thinkers.push(Box::new(MapObjectThinker::new(Rc::clone(map_object))));
@@ -87,5 +196,6 @@ fn init_map_obj_thinkers(thinkers: &mut Vec<Box<dyn Thinker>>, map_objects: &Map
 
 pub fn init_thinkers(thinkers: &mut Vec<Box<dyn Thinker>>, map: &Map, map_objects: &MapObjects) {
     init_sector_thinkers(thinkers, map);
+    init_linedef_thinkers(thinkers, map);
     init_map_obj_thinkers(thinkers, map_objects);
 }
@@ -1,10 +1,11 @@
 use std::collections::HashMap;
-use std::ffi::CStr;
+use std::error::Error;
 use std::rc::Rc;
 use std::{fmt, str};
 
 // An enum which encodes the relative position in the wad file for map lumps
 #[allow(dead_code)]
+#[derive(Debug, Clone, Copy)]
 pub enum MapLumpName {
     Things = 1, // Monsters, weapons, keys, etc
     Linedefs,   // Lines
@@ -35,6 +36,87 @@ impl fmt::Display for MapLumpName {
     }
 }
 
+// Errors that can happen while reading a WAD file or the lumps in it
+#[derive(Debug)]
+pub enum WadError {
+    TruncatedHeader {
+        size: usize, // Size of the file
+    },
+    InvalidMagic {
+        magic: String, // Anything but IWAD or PWAD
+    },
+    DirectoryOutOfBounds {
+        dir_offset: u32, // Offset of the directory in the file
+        lump_count: u32, // Amount of lumps in the directory
+        size: usize,     // Size of the file
+    },
+    LumpOutOfBounds {
+        name: String, // Lump name
+        offset: u32,  // Lump offset in the file
+        size: u32,    // Lump size
+    },
+    LumpNotFound {
+        name: String,         // Lump name
+        namespace: Namespace, // The namespace that was searched
+    },
+    MapLumpNotFound {
+        map_name: String,  // Map name
+        lump_name: String, // THINGS, LINEDEFS, etc.
+    },
+    InvalidLump {
+        name: String,   // Lump name
+        offset: usize,  // Offset relative to the start of the lump
+        reason: String, // What's wrong
+    },
+    UnknownTexture {
+        name: String, // Texture name
+    },
+    InvalidLumpName {
+        name: String, // Lump name that can't be written to a directory entry
+    },
+}
+
+impl fmt::Display for WadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WadError::TruncatedHeader { size } => {
+                write!(f, "File of {} bytes is too small for a WAD header", size)
+            }
+            WadError::InvalidMagic { magic } => write!(f, "Unhandled WAD file type: {}", magic),
+            WadError::DirectoryOutOfBounds {
+                dir_offset,
+                lump_count,
+                size,
+            } => write!(
+                f,
+                "Directory of {} lumps at offset {} doesn't fit in file of {} bytes",
+                lump_count, dir_offset, size
+            ),
+            WadError::LumpOutOfBounds { name, offset, size } => write!(
+                f,
+                "Lump {} of {} bytes at offset {} is outside of the file",
+                name, size, offset
+            ),
+            WadError::LumpNotFound { name, namespace } => {
+                write!(f, "Could not find lump {} in {:?}", name, namespace)
+            }
+            WadError::MapLumpNotFound {
+                map_name,
+                lump_name,
+            } => write!(f, "Could not find lump {} in map {}", lump_name, map_name),
+            WadError::InvalidLump {
+                name,
+                offset,
+                reason,
+            } => write!(f, "Invalid lump {} at offset {}: {}", name, offset, reason),
+            WadError::UnknownTexture { name } => write!(f, "Unknown texture {}", name),
+            WadError::InvalidLumpName { name } => write!(f, "Invalid lump name {:?}", name),
+        }
+    }
+}
+
+impl Error for WadError {}
+
 // Wad file header
 pub struct Header {
     magic: String,       // Magic 4-character string, IWAD or PWAD
@@ -54,106 +136,216 @@ impl fmt::Debug for Header {
 
 // Read the WAD file header from file data
 impl Header {
-    pub fn read(file: &[u8]) -> Header {
-        Header {
-            magic: str::from_utf8(&file[0..4]).unwrap().to_string(),
+    pub fn read(file: &[u8]) -> Result<Header, WadError> {
+        // The header is 12 bytes long
+        if file.len() < 12 {
+            return Err(WadError::TruncatedHeader { size: file.len() });
+        }
+
+        let header = Header {
+            magic: String::from_utf8_lossy(&file[0..4]).to_string(),
             lump_count: u32::from_le_bytes(file[4..8].try_into().unwrap()),
             dir_offset: u32::from_le_bytes(file[8..12].try_into().unwrap()),
+        };
+
+        if header.magic != "IWAD" && header.magic != "PWAD" {
+            return Err(WadError::InvalidMagic {
+                magic: header.magic,
+            });
+        }
+
+        // A directory entry is 16 bytes long
+        if header.dir_offset as u64 + header.lump_count as u64 * 16 > file.len() as u64 {
+            return Err(WadError::DirectoryOutOfBounds {
+                dir_offset: header.dir_offset,
+                lump_count: header.lump_count,
+                size: file.len(),
+            });
+        }
+
+        Ok(header)
+    }
+}
+
+// Lumps between a pair of start and end markers belong to a namespace. A flat and
+// a patch may have the same name, so lookups of flats, sprites and patches are
+// done in their own namespace.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Namespace {
+    Global,  // Not between markers
+    Sprites, // Between S_START and S_END or SS_START and SS_END
+    Flats,   // Between F_START and F_END or FF_START and FF_END
+    Patches, // Between P_START and P_END or PP_START and PP_END
+}
+
+impl Namespace {
+    // Determine if a lump name is a marker. Returns the namespace that starts after
+    // the marker, Global for an end marker, or None if it's not a marker.
+    // The F1_START, P1_START, etc. markers only subdivide a namespace and don't
+    // change it.
+    fn from_marker(name: &str, current: Namespace) -> Option<Namespace> {
+        match name {
+            "S_START" | "SS_START" => Some(Namespace::Sprites),
+            "F_START" | "FF_START" => Some(Namespace::Flats),
+            "P_START" | "PP_START" => Some(Namespace::Patches),
+            "S_END" | "SS_END" | "F_END" | "FF_END" | "P_END" | "PP_END" => Some(Namespace::Global),
+            "F1_START" | "F2_START" | "F3_START" | "F1_END" | "F2_END" | "F3_END" | "P1_START"
+            | "P2_START" | "P3_START" | "P1_END" | "P2_END" | "P3_END" => Some(current),
+            _ => None,
         }
     }
 }
 
 #[derive(Debug)]
 pub struct DirEntry {
-    pub index: i16,   // Index in the lump list
-    pub name: String, // Lump name
-    pub offset: u32,  // Lump offset in file
-    pub size: u32,    // Lump size
+    pub index: usize,         // Index in the lump list
+    pub name: String,         // Lump name
+    pub offset: u32,          // Lump offset in file
+    pub size: u32,            // Lump size
+    pub namespace: Namespace, // The markers the lump is between
 }
 
-// A loaded WAD file
+impl DirEntry {
+    // Make sure that length bytes at offset (relative to the start of the lump)
+    // are within the lump.
+    pub fn check_range(&self, offset: usize, length: usize) -> Result<(), WadError> {
+        if offset + length > self.size as usize {
+            return Err(WadError::InvalidLump {
+                name: self.name.clone(),
+                offset,
+                reason: format!("reading {} bytes past the end of the lump", length),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+// An 8 character lump name, which is null terminated unless it's exactly 8
+// characters long
+fn lump_name(bytes: &[u8]) -> String {
+    let length = bytes.iter().position(|&b| b == 0).unwrap_or(8);
+
+    String::from_utf8_lossy(&bytes[..length]).to_string()
+}
+
+// Read the directory of a file that will start at base_offset in the file data. The
+// indexes of the entries start at first_index.
+fn read_dirs(
+    file: &[u8],
+    header: &Header,
+    base_offset: u32,
+    first_index: usize,
+) -> Result<Vec<DirEntry>, WadError> {
+    // Each file starts outside of any markers
+    let mut namespace = Namespace::Global;
+    let mut dir_entries = Vec::new();
+
+    for i in 0..header.lump_count {
+        // A directory entry is 16 bytes long
+        let dir_entry_offset = (header.dir_offset + i * 16) as usize;
+
+        let file_offset = u32::from_le_bytes(
+            file[dir_entry_offset..dir_entry_offset + 4]
+                .try_into()
+                .unwrap(),
+        );
+
+        let size = u32::from_le_bytes(
+            file[dir_entry_offset + 4..dir_entry_offset + 8]
+                .try_into()
+                .unwrap(),
+        );
+
+        let name =
+            lump_name(&file[dir_entry_offset + 8..dir_entry_offset + 16]).to_ascii_uppercase();
+
+        if file_offset as u64 + size as u64 > file.len() as u64 {
+            return Err(WadError::LumpOutOfBounds {
+                name,
+                offset: file_offset,
+                size,
+            });
+        }
+
+        // Markers themselves are global lumps
+        let marker_namespace = Namespace::from_marker(&name, namespace);
+
+        dir_entries.push(DirEntry {
+            index: first_index + dir_entries.len(),
+            name,
+            offset: base_offset + file_offset,
+            size,
+            namespace: if marker_namespace.is_some() {
+                Namespace::Global
+            } else {
+                namespace
+            },
+        });
+
+        if let Some(marker_namespace) = marker_namespace {
+            namespace = marker_namespace;
+        }
+    }
+
+    Ok(dir_entries)
+}
+
+// A loaded IWAD, optionally with PWADs stacked on top of it. The data of all
+// files is concatenated into one buffer, so that directory entry offsets are
+// always offsets into `file`, regardless of which file the lump came from.
 pub struct WadFile {
     pub file: Vec<u8>,
-    pub header: Header,
     pub dirs_list: Vec<Rc<DirEntry>>,
-    pub dirs_map: HashMap<String, Rc<DirEntry>>,
-    pub first_sprite_lump: i16,
-    pub last_sprite_lump: i16,
+    pub dirs_map: HashMap<String, Rc<DirEntry>>, // All lumps, the last one wins
+    namespaces: HashMap<Namespace, HashMap<String, Rc<DirEntry>>>, // Lumps per namespace
 }
 
 impl WadFile {
     // Load a WAD file
-    pub fn new(file: Vec<u8>) -> WadFile {
-        let header = Header::read(&file);
-
-        // PWAD handling not implemented
-        if header.magic != "IWAD" {
-            panic!("Unhandled WAD file type: {}", header.magic);
-        }
-
+    pub fn new(file: Vec<u8>) -> Result<WadFile, WadError> {
         let mut wad_file = WadFile {
-            file,
-            header,
+            file: Vec::new(),
             dirs_list: Vec::new(),
             dirs_map: HashMap::new(),
-            first_sprite_lump: -1, // Populated later
-            last_sprite_lump: -1,  // Populated later
+            namespaces: HashMap::new(),
         };
 
-        wad_file.load_dirs();
-
-        wad_file.first_sprite_lump = wad_file.get_dir_entry("S_START").unwrap().index;
-        wad_file.last_sprite_lump = wad_file.get_dir_entry("S_END").unwrap().index;
+        wad_file.add_file(file)?;
 
-        wad_file
+        Ok(wad_file)
     }
 
-    // Read an 8 character null terminated string
-    pub fn read_lump_name(&self, offset: usize) -> String {
-        if self.file[offset + 7] == 0 {
-            // The lump name is null terminated
-            CStr::from_bytes_until_nul(&self.file[offset..offset + 8])
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string()
-        } else {
-            // The lump name is exactly 8 bytes long
-            str::from_utf8(&self.file[offset..offset + 8])
-                .unwrap()
-                .to_string()
-        }
-    }
-
-    fn load_dirs(&mut self) {
-        for i in 0..self.header.lump_count {
-            // A directory entry is 16 bytes long
-            let dir_entry_offset: usize = (self.header.dir_offset + i * 16).try_into().unwrap();
-
-            let offset = u32::from_le_bytes(
-                self.file[dir_entry_offset..dir_entry_offset + 4]
-                    .try_into()
-                    .unwrap(),
-            );
-
-            let size = u32::from_le_bytes(
-                self.file[dir_entry_offset + 4..dir_entry_offset + 8]
-                    .try_into()
-                    .unwrap(),
-            );
-
-            let name = self.read_lump_name(dir_entry_offset + 8);
-
-            let dir_entry = Rc::new(DirEntry {
-                index: i as i16,
-                name: name.clone().to_ascii_uppercase(),
-                offset,
-                size,
-            });
+    // Stack a PWAD on top of the already loaded files. Lumps in the PWAD override
+    // earlier lumps with the same name, like the -file option in doom does. The
+    // whole directory is checked first, so that a bad file leaves nothing behind.
+    pub fn add_file(&mut self, file: Vec<u8>) -> Result<(), WadError> {
+        let header = Header::read(&file)?;
+
+        let base_offset = self.file.len() as u32;
+        let dir_entries = read_dirs(&file, &header, base_offset, self.dirs_list.len())?;
+
+        self.file.extend(file);
+        for dir_entry in dir_entries {
+            let dir_entry = Rc::new(dir_entry);
+
+            // Later files override lumps with the same name
             self.dirs_map
-                .insert(name.clone().to_ascii_uppercase(), Rc::clone(&dir_entry));
+                .insert(dir_entry.name.clone(), Rc::clone(&dir_entry));
+            self.namespaces
+                .entry(dir_entry.namespace)
+                .or_default()
+                .insert(dir_entry.name.clone(), Rc::clone(&dir_entry));
             self.dirs_list.push(dir_entry);
         }
+
+        Ok(())
+    }
+
+    // Read an 8 character string, which is null terminated unless it's exactly
+    // 8 characters long.
+    pub fn read_lump_name(&self, offset: usize) -> String {
+        lump_name(&self.file[offset..offset + 8])
     }
 
     #[allow(dead_code)]
@@ -163,23 +355,93 @@ impl WadFile {
         }
     }
 
-    pub fn get_dir_entry(&self, name: &str) -> Result<&DirEntry, String> {
+    pub fn get_dir_entry(&self, name: &str) -> Result<&DirEntry, WadError> {
         if let Some(dir_entry) = self.dirs_map.get(&name.to_ascii_uppercase()) {
             return Ok(dir_entry);
         }
 
-        Err(format!("Could not find lump {}", &name))
+        Err(WadError::LumpNotFound {
+            name: name.to_ascii_uppercase(),
+            namespace: Namespace::Global,
+        })
     }
 
-    // Get lump for a map
-    pub fn get_dir_entry_for_map_lump(&self, map_name: &str, lump_name: MapLumpName) -> &DirEntry {
-        for (i, dir_entry) in self.dirs_list.iter().enumerate() {
-            if dir_entry.name == map_name.to_ascii_uppercase() {
-                return &self.dirs_list[i + lump_name as usize];
+    // Get a lump in a namespace. Patches are also looked up outside of the
+    // P_START/P_END markers, since vanilla doom doesn't require the markers and
+    // many PWADs don't use them for patches.
+    pub fn get_dir_entry_in_namespace(
+        &self,
+        name: &str,
+        namespace: Namespace,
+    ) -> Result<&DirEntry, WadError> {
+        let name = name.to_ascii_uppercase();
+
+        if let Some(dir_entry) = self
+            .namespaces
+            .get(&namespace)
+            .and_then(|entries| entries.get(&name))
+        {
+            return Ok(dir_entry);
+        }
+
+        if namespace == Namespace::Patches {
+            if let Some(dir_entry) = self
+                .namespaces
+                .get(&Namespace::Global)
+                .and_then(|entries| entries.get(&name))
+            {
+                return Ok(dir_entry);
             }
         }
 
-        panic!("Could not find lump {} in map {}", lump_name, map_name);
+        Err(WadError::LumpNotFound { name, namespace })
+    }
+
+    // Get all lumps in a namespace in directory order. Lumps that are overridden
+    // by a later lump with the same name are left out.
+    pub fn get_dir_entries_in_namespace(&self, namespace: Namespace) -> Vec<Rc<DirEntry>> {
+        let mut dir_entries: Vec<Rc<DirEntry>> = self
+            .namespaces
+            .get(&namespace)
+            .map(|entries| entries.values().cloned().collect())
+            .unwrap_or_default();
+
+        dir_entries.sort_by_key(|dir_entry| dir_entry.index);
+
+        dir_entries
+    }
+
+    // Get lump for a map. The last map marker wins, so that a map in a PWAD
+    // replaces the map with the same name in the IWAD.
+    pub fn get_dir_entry_for_map_lump(
+        &self,
+        map_name: &str,
+        lump_name: MapLumpName,
+    ) -> Result<&DirEntry, WadError> {
+        let not_found = || WadError::MapLumpNotFound {
+            map_name: map_name.to_string(),
+            lump_name: lump_name.to_string(),
+        };
+
+        let (i, _) = self
+            .dirs_list
+            .iter()
+            .enumerate()
+            .rev()
+            .find(|(_, dir_entry)| dir_entry.name == map_name.to_ascii_uppercase())
+            .ok_or_else(not_found)?;
+
+        // The lump must follow the map marker in the expected order
+        match self.dirs_list.get(i + lump_name as usize) {
+            Some(dir_entry) if dir_entry.name == lump_name.to_string() => Ok(dir_entry),
+            _ => Err(not_found()),
+        }
+    }
+
+    // Get the data of a lump
+    pub fn get_lump_data(&self, dir_entry: &DirEntry) -> &[u8] {
+        let offset = dir_entry.offset as usize;
+        &self.file[offset..offset + dir_entry.size as usize]
     }
 
     pub fn read_i16(&self, offset: usize) -> i16 {
@@ -190,7 +452,147 @@ impl WadFile {
         i16::from_le_bytes(self.file[offset..offset + 2].try_into().unwrap()) as f32
     }
 
+    pub fn read_u16(&self, offset: usize) -> u16 {
+        u16::from_le_bytes(self.file[offset..offset + 2].try_into().unwrap())
+    }
+
     pub fn read_u32(&self, offset: usize) -> u32 {
         u32::from_le_bytes(self.file[offset..offset + 4].try_into().unwrap())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wad_builder::{WadBuilder, WadType};
+
+    #[test]
+    fn test_truncated_header() {
+        assert!(matches!(
+            WadFile::new(b"IWAD".to_vec()),
+            Err(WadError::TruncatedHeader { size: 4 })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_magic() {
+        let mut file = WadBuilder::new(WadType::Iwad).write().unwrap();
+        file[0..4].copy_from_slice(b"JWAD");
+
+        assert!(matches!(
+            WadFile::new(file),
+            Err(WadError::InvalidMagic { .. })
+        ));
+    }
+
+    #[test]
+    fn test_directory_out_of_bounds() {
+        let mut wad_builder = WadBuilder::new(WadType::Iwad);
+        wad_builder.add_lump("DEMO1", vec![0; 4]).unwrap();
+        let mut file = wad_builder.write().unwrap();
+        file.truncate(file.len() - 1);
+
+        assert!(matches!(
+            WadFile::new(file),
+            Err(WadError::DirectoryOutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_lump_out_of_bounds() {
+        let mut wad_builder = WadBuilder::new(WadType::Iwad);
+        wad_builder.add_lump("DEMO1", vec![0; 4]).unwrap();
+        let mut file = wad_builder.write().unwrap();
+
+        // Make the lump size larger than the file
+        let size_offset = file.len() - 12;
+        file[size_offset..size_offset + 4].copy_from_slice(&1000u32.to_le_bytes());
+
+        match WadFile::new(file) {
+            Err(WadError::LumpOutOfBounds { name, size, .. }) => {
+                assert_eq!(name, "DEMO1");
+                assert_eq!(size, 1000);
+            }
+            _ => panic!("Expected a lump out of bounds"),
+        }
+    }
+
+    #[test]
+    fn test_bad_pwad_is_not_added() {
+        let mut wad_builder = WadBuilder::new(WadType::Iwad);
+        wad_builder.add_lump("DEMO1", vec![1]).unwrap();
+        let mut wad_file = WadFile::new(wad_builder.write().unwrap()).unwrap();
+        let file_size = wad_file.file.len();
+
+        // The first lump is fine, the second is out of bounds
+        let mut pwad_builder = WadBuilder::new(WadType::Pwad);
+        pwad_builder.add_lump("DEMO1", vec![2]).unwrap();
+        pwad_builder.add_lump("DEMO2", vec![0; 4]).unwrap();
+        let mut pwad = pwad_builder.write().unwrap();
+        let size_offset = pwad.len() - 12;
+        pwad[size_offset..size_offset + 4].copy_from_slice(&1000u32.to_le_bytes());
+
+        assert!(wad_file.add_file(pwad).is_err());
+        assert_eq!(wad_file.file.len(), file_size);
+        assert_eq!(wad_file.dirs_list.len(), 1);
+        assert!(wad_file.get_dir_entry("DEMO2").is_err());
+        let demo = wad_file.get_dir_entry("DEMO1").unwrap();
+        assert_eq!(wad_file.get_lump_data(demo), &[1]);
+    }
+
+    #[test]
+    fn test_namespaces() {
+        let mut wad_builder = WadBuilder::new(WadType::Iwad);
+        wad_builder.add_lump("FLAT1", vec![1]).unwrap();
+        wad_builder.add_marker("F_START").unwrap();
+        wad_builder.add_marker("F1_START").unwrap();
+        wad_builder.add_lump("FLAT1", vec![2]).unwrap();
+        wad_builder.add_marker("F1_END").unwrap();
+        wad_builder.add_marker("F_END").unwrap();
+        wad_builder.add_marker("S_START").unwrap();
+        wad_builder.add_lump("TROOA1", vec![3]).unwrap();
+        wad_builder.add_marker("S_END").unwrap();
+        let wad_file = WadFile::new(wad_builder.write().unwrap()).unwrap();
+
+        let flat = wad_file
+            .get_dir_entry_in_namespace("flat1", Namespace::Flats)
+            .unwrap();
+        assert_eq!(wad_file.get_lump_data(flat), &[2]);
+        assert!(wad_file
+            .get_dir_entry_in_namespace("TROOA1", Namespace::Flats)
+            .is_err());
+
+        let sprites = wad_file.get_dir_entries_in_namespace(Namespace::Sprites);
+        assert_eq!(sprites.len(), 1);
+        assert_eq!(sprites[0].name, "TROOA1");
+
+        // Patches fall back to the global namespace
+        let patch = wad_file
+            .get_dir_entry_in_namespace("FLAT1", Namespace::Patches)
+            .unwrap();
+        assert_eq!(wad_file.get_lump_data(patch), &[1]);
+    }
+
+    #[test]
+    fn test_more_lumps_than_i16() {
+        let mut wad_builder = WadBuilder::new(WadType::Iwad);
+        wad_builder.add_marker("S_START").unwrap();
+        wad_builder.add_lump("TROOA1", vec![1]).unwrap();
+        wad_builder.add_marker("S_END").unwrap();
+        for _ in 0..i16::MAX {
+            wad_builder.add_marker("DUMMY").unwrap();
+        }
+        let mut wad_file = WadFile::new(wad_builder.write().unwrap()).unwrap();
+
+        let mut pwad_builder = WadBuilder::new(WadType::Pwad);
+        pwad_builder.add_marker("S_START").unwrap();
+        pwad_builder.add_lump("TROOB1", vec![2]).unwrap();
+        pwad_builder.add_marker("S_END").unwrap();
+        wad_file.add_file(pwad_builder.write().unwrap()).unwrap();
+
+        // The sprites are still in directory order
+        let sprites = wad_file.get_dir_entries_in_namespace(Namespace::Sprites);
+        let names: Vec<&str> = sprites.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["TROOA1", "TROOB1"]);
+    }
+}
@@ -0,0 +1,293 @@
+use std::{fs, io};
+
+use crate::wad::{MapLumpName, Namespace, WadError, WadFile};
+
+// The type of WAD file to write
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WadType {
+    Iwad, // A complete game
+    Pwad, // Patches loaded on top of an IWAD
+}
+
+impl WadType {
+    fn magic(&self) -> &'static [u8; 4] {
+        match self {
+            WadType::Iwad => b"IWAD",
+            WadType::Pwad => b"PWAD",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lump {
+    pub name: String,  // Lump name, at most 8 characters
+    pub data: Vec<u8>, // Lump contents, empty for markers
+}
+
+// Build a WAD file in memory from a list of lumps and write it out. The lumps are
+// written in order, so map lumps and namespace markers must be added in the order
+// doom expects them.
+#[allow(dead_code)]
+#[derive(Debug, Clone)]
+pub struct WadBuilder {
+    pub wad_type: WadType,
+    lumps: Vec<Lump>,
+}
+
+#[allow(dead_code)]
+impl WadBuilder {
+    pub fn new(wad_type: WadType) -> WadBuilder {
+        WadBuilder {
+            wad_type,
+            lumps: Vec::new(),
+        }
+    }
+
+    // Copy all lumps from a loaded WAD file, in directory order. If PWADs are
+    // stacked on the WAD file, overridden lumps are included as well. Lump names
+    // that can't be written back, such as names with non-ASCII bytes, are an error.
+    pub fn from_wad_file(wad_file: &WadFile, wad_type: WadType) -> Result<WadBuilder, WadError> {
+        let lumps = wad_file
+            .dirs_list
+            .iter()
+            .map(|dir_entry| {
+                Ok(Lump {
+                    name: Self::check_lump_name(&dir_entry.name)?,
+                    data: wad_file.get_lump_data(dir_entry).to_vec(),
+                })
+            })
+            .collect::<Result<_, WadError>>()?;
+
+        Ok(WadBuilder { wad_type, lumps })
+    }
+
+    pub fn lumps(&self) -> &[Lump] {
+        &self.lumps
+    }
+
+    // Add a lump at the end of the directory
+    pub fn add_lump(&mut self, name: &str, data: Vec<u8>) -> Result<&mut Self, WadError> {
+        self.insert_lump(self.lumps.len(), name, data)
+    }
+
+    // Add a zero-sized lump, such as a map or namespace marker
+    pub fn add_marker(&mut self, name: &str) -> Result<&mut Self, WadError> {
+        self.add_lump(name, Vec::new())
+    }
+
+    // Insert a lump before the lump at index
+    pub fn insert_lump(
+        &mut self,
+        index: usize,
+        name: &str,
+        data: Vec<u8>,
+    ) -> Result<&mut Self, WadError> {
+        let name = Self::check_lump_name(name)?;
+        self.lumps.insert(index, Lump { name, data });
+
+        Ok(self)
+    }
+
+    // Replace the data of a lump. Like the reader, the last lump with the name is
+    // the one that is used.
+    pub fn replace_lump(&mut self, name: &str, data: Vec<u8>) -> Result<&mut Self, WadError> {
+        let index = self.find_lump(name)?;
+        self.lumps[index].data = data;
+
+        Ok(self)
+    }
+
+    // Replace the data of a lump that belongs to a map. Map lumps have the same
+    // names in every map, so they are found relative to the map marker.
+    pub fn replace_map_lump(
+        &mut self,
+        map_name: &str,
+        lump_name: MapLumpName,
+        data: Vec<u8>,
+    ) -> Result<&mut Self, WadError> {
+        let not_found = || WadError::MapLumpNotFound {
+            map_name: map_name.to_string(),
+            lump_name: lump_name.to_string(),
+        };
+
+        let map_index = self.find_lump(map_name).map_err(|_| not_found())?;
+        match self.lumps.get_mut(map_index + lump_name as usize) {
+            Some(lump) if lump.name == lump_name.to_string() => lump.data = data,
+            _ => return Err(not_found()),
+        }
+
+        Ok(self)
+    }
+
+    // Remove the last lump with the name and return it
+    pub fn remove_lump(&mut self, name: &str) -> Result<Lump, WadError> {
+        let index = self.find_lump(name)?;
+
+        Ok(self.lumps.remove(index))
+    }
+
+    // Write the header, followed by the lump data and the directory
+    pub fn write(&self) -> Result<Vec<u8>, WadError> {
+        let data_size: usize = self.lumps.iter().map(|lump| lump.data.len()).sum();
+        let dir_offset = 12 + data_size;
+
+        let mut file = Vec::with_capacity(dir_offset + self.lumps.len() * 16);
+        file.extend(self.wad_type.magic());
+        file.extend((self.lumps.len() as u32).to_le_bytes());
+        file.extend((dir_offset as u32).to_le_bytes());
+
+        for lump in &self.lumps {
+            file.extend(&lump.data);
+        }
+
+        // A directory entry is 16 bytes long. Names shorter than 8 characters
+        // are padded with nul bytes.
+        let mut offset = 12;
+        for lump in &self.lumps {
+            let lump_name = Self::check_lump_name(&lump.name)?;
+            let mut name = [0u8; 8];
+            name[..lump_name.len()].copy_from_slice(lump_name.as_bytes());
+
+            file.extend((offset as u32).to_le_bytes());
+            file.extend((lump.data.len() as u32).to_le_bytes());
+            file.extend(name);
+
+            offset += lump.data.len();
+        }
+
+        Ok(file)
+    }
+
+    // Write the WAD to a file on disk
+    pub fn save(&self, filename: &str) -> Result<(), io::Error> {
+        let file = self
+            .write()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+        fs::write(filename, file)
+    }
+
+    fn find_lump(&self, name: &str) -> Result<usize, WadError> {
+        let name = name.to_ascii_uppercase();
+
+        self.lumps
+            .iter()
+            .rposition(|lump| lump.name == name)
+            .ok_or(WadError::LumpNotFound {
+                name,
+                namespace: Namespace::Global,
+            })
+    }
+
+    // Lump names are stored uppercase in 8 bytes
+    fn check_lump_name(name: &str) -> Result<String, WadError> {
+        if name.is_empty() || name.len() > 8 || !name.bytes().all(|b| b.is_ascii_graphic()) {
+            return Err(WadError::InvalidLumpName {
+                name: name.to_string(),
+            });
+        }
+
+        Ok(name.to_ascii_uppercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_maps::{square_room, TEST_MAP_NAME};
+
+    #[test]
+    fn test_round_trip() {
+        let wad_file = square_room().build();
+        let written = WadBuilder::from_wad_file(&wad_file, WadType::Iwad)
+            .unwrap()
+            .write()
+            .unwrap();
+        let reread = WadFile::new(written).unwrap();
+
+        assert_eq!(reread.dirs_list.len(), wad_file.dirs_list.len());
+        for (dir_entry, reread_dir_entry) in wad_file.dirs_list.iter().zip(&reread.dirs_list) {
+            assert_eq!(dir_entry.name, reread_dir_entry.name);
+            assert_eq!(
+                wad_file.get_lump_data(dir_entry),
+                reread.get_lump_data(reread_dir_entry)
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_header() {
+        let mut wad_builder = WadBuilder::new(WadType::Pwad);
+        wad_builder.add_lump("demo1", vec![1, 2, 3]).unwrap();
+        let written = wad_builder.write().unwrap();
+
+        assert_eq!(&written[0..4], b"PWAD");
+        assert_eq!(u32::from_le_bytes(written[4..8].try_into().unwrap()), 1);
+        assert_eq!(u32::from_le_bytes(written[8..12].try_into().unwrap()), 15);
+        assert_eq!(&written[12..15], &[1, 2, 3]);
+        assert_eq!(&written[23..31], b"DEMO1\0\0\0");
+    }
+
+    #[test]
+    fn test_replace_and_remove_lumps() {
+        let wad_file = square_room().build();
+        let mut wad_builder = WadBuilder::from_wad_file(&wad_file, WadType::Pwad).unwrap();
+
+        wad_builder
+            .replace_map_lump(TEST_MAP_NAME, MapLumpName::Things, Vec::new())
+            .unwrap();
+        wad_builder.add_lump("EXTRA", vec![0; 4]).unwrap();
+        let removed = wad_builder.remove_lump("REJECT").unwrap();
+        assert_eq!(removed.name, "REJECT");
+
+        let reread = WadFile::new(wad_builder.write().unwrap()).unwrap();
+        assert_eq!(reread.get_dir_entry("EXTRA").unwrap().size, 4);
+        assert!(reread.get_dir_entry("REJECT").is_err());
+        assert_eq!(
+            reread
+                .get_dir_entry_for_map_lump(TEST_MAP_NAME, MapLumpName::Things)
+                .unwrap()
+                .size,
+            0
+        );
+    }
+
+    #[test]
+    fn test_pwad_overrides_lump() {
+        let mut iwad = WadBuilder::new(WadType::Iwad);
+        iwad.add_lump("PLAYPAL", vec![1]).unwrap();
+        let mut pwad = WadBuilder::new(WadType::Pwad);
+        pwad.add_lump("PLAYPAL", vec![2]).unwrap();
+
+        let mut wad_file = WadFile::new(iwad.write().unwrap()).unwrap();
+        wad_file.add_file(pwad.write().unwrap()).unwrap();
+
+        let dir_entry = wad_file.get_dir_entry("PLAYPAL").unwrap();
+        assert_eq!(wad_file.get_lump_data(dir_entry), &[2]);
+    }
+
+    #[test]
+    fn test_invalid_lump_names() {
+        let mut wad_builder = WadBuilder::new(WadType::Pwad);
+
+        assert!(wad_builder.add_lump("", Vec::new()).is_err());
+        assert!(wad_builder.add_lump("TOOLONGNAME", Vec::new()).is_err());
+        assert!(wad_builder.add_lump("A B", Vec::new()).is_err());
+        assert!(wad_builder.replace_lump("MISSING", Vec::new()).is_err());
+
+        // A name that isn't ASCII can't be copied from a WAD file
+        let mut file = wad_builder
+            .add_lump("DEMO1", Vec::new())
+            .unwrap()
+            .write()
+            .unwrap();
+        let name_offset = file.len() - 8;
+        file[name_offset] = 0xff;
+        let wad_file = WadFile::new(file).unwrap();
+        assert!(matches!(
+            WadBuilder::from_wad_file(&wad_file, WadType::Pwad),
+            Err(WadError::InvalidLumpName { .. })
+        ));
+    }
+}